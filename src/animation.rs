@@ -0,0 +1,245 @@
+use crate::physics::{Quaternion, Vec3};
+
+// A value that can be animated, blending between two of its states. Rotations blend along the
+// shortest arc between them.
+pub(crate) trait Animatable: Clone {
+    // The state a fraction `t` of the way from `self` to `other`. `t` may lie outside [0, 1],
+    // carrying on past either end.
+    fn lerp(&self, other: &Self, t: f64) -> Self;
+}
+
+impl Animatable for f64 {
+    fn lerp(&self, other: &Self, t: f64) -> Self {
+        (1.0 - t) * self + t * other
+    }
+}
+
+impl Animatable for Vec3 {
+    fn lerp(&self, other: &Self, t: f64) -> Self {
+        (1.0 - t) * self + t * other
+    }
+}
+
+impl Animatable for Quaternion {
+    fn lerp(&self, other: &Self, t: f64) -> Self {
+        self.slerp(other, t)
+    }
+}
+
+// How a track moves from a keyframe to the next.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Interpolation {
+    // At a constant rate, which for rotations is a slerp.
+    Linear,
+    // Along a cubic Bezier curve through the keyframes, with handles pointing from the previous
+    // keyframe to the next one. The first and last keyframes have their handles on them, so that
+    // the motion eases in and out there.
+    Bezier,
+    // Not at all, holding the keyframe's value until the next one.
+    Step,
+}
+
+#[derive(Clone, Debug)]
+struct Key<T> {
+    time: f64,
+    value: T,
+    // How the track leaves this keyframe.
+    interpolation: Interpolation,
+}
+
+// A value changing over time through keyframes. It holds the first keyframe's value before it
+// and the last one's after.
+#[derive(Clone, Debug)]
+pub(crate) struct Track<T> {
+    // In increasing order of time.
+    keys: Vec<Key<T>>,
+}
+
+impl<T: Animatable> Track<T> {
+    pub(crate) fn new(time: f64, value: T, interpolation: Interpolation) -> Self {
+        Track {
+            keys: vec![Key {
+                time,
+                value,
+                interpolation,
+            }],
+        }
+    }
+
+    // Adds a keyframe, replacing any at the same time.
+    pub(crate) fn key(mut self, time: f64, value: T, interpolation: Interpolation) -> Self {
+        let key = Key {
+            time,
+            value,
+            interpolation,
+        };
+        let i = self.keys.partition_point(|k| k.time < time);
+        if self.keys.get(i).is_some_and(|k| k.time == time) {
+            self.keys[i] = key;
+        } else {
+            self.keys.insert(i, key);
+        }
+        self
+    }
+
+    // Times of the keyframes, in increasing order.
+    pub(crate) fn times(&self) -> impl Iterator<Item = f64> + '_ {
+        self.keys.iter().map(|k| k.time)
+    }
+
+    pub(crate) fn at(&self, time: f64) -> T {
+        let i = self.keys.partition_point(|k| k.time <= time);
+        if i == 0 {
+            return self.keys[0].value.clone();
+        }
+        if i == self.keys.len() {
+            return self.keys[i - 1].value.clone();
+        }
+
+        let (k1, k2) = (&self.keys[i - 1], &self.keys[i]);
+        let t = (time - k1.time) / (k2.time - k1.time);
+        match k1.interpolation {
+            Interpolation::Linear => k1.value.lerp(&k2.value, t),
+            Interpolation::Step => k1.value.clone(),
+            Interpolation::Bezier => {
+                // Handles a third of the way to the neighbouring keyframes, along the tangent
+                // that a curve through the previous and next keyframes would have.
+                let span = k2.time - k1.time;
+                let c1 = match i.checked_sub(2).map(|j| &self.keys[j]) {
+                    Some(k0) => handle(
+                        &k0.value,
+                        &k1.value,
+                        &k2.value,
+                        span / (3.0 * (k2.time - k0.time)),
+                    ),
+                    None => k1.value.clone(),
+                };
+                let c2 = match self.keys.get(i + 1) {
+                    Some(k3) => handle(
+                        &k3.value,
+                        &k2.value,
+                        &k1.value,
+                        span / (3.0 * (k3.time - k1.time)),
+                    ),
+                    None => k2.value.clone(),
+                };
+
+                // De Casteljau's construction, which only needs blends, so rotations follow a
+                // spherical curve.
+                let (a, b, c) = (
+                    k1.value.lerp(&c1, t),
+                    c1.lerp(&c2, t),
+                    c2.lerp(&k2.value, t),
+                );
+                let (d, e) = (a.lerp(&b, t), b.lerp(&c, t));
+                d.lerp(&e, t)
+            }
+        }
+    }
+}
+
+// The point `k` times the step from `previous` to `next` away from `value`, built from blends.
+fn handle<T: Animatable>(previous: &T, value: &T, next: &T, k: f64) -> T {
+    let ahead = value.lerp(next, 2.0 * k);
+    let behind = previous.lerp(value, 1.0 + 2.0 * k);
+    ahead.lerp(&behind, 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn holds_the_end_values_outside_the_keyframes() {
+        let track =
+            Track::new(1.0, 2.0, Interpolation::Linear).key(3.0, 6.0, Interpolation::Linear);
+        assert_eq!(track.at(-5.0), 2.0);
+        assert_eq!(track.at(1.0), 2.0);
+        assert_eq!(track.at(3.0), 6.0);
+        assert_eq!(track.at(10.0), 6.0);
+        assert_eq!(Track::new(0.0, 7.0, Interpolation::Bezier).at(1.0), 7.0);
+    }
+
+    #[test]
+    fn interpolates_linearly() {
+        let track = Track::new(0.0, Vec3::new(0.0, 0.0, 0.0), Interpolation::Linear).key(
+            2.0,
+            Vec3::new(4.0, -2.0, 0.0),
+            Interpolation::Linear,
+        );
+        let p = track.at(0.5);
+        assert_eq!((p.x(), p.y(), p.z()), (1.0, -0.5, 0.0));
+    }
+
+    #[test]
+    fn steps_to_the_next_keyframe() {
+        let track = Track::new(0.0, 1.0, Interpolation::Step).key(1.0, 5.0, Interpolation::Linear);
+        assert_eq!(track.at(0.999), 1.0);
+        assert_eq!(track.at(1.0), 5.0);
+    }
+
+    #[test]
+    fn sorts_keyframes_and_replaces_those_at_the_same_time() {
+        let track = Track::new(2.0, 20.0, Interpolation::Linear)
+            .key(0.0, 0.0, Interpolation::Linear)
+            .key(1.0, 10.0, Interpolation::Step)
+            .key(1.0, 5.0, Interpolation::Linear);
+        assert_eq!(track.times().collect::<Vec<_>>(), [0.0, 1.0, 2.0]);
+        assert_eq!(track.at(1.0), 5.0);
+        // The replacement's interpolation applies too.
+        assert_eq!(track.at(1.5), 12.5);
+    }
+
+    #[test]
+    fn eases_in_and_out_at_the_ends_of_a_bezier_track() {
+        let track =
+            Track::new(0.0, 0.0, Interpolation::Bezier).key(1.0, 1.0, Interpolation::Bezier);
+        // With both handles on their keyframes the curve is a smoothstep.
+        for t in [0.0, 0.1, 0.25, 0.5, 0.9] {
+            assert_near(track.at(t), t * t * (3.0 - 2.0 * t));
+        }
+    }
+
+    #[test]
+    fn keeps_the_rate_across_inner_bezier_keyframes() {
+        // Values in proportion to time, with keyframes unevenly spaced: the handles on the middle
+        // keyframe point along a tangent of slope one, whichever segment they're in.
+        let track = Track::new(0.0, 0.0, Interpolation::Bezier)
+            .key(1.0, 1.0, Interpolation::Bezier)
+            .key(3.0, 3.0, Interpolation::Bezier);
+        assert_near(track.at(1.0), 1.0);
+        let h = 1e-4;
+        assert!((track.at(1.0 + h) - track.at(1.0 - h) - 2.0 * h).abs() < 1e-7);
+        // Handles at 0 and 2/3 in the first segment, and at 5/3 and 3 in the second.
+        assert_near(track.at(0.5), 0.375);
+        assert_near(track.at(2.0), 2.25);
+    }
+
+    #[test]
+    fn turns_rotations_along_a_bezier_track() {
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let start = Quaternion::default();
+        let track = Track::new(0.0, start, Interpolation::Bezier).key(
+            1.0,
+            Quaternion::from_axis_angle(&y, 90.0),
+            Interpolation::Linear,
+        );
+        for t in [0.25, 0.5] {
+            assert_near(
+                track.at(t).angle_to(&start),
+                FRAC_PI_2 * t * t * (3.0 - 2.0 * t),
+            );
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{
+    image::Image,
+    physics::Color,
+    utils::{hash_f64, Interval},
+};
+
+// Render pass written alongside the image. Each is kept as an image of colours, of which
+// passes with a single channel use the first component.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Aov {
+    // Distance along the camera ray to the first surface.
+    Depth,
+    // World space shading normal of the first surface, facing the camera.
+    Normal,
+    // World space position of the first surface.
+    Position,
+    Albedo,
+    // Index of the object in the scene's world, from one, zero for the background. A mesh or
+    // BVH added as a whole is one object.
+    ObjectId,
+    // Materials numbered from one in the order the render comes across them.
+    MaterialId,
+    // The image split up by the path light took: emitted by the surfaces and background the
+    // camera sees, or scattered off the first surface straight from a light or after further
+    // bounces, diffusely or specularly. Together they add up to the image.
+    Emission,
+    DirectDiffuse,
+    DirectSpecular,
+    IndirectDiffuse,
+    IndirectSpecular,
+    // Variance of the pixel's estimate of the image's luminance, falling as samples are added.
+    Variance,
+}
+
+pub(crate) const AOV_NAMES: &[&str] = &[
+    "depth",
+    "normal",
+    "position",
+    "albedo",
+    "object_id",
+    "material_id",
+    "emission",
+    "direct_diffuse",
+    "direct_specular",
+    "indirect_diffuse",
+    "indirect_specular",
+    "variance",
+];
+
+const AOVS: [Aov; 12] = [
+    Aov::Depth,
+    Aov::Normal,
+    Aov::Position,
+    Aov::Albedo,
+    Aov::ObjectId,
+    Aov::MaterialId,
+    Aov::Emission,
+    Aov::DirectDiffuse,
+    Aov::DirectSpecular,
+    Aov::IndirectDiffuse,
+    Aov::IndirectSpecular,
+    Aov::Variance,
+];
+
+impl Aov {
+    pub(crate) fn by_name(name: &str) -> Option<Self> {
+        AOV_NAMES.iter().position(|&n| n == name).map(|i| AOVS[i])
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        AOV_NAMES[self as usize]
+    }
+
+    // Names of the channels of the pass in an OpenEXR file.
+    pub(crate) fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::Variance => &["V"],
+            _ => &["R", "G", "B"],
+        }
+    }
+
+    // Whether the pass is part of the split of the image's light, rather than a property of the
+    // first surface.
+    pub(crate) fn is_light(self) -> bool {
+        matches!(
+            self,
+            Aov::Emission
+                | Aov::DirectDiffuse
+                | Aov::DirectSpecular
+                | Aov::IndirectDiffuse
+                | Aov::IndirectSpecular
+        )
+    }
+
+    // The pass brought into the range of an 8-bit image for viewing: depth, positions and
+    // variance scaled to fit, normals from [-1, 1], and a distinct colour for each ID.
+    pub(crate) fn visualize(self, image: &Image) -> Image {
+        let pixels = (0..image.height())
+            .flat_map(|y| (0..image.width()).map(move |x| image.pixel(x, y)))
+            .cloned();
+        let extent = |component: fn(&Color) -> f64| {
+            (0..image.height())
+                .flat_map(|y| (0..image.width()).map(move |x| component(image.pixel(x, y))))
+                .fold(Interval::default(), |i, v| {
+                    Interval::new(i.min.min(v), i.max.max(v))
+                })
+        };
+        let fit = |v: f64, interval: &Interval| {
+            if interval.size() > 0.0 {
+                (v - interval.min) / interval.size()
+            } else {
+                0.0
+            }
+        };
+
+        let pixels = match self {
+            Aov::Depth | Aov::Variance => {
+                let max = extent(Color::r).max;
+                pixels
+                    .map(|c| {
+                        let v = if max > 0.0 { c.r() / max } else { 0.0 };
+                        Color::new(v, v, v)
+                    })
+                    .collect()
+            }
+            Aov::Normal => pixels
+                .map(|c| {
+                    Color::new(
+                        0.5 * (c.r() + 1.0),
+                        0.5 * (c.g() + 1.0),
+                        0.5 * (c.b() + 1.0),
+                    )
+                })
+                .collect(),
+            Aov::Position => {
+                let (x, y, z) = (extent(Color::r), extent(Color::g), extent(Color::b));
+                pixels
+                    .map(|c| Color::new(fit(c.r(), &x), fit(c.g(), &y), fit(c.b(), &z)))
+                    .collect()
+            }
+            Aov::ObjectId | Aov::MaterialId => pixels
+                .map(|c| {
+                    if c.r() == 0.0 {
+                        return Color::new(0.0, 0.0, 0.0);
+                    }
+                    let channel = |k: f64| 0.2 + 0.8 * hash_f64(&[c.r(), k]);
+                    Color::new(channel(0.0), channel(1.0), channel(2.0))
+                })
+                .collect(),
+            _ => pixels.collect(),
+        };
+        Image::new(image.width(), image.height(), pixels)
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    f64::consts::PI,
+    io::{Error, ErrorKind},
+    sync::Arc,
+};
+
+use crate::{
+    image::Image,
+    lights::Distribution2D,
+    physics::Vec3,
+    utils::{degrees_to_radians, random_f64},
+};
+
+// Shape of the opening light passes through on its way to the film, in coordinates where it
+// spans the unit disk, x to the right and y up. Out of focus highlights take on this shape.
+#[derive(Clone, Debug)]
+pub(crate) enum Aperture {
+    Circle,
+    // Regular polygon inscribed in the unit circle, as formed by `blades` straight diaphragm
+    // blades, with a corner `rotation` radians anticlockwise from the right.
+    Polygon { blades: usize, rotation: f64 },
+    // Grayscale image stretched over the square around the unit disk, passing light in
+    // proportion to its luminance.
+    Mask(Arc<ApertureMask>),
+}
+
+#[derive(Debug)]
+pub(crate) struct ApertureMask {
+    image: Image,
+    distribution: Distribution2D,
+}
+
+impl Aperture {
+    // Polygon of `blades` sides turned `rotation` degrees. Fewer than three blades leave the
+    // aperture round.
+    pub(crate) fn polygon(blades: usize, rotation: f64) -> Self {
+        if blades < 3 {
+            return Aperture::Circle;
+        }
+        Aperture::Polygon {
+            blades,
+            rotation: degrees_to_radians(rotation),
+        }
+    }
+
+    pub(crate) fn mask(image: Image) -> Result<Self, Error> {
+        let func: Vec<f64> = (0..image.height())
+            .flat_map(|y| (0..image.width()).map(move |x| (x, y)))
+            .map(|(x, y)| image.pixel(x, y).luminance().max(0.0))
+            .collect();
+        if func.iter().all(|&f| f == 0.0) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "aperture mask lets no light through",
+            ));
+        }
+        let distribution = Distribution2D::new(&func, image.width(), image.height());
+        Ok(Aperture::Mask(Arc::new(ApertureMask {
+            image,
+            distribution,
+        })))
+    }
+
+    pub(crate) fn load_mask(path: &str) -> Result<Self, Error> {
+        Self::mask(Image::load(path)?)
+    }
+
+    // Samples a point of the aperture, uniformly over its area or in proportion to a mask.
+    pub(crate) fn sample(&self) -> (f64, f64) {
+        match self {
+            Aperture::Circle => {
+                let p = Vec3::new_random_in_unit_disk();
+                (p.x(), p.y())
+            }
+            Aperture::Polygon { blades, rotation } => {
+                // The polygon is a fan of equal triangles around the center.
+                let n = *blades as f64;
+                let k = (random_f64() * n).floor();
+                let (a0, a1) = (
+                    rotation + 2.0 * PI * k / n,
+                    rotation + 2.0 * PI * (k + 1.0) / n,
+                );
+                let (mut s, mut t) = (random_f64(), random_f64());
+                if s + t > 1.0 {
+                    (s, t) = (1.0 - s, 1.0 - t);
+                }
+                (s * a0.cos() + t * a1.cos(), s * a0.sin() + t * a1.sin())
+            }
+            Aperture::Mask(mask) => {
+                let ((u, v), _) = mask.distribution.sample((random_f64(), random_f64()));
+                (2.0 * u - 1.0, 1.0 - 2.0 * v)
+            }
+        }
+    }
+
+    // Whether the point lets light through. Masks pass light where they are at least half
+    // transparent.
+    pub(crate) fn contains(&self, x: f64, y: f64) -> bool {
+        match self {
+            Aperture::Circle => x * x + y * y <= 1.0,
+            Aperture::Polygon { blades, rotation } => {
+                // Distance along the middle of the point's sector against the polygon's apothem.
+                let sector = 2.0 * PI / *blades as f64;
+                let angle = (y.atan2(x) - rotation).rem_euclid(sector) - 0.5 * sector;
+                x.hypot(y) * angle.cos() <= (0.5 * sector).cos()
+            }
+            Aperture::Mask(mask) => {
+                let (width, height) = (mask.image.width(), mask.image.height());
+                if x.abs() > 1.0 || y.abs() > 1.0 {
+                    return false;
+                }
+                let i = ((0.5 * (x + 1.0) * width as f64) as usize).min(width - 1);
+                let j = ((0.5 * (1.0 - y) * height as f64) as usize).min(height - 1);
+                mask.image.pixel(i, j).luminance() >= 0.5
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::physics::Color;
+
+// Sums of the samples splatted onto a band of whole rows of the image: their colours and passes
+// weighted by the reconstruction filter, and the filter's weights. Pixels are indexed as in the
+// whole image.
+pub(crate) struct Film {
+    // Index of the band's first pixel in the image.
+    offset: usize,
+    pub(crate) pixels: Vec<Color>,
+    pub(crate) weights: Vec<f64>,
+    pub(crate) passes: Vec<Vec<Color>>,
+}
+
+impl Film {
+    // Band of `rows` rows from `first_row` down, in an image `width` pixels wide.
+    pub(crate) fn new(first_row: usize, rows: usize, width: usize, passes: usize) -> Self {
+        let size = rows * width;
+        let black = Color::new(0.0, 0.0, 0.0);
+        Film {
+            offset: first_row * width,
+            pixels: vec![black.clone(); size],
+            weights: vec![0.0; size],
+            passes: vec![vec![black; size]; passes],
+        }
+    }
+
+    // Adds a sample of colour `value` to the image's pixel `p` with filter weight `weight`. A
+    // sample that misses the image still adds its weight, as black.
+    pub(crate) fn splat(&mut self, p: usize, weight: f64, value: &Color) {
+        let p = p - self.offset;
+        self.weights[p] += weight;
+        self.pixels[p] += &(weight * value.clone());
+    }
+
+    // Adds an already weighted value to pass `k` at pixel `p`.
+    pub(crate) fn splat_pass(&mut self, k: usize, p: usize, value: &Color) {
+        let p = p - self.offset;
+        self.passes[k][p] += value;
+    }
+
+    // Sets pass `k` at pixel `p`, for the passes worked out from each pixel's own samples.
+    pub(crate) fn set_pass(&mut self, k: usize, p: usize, value: Color) {
+        let p = p - self.offset;
+        self.passes[k][p] = value;
+    }
+
+    // Adds the sums of `band`, which has to lie within this film.
+    pub(crate) fn add(&mut self, band: &Film) {
+        let start = band.offset - self.offset;
+        let end = start + band.weights.len();
+        for (sum, value) in self.pixels[start..end].iter_mut().zip(&band.pixels) {
+            *sum += value;
+        }
+        for (sum, weight) in self.weights[start..end].iter_mut().zip(&band.weights) {
+            *sum += weight;
+        }
+        for (pass, band_pass) in self.passes.iter_mut().zip(&band.passes) {
+            for (sum, value) in pass[start..end].iter_mut().zip(band_pass) {
+                *sum += value;
+            }
+        }
+    }
+}
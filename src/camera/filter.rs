@@ -0,0 +1,94 @@
+use std::f64::consts::PI;
+
+// Reconstruction filter weighing the samples around a pixel's center into its value. Each
+// weight is the product of the filter at the sample's horizontal and vertical distance, in
+// pixels, from the center.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Filter {
+    // Equal weight within the radius. At half a pixel each pixel averages its own samples.
+    Box,
+    // Falling linearly to zero at the radius.
+    Tent,
+    // Gaussian with a standard deviation of a third of the radius, shifted down to reach zero
+    // there.
+    Gaussian,
+    // The cubic of Mitchell and Netravali with B = C = 1/3, stretched over the radius. Its
+    // negative lobes sharpen the image slightly.
+    Mitchell,
+    // Sinc windowed by a sinc as wide as the radius, as sharp as the filters get, at the cost of
+    // ringing around edges.
+    Lanczos,
+}
+
+pub(crate) const FILTER_NAMES: &[&str] = &["box", "tent", "gaussian", "mitchell", "lanczos"];
+
+const FILTERS: [Filter; 5] = [
+    Filter::Box,
+    Filter::Tent,
+    Filter::Gaussian,
+    Filter::Mitchell,
+    Filter::Lanczos,
+];
+
+impl Filter {
+    pub(crate) fn by_name(name: &str) -> Option<Self> {
+        FILTER_NAMES
+            .iter()
+            .position(|&n| n == name)
+            .map(|i| FILTERS[i])
+    }
+
+    // Radius in pixels the filter is usually given.
+    pub(crate) fn default_radius(self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+            Filter::Lanczos => 3.0,
+        }
+    }
+
+    // The filter at `x` pixels from the center, zero beyond `radius`.
+    pub(crate) fn evaluate(self, x: f64, radius: f64) -> f64 {
+        let x = x.abs();
+        if x > radius {
+            return 0.0;
+        }
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => radius - x,
+            Filter::Gaussian => {
+                let sigma = radius / 3.0;
+                let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                gaussian(x) - gaussian(radius)
+            }
+            Filter::Mitchell => {
+                const B: f64 = 1.0 / 3.0;
+                const C: f64 = 1.0 / 3.0;
+                let x = 2.0 * x / radius;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * B - 6.0 * C) * x.powi(3)
+                        + (-18.0 + 12.0 * B + 6.0 * C) * x * x
+                        + (6.0 - 2.0 * B))
+                        / 6.0
+                } else {
+                    ((-B - 6.0 * C) * x.powi(3)
+                        + (6.0 * B + 30.0 * C) * x * x
+                        + (-12.0 * B - 48.0 * C) * x
+                        + (8.0 * B + 24.0 * C))
+                        / 6.0
+                }
+            }
+            Filter::Lanczos => sinc(x) * sinc(x / radius),
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
@@ -0,0 +1,551 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+};
+
+use crate::{
+    camera::Aperture,
+    physics::{Ray, Vec3},
+    utils::random_f64,
+};
+
+// Double-Gauss 50mm f/2, US patent 2,673,491 (Tronnier), scaled from 100mm.
+const DGAUSS_50MM: &str = "
+29.475   3.76   1.67   25.2
+84.83    0.12   1      25.2
+19.275   4.025  1.67   23
+40.77    3.275  1.699  23
+12.75    5.705  1      18
+0        4.5    0      17.1
+-14.495  1.18   1.603  17
+40.77    6.065  1.658  20
+-20.385  0.19   1      20
+437.065  3.22   1.717  20
+-39.73   0      1      20
+";
+
+// Wide-angle 22mm, Nakamura, scaled from 100mm.
+const WIDE_22MM: &str = "
+35.98738   1.21638  1.54   23.716
+11.69718   9.9957   1      17.996
+13.08714   5.12622  1.772  12.364
+-22.63294  1.76924  1.617  9.812
+71.05802   0.8184   1      9.152
+0          2.27766  0      8.756
+-9.58584   2.43254  1.617  8.184
+-11.28864  0.11506  1      9.152
+-166.7765  3.09606  1.713  10.648
+-7.5911    1.32682  1.805  11.44
+-16.7662   3.98068  1      12.276
+-7.70286   1.21638  1.617  13.42
+-11.97328  0        1      17.996
+";
+
+// Telephoto 250mm.
+const TELEPHOTO_250MM: &str = "
+54.6275   12.52    1.529  47.5
+-86.365   3.755    1.599  44.5
+271.7625  2.8175   1      41.5
+0         67.4125  0      40.5
+-32.13    3.755    1.613  31.5
+49.5325   12.52    1.603  33.5
+-50.945   0        1      37
+";
+
+pub(crate) const LENS_NAMES: &[&str] = &["dgauss50", "wide22", "telephoto250"];
+
+// Film points at increasing distances from the center for which the exit pupil is bounded, and
+// the resolution of the grid of rays bounding it.
+const PUPIL_SEGMENTS: usize = 32;
+const PUPIL_GRID: usize = 128;
+
+// One surface of a lens prescription, in scene units.
+#[derive(Clone, Debug)]
+struct LensElement {
+    // Radius of curvature, positive when its center lies towards the film, or zero for the
+    // aperture stop.
+    radius: f64,
+    // Distance along the axis to the next surface, or to the film for the last one.
+    thickness: f64,
+    // Refractive index behind the surface, towards the film. Zero stands for air.
+    eta: f64,
+    // Radius of the clear aperture.
+    aperture: f64,
+    // Shape of the aperture stop's opening, when it isn't round.
+    shape: Option<Aperture>,
+}
+
+// A lens prescription as tabulated in lens patents and design books: one surface per row from
+// the front of the lens to the back, giving its radius of curvature, the thickness to the next
+// surface, the refractive index after it and the aperture diameter, all in millimeters. The
+// aperture stop is the row with zero radius.
+#[derive(Clone, Debug)]
+pub(crate) struct LensSystem {
+    elements: Vec<LensElement>,
+}
+
+impl LensSystem {
+    // One of the prescriptions in `LENS_NAMES`.
+    pub(crate) fn by_name(name: &str) -> Option<Self> {
+        let text = match name {
+            "dgauss50" => DGAUSS_50MM,
+            "wide22" => WIDE_22MM,
+            "telephoto250" => TELEPHOTO_250MM,
+            _ => return None,
+        };
+        Self::parse(text, name).ok()
+    }
+
+    pub(crate) fn load(path: &str) -> Result<Self, Error> {
+        Self::parse(&fs::read_to_string(path)?, path)
+    }
+
+    // Parses a prescription, one surface per line with '#' starting a comment. `name` only
+    // appears in error messages.
+    pub(crate) fn parse(text: &str, name: &str) -> Result<Self, Error> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, format!("{}: {}", name, msg));
+
+        // Scene units are taken to be meters.
+        const MM: f64 = 0.001;
+        let mut elements = Vec::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let values = line
+                .split_whitespace()
+                .map(|v| v.parse::<f64>().map_err(|_| invalid("bad number")))
+                .collect::<Result<Vec<_>, _>>()?;
+            let [radius, thickness, eta, diameter] = values[..] else {
+                return Err(invalid("expected radius, thickness, index and aperture"));
+            };
+            elements.push(LensElement {
+                radius: radius * MM,
+                thickness: thickness * MM,
+                eta,
+                aperture: 0.5 * diameter * MM,
+                shape: None,
+            });
+        }
+
+        if elements.is_empty() {
+            return Err(invalid("no lens surfaces"));
+        }
+        Ok(LensSystem { elements })
+    }
+
+    // Gives the aperture stop the shape of `aperture`, scaled to its diameter.
+    pub(crate) fn with_aperture(mut self, aperture: Aperture) -> Self {
+        for element in &mut self.elements {
+            if element.radius == 0.0 {
+                element.shape = Some(aperture.clone());
+            }
+        }
+        self
+    }
+}
+
+// Bounds of the exit pupil on the plane of the rear element, for film points on the positive x
+// axis.
+#[derive(Clone, Copy, Debug)]
+struct PupilBounds {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl PupilBounds {
+    fn area(&self) -> f64 {
+        (self.max.0 - self.min.0) * (self.max.1 - self.min.1)
+    }
+}
+
+// A lens system focused in front of a film, traced the way pbrt's realistic camera does. The
+// lens looks down the negative z axis, with the film in the plane z = 0 behind it. Rays start at
+// a point on the film, aim at a point of the exit pupil, and refract through every surface in
+// turn; those blocked by an aperture are lost, which is what vignettes the image.
+#[derive(Debug)]
+pub(crate) struct RealisticLens {
+    elements: Vec<LensElement>,
+    film_width: f64,
+    film_height: f64,
+    pupils: Vec<PupilBounds>,
+    // Exit pupil area seen from the center of the film, weighted by the cos⁴ falloff, against
+    // which samples are weighted so that the center of the image is exposed like a pinhole's.
+    center_area: f64,
+}
+
+impl RealisticLens {
+    // Moves `system` to focus on objects `focus_distance` in front of the film, a film
+    // `film_width` by `film_height` in millimeters. Fails when the lens can't focus that close.
+    pub(crate) fn new(
+        system: &LensSystem,
+        film_width: f64,
+        film_height: f64,
+        focus_distance: f64,
+    ) -> Result<Self, Error> {
+        let mut lens = RealisticLens {
+            elements: system.elements.clone(),
+            film_width: 0.001 * film_width,
+            film_height: 0.001 * film_height,
+            pupils: Vec::new(),
+            center_area: 0.0,
+        };
+
+        let thickness = lens.focus(focus_distance).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("lens can't focus at a distance of {}", focus_distance),
+            )
+        })?;
+        lens.elements.last_mut().unwrap().thickness = thickness;
+
+        let half_diagonal = lens.half_diagonal();
+        lens.pupils = (0..PUPIL_SEGMENTS)
+            .map(|i| {
+                let r0 = half_diagonal * i as f64 / PUPIL_SEGMENTS as f64;
+                let r1 = half_diagonal * (i + 1) as f64 / PUPIL_SEGMENTS as f64;
+                lens.bound_exit_pupil(r0, r1)
+            })
+            .collect();
+        lens.center_area = lens.center_area();
+        if lens.center_area <= 0.0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "no light passes through the lens",
+            ));
+        }
+        Ok(lens)
+    }
+
+    // The same lens with a film as wide, focused at `focus_distance` instead and cut to
+    // `aspect_ratio`.
+    pub(crate) fn refocused(&self, focus_distance: f64, aspect_ratio: f64) -> Result<Self, Error> {
+        let system = LensSystem {
+            elements: self.elements.clone(),
+        };
+        RealisticLens::new(
+            &system,
+            1000.0 * self.film_width,
+            1000.0 * self.film_width / aspect_ratio,
+            focus_distance,
+        )
+    }
+
+    // Samples a ray leaving the lens for the image position `x`, `y` in [0, 1] from the top
+    // left, in the lens's frame, along with the weight of its contribution. None when the ray
+    // is blocked inside the lens.
+    pub(crate) fn sample_ray(&self, x: f64, y: f64) -> Option<(Ray, f64)> {
+        // The lens turns the image upside down, so the film is read rotated by half a turn.
+        let film = Vec3::new(
+            (0.5 - x) * self.film_width,
+            (y - 0.5) * self.film_height,
+            0.0,
+        );
+        let r = film.x().hypot(film.y());
+        let segment =
+            ((r / self.half_diagonal() * PUPIL_SEGMENTS as f64) as usize).min(PUPIL_SEGMENTS - 1);
+        let pupil = &self.pupils[segment];
+
+        // The bounds are for film points on the x axis, so turn the sample to where this one is.
+        let a = pupil.min.0 + random_f64() * (pupil.max.0 - pupil.min.0);
+        let b = pupil.min.1 + random_f64() * (pupil.max.1 - pupil.min.1);
+        let (sin, cos) = if r > 0.0 {
+            (film.y() / r, film.x() / r)
+        } else {
+            (0.0, 1.0)
+        };
+        let rear = Vec3::new(cos * a - sin * b, sin * a + cos * b, -self.rear_z());
+
+        let direction = rear - &film;
+        let ray = self.trace_from_film(&Ray::new(&film, &direction, 0.0))?;
+        let cos_theta = direction.unit().z().abs();
+        let weight = cos_theta.powi(4) * pupil.area() / self.center_area;
+        Some((ray, weight))
+    }
+
+    fn half_diagonal(&self) -> f64 {
+        0.5 * self.film_width.hypot(self.film_height)
+    }
+
+    fn rear_z(&self) -> f64 {
+        self.elements.last().unwrap().thickness
+    }
+
+    fn front_z(&self) -> f64 {
+        self.elements.iter().map(|e| e.thickness).sum()
+    }
+
+    // Thickness between the rear element and the film that focuses at `distance` in front of
+    // the film, from the thick lens approximation of the system.
+    fn focus(&self, distance: f64) -> Option<f64> {
+        // Rays parallel to the axis, entering from either side a little off it.
+        let x = 0.001 * self.film_width.hypot(self.film_height);
+        let scene = Ray::new(
+            &Vec3::new(x, 0.0, -self.front_z() - 1.0),
+            &Vec3::new(0.0, 0.0, 1.0),
+            0.0,
+        );
+        let (pz0, fz0) = cardinal_points(&scene, &self.trace_from_scene(&scene)?);
+        let film = Ray::new(&Vec3::new(x, 0.0, 1.0), &Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let (pz1, _) = cardinal_points(&film, &self.trace_from_film(&film)?);
+
+        // Shift the lens so that the thin lens equation holds between its principal planes.
+        let f = fz0 - pz0;
+        let z = -distance;
+        let c = (pz1 - z - pz0) * (pz1 - z - 4.0 * f - pz0);
+        if f <= 0.0 || c < 0.0 {
+            return None;
+        }
+        let delta = 0.5 * (pz1 - z + pz0 - c.sqrt());
+        Some(self.rear_z() + delta)
+    }
+
+    // Bounds of the points on the rear element's plane through which light from film points
+    // between `r0` and `r1` along the x axis makes it through the lens.
+    fn bound_exit_pupil(&self, r0: f64, r1: f64) -> PupilBounds {
+        let extent = 1.5 * self.elements.last().unwrap().aperture;
+        let cell = 2.0 * extent / PUPIL_GRID as f64;
+        let mut bounds: Option<PupilBounds> = None;
+        for k in 0..PUPIL_GRID * PUPIL_GRID {
+            let (i, j) = (k % PUPIL_GRID, k / PUPIL_GRID);
+            let a = -extent + (i as f64 + 0.5) * cell;
+            let b = -extent + (j as f64 + 0.5) * cell;
+            if let Some(bounds) = &bounds {
+                if (bounds.min.0..=bounds.max.0).contains(&a)
+                    && (bounds.min.1..=bounds.max.1).contains(&b)
+                {
+                    continue;
+                }
+            }
+
+            let t = (k as f64 + 0.5) / (PUPIL_GRID * PUPIL_GRID) as f64;
+            let film = Vec3::new(r0 + t * (r1 - r0), 0.0, 0.0);
+            let rear = Vec3::new(a, b, -self.rear_z());
+            if self
+                .trace_from_film(&Ray::new(&film, &(rear - &film), 0.0))
+                .is_some()
+            {
+                bounds = Some(match bounds {
+                    Some(p) => PupilBounds {
+                        min: (p.min.0.min(a), p.min.1.min(b)),
+                        max: (p.max.0.max(a), p.max.1.max(b)),
+                    },
+                    None => PupilBounds {
+                        min: (a, b),
+                        max: (a, b),
+                    },
+                });
+            }
+        }
+
+        // Pad by a cell to cover what the grid missed; when nothing gets through, the whole
+        // element is tried and every ray is lost.
+        match bounds {
+            Some(p) => PupilBounds {
+                min: (p.min.0 - cell, p.min.1 - cell),
+                max: (p.max.0 + cell, p.max.1 + cell),
+            },
+            None => PupilBounds {
+                min: (-extent, -extent),
+                max: (extent, extent),
+            },
+        }
+    }
+
+    fn center_area(&self) -> f64 {
+        let pupil = &self.pupils[0];
+        let (width, height) = (pupil.max.0 - pupil.min.0, pupil.max.1 - pupil.min.1);
+        let cell = width * height / (PUPIL_GRID * PUPIL_GRID) as f64;
+        let film = Vec3::new(0.0, 0.0, 0.0);
+        (0..PUPIL_GRID * PUPIL_GRID)
+            .map(|k| {
+                let a = pupil.min.0 + ((k % PUPIL_GRID) as f64 + 0.5) / PUPIL_GRID as f64 * width;
+                let b = pupil.min.1 + ((k / PUPIL_GRID) as f64 + 0.5) / PUPIL_GRID as f64 * height;
+                let direction = Vec3::new(a, b, -self.rear_z()) - &film;
+                match self.trace_from_film(&Ray::new(&film, &direction, 0.0)) {
+                    Some(_) => direction.unit().z().powi(4) * cell,
+                    None => 0.0,
+                }
+            })
+            .sum()
+    }
+
+    // Traces a ray from the film out through the front of the lens.
+    fn trace_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let (mut origin, mut direction) = (ray.origin(), ray.direction());
+        let mut z = 0.0;
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            z -= element.thickness;
+            let eta_t = if i > 0 {
+                medium(self.elements[i - 1].eta)
+            } else {
+                1.0
+            };
+            (origin, direction) =
+                element.refract(z, &origin, &direction, medium(element.eta) / eta_t)?;
+        }
+        Some(Ray::new(&origin, &direction, 0.0))
+    }
+
+    // Traces a ray from the scene in through the front of the lens towards the film.
+    fn trace_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let (mut origin, mut direction) = (ray.origin(), ray.direction());
+        let mut z = -self.front_z();
+        for (i, element) in self.elements.iter().enumerate() {
+            let eta_i = if i > 0 {
+                medium(self.elements[i - 1].eta)
+            } else {
+                1.0
+            };
+            (origin, direction) =
+                element.refract(z, &origin, &direction, eta_i / medium(element.eta))?;
+            z += element.thickness;
+        }
+        Some(Ray::new(&origin, &direction, 0.0))
+    }
+}
+
+impl LensElement {
+    // Carries a ray across the surface whose vertex is at `z` on the axis, returning where it
+    // crosses the surface and its direction beyond, or None when the ray misses the aperture or
+    // is totally internally reflected.
+    fn refract(&self, z: f64, origin: &Vec3, direction: &Vec3, eta: f64) -> Option<(Vec3, Vec3)> {
+        if self.radius == 0.0 {
+            let t = (z - origin.z()) / direction.z();
+            let p = origin + t * direction;
+            let inside = match &self.shape {
+                Some(shape) => shape.contains(p.x() / self.aperture, p.y() / self.aperture),
+                None => p.x().hypot(p.y()) <= self.aperture,
+            };
+            return (t >= 0.0 && inside).then_some((p, direction.clone()));
+        }
+
+        // Of the sphere's two intersections, the one on the side of its vertex.
+        let oc = origin - Vec3::new(0.0, 0.0, z + self.radius);
+        let a = direction.length_squared();
+        let half_b = oc.dot(direction);
+        let c = oc.length_squared() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let t = if (direction.z() > 0.0) ^ (self.radius < 0.0) {
+            (-half_b - root) / a
+        } else {
+            (-half_b + root) / a
+        };
+        if t < 0.0 {
+            return None;
+        }
+        let p = origin + t * direction;
+        if p.x().hypot(p.y()) > self.aperture {
+            return None;
+        }
+
+        let d = direction.unit();
+        let mut n = (oc + t * direction).unit();
+        if n.dot(&d) > 0.0 {
+            n = -n;
+        }
+        let cos_i = -n.dot(&d);
+        let sin2_t = eta * eta * (1.0 - cos_i * cos_i).max(0.0);
+        if sin2_t >= 1.0 {
+            return None;
+        }
+        let cos_t = (1.0 - sin2_t).sqrt();
+        Some((p, eta * d + (eta * cos_i - cos_t) * n))
+    }
+}
+
+fn medium(eta: f64) -> f64 {
+    if eta == 0.0 {
+        1.0
+    } else {
+        eta
+    }
+}
+
+// Axial positions of the principal plane and the focal point on the side where a ray parallel
+// to the axis, `r_in`, leaves the lens as `r_out`.
+fn cardinal_points(r_in: &Ray, r_out: &Ray) -> (f64, f64) {
+    let (o, d) = (r_out.origin(), r_out.direction());
+    let tf = -o.x() / d.x();
+    let tp = (r_in.origin().x() - o.x()) / d.x();
+    (o.z() + tp * d.z(), o.z() + tf * d.z())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_prescription_in_millimeters() {
+        let system = LensSystem::parse(
+            "# radius thickness index aperture\n\
+             \n\
+             50  5  1.5  20  # front\n\
+             0   2  0    10\n\
+             -50 40 1    20\n",
+            "test",
+        )
+        .unwrap();
+        let radii: Vec<_> = system.elements.iter().map(|e| e.radius).collect();
+        assert_eq!(radii, [0.05, 0.0, -0.05]);
+        assert_eq!(system.elements[0].thickness, 0.005);
+        assert_eq!(system.elements[0].eta, 1.5);
+        assert_eq!(system.elements[1].aperture, 0.005);
+    }
+
+    #[test]
+    fn shapes_only_the_aperture_stop() {
+        let system = LensSystem::by_name("dgauss50")
+            .unwrap()
+            .with_aperture(Aperture::Polygon {
+                blades: 6,
+                rotation: 0.0,
+            });
+        for element in &system.elements {
+            assert_eq!(element.shape.is_some(), element.radius == 0.0);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_prescriptions() {
+        for (text, message) in [
+            ("50 5 1.5 twenty", "bad number"),
+            ("50 5 1.5", "expected radius, thickness, index and aperture"),
+            (
+                "50 5 1.5 20 1",
+                "expected radius, thickness, index and aperture",
+            ),
+            ("", "no lens surfaces"),
+            ("# only a comment\n\n", "no lens surfaces"),
+        ] {
+            let e = LensSystem::parse(text, "broken.lens").unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidData);
+            assert_eq!(e.to_string(), format!("broken.lens: {}", message));
+        }
+    }
+
+    #[test]
+    fn focuses_the_built_in_lenses() {
+        for name in LENS_NAMES {
+            let system = LensSystem::by_name(name).unwrap();
+            assert!(
+                RealisticLens::new(&system, 36.0, 24.0, 5.0).is_ok(),
+                "{}",
+                name
+            );
+        }
+        assert!(LensSystem::by_name("fisheye").is_none());
+    }
+
+    #[test]
+    fn rejects_a_focus_closer_than_the_lens_allows() {
+        let system = LensSystem::by_name("dgauss50").unwrap();
+        let e = RealisticLens::new(&system, 36.0, 24.0, 0.01).unwrap_err();
+        assert!(e.to_string().contains("can't focus"), "{}", e);
+    }
+}
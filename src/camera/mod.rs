@@ -0,0 +1,1511 @@
+mod aov;
+mod aperture;
+mod film;
+mod filter;
+mod lens;
+mod region;
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    f64::consts::PI,
+    io::{Error, ErrorKind},
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    animation::Track,
+    image::{Denoiser, Guides, Image, Layer},
+    lights::{power_heuristic, Background, LightSample, LightSampler, LUMINANCE_SCALE},
+    physics::{Color, Material, Point3, Ray, SampledSpectrum, SampledWavelengths, Vec3},
+    shapes::{HitRecord, Hittable, HittableList},
+    utils::{degrees_to_radians, random_f64, seed_random, Interval},
+};
+
+pub(crate) use aov::{Aov, AOV_NAMES};
+pub(crate) use aperture::Aperture;
+pub(crate) use filter::{Filter, FILTER_NAMES};
+pub(crate) use lens::{LensSystem, LENS_NAMES};
+pub(crate) use region::Region;
+
+use film::Film;
+use lens::RealisticLens;
+
+// Longest path traced, in bounces.
+const MAX_DEPTH: u8 = 50;
+
+thread_local! {
+    // Whether tracing prints every bounce, for a debug render of a single pixel.
+    static VERBOSE: Cell<bool> = const { Cell::new(false) };
+}
+
+// Prints the message for a bounce `depth` steps before the path is cut off, indented by how far
+// along the path it is, when tracing verbosely.
+fn log(depth: u8, message: impl FnOnce() -> String) {
+    if VERBOSE.get() {
+        let indent = 2 * (1 + MAX_DEPTH.saturating_sub(depth) as usize);
+        println!("{:indent$}{}", "", message(), indent = indent);
+    }
+}
+
+// Where the previous bounce was and the density with which it picked the ray leaving it, kept
+// when it also sampled lights directly and the lights the ray finds have to be weighted against
+// those samples.
+struct Bounce {
+    p: Point3,
+    pdf: f64,
+}
+
+// How the camera maps pixels to rays.
+#[derive(Clone, Debug)]
+enum Projection {
+    // Rays fan out from the camera center through the viewport.
+    Perspective,
+    // Rays leave the viewport in parallel, along the view direction. The viewport passes
+    // through the camera center, so nothing behind the camera is seen. It is `view_width` scene
+    // units across whatever the field of view.
+    Orthographic { view_width: f64 },
+    // Longitude across and latitude down the whole sphere of directions, centered on the view
+    // direction. With an interpupillary distance, an omni-directional stereo pair with the left
+    // eye in the top half of the image and the right eye in the bottom half.
+    Equirectangular { ipd: Option<f64> },
+    // Circular fisheye image filling the square frame, covering `fov` radians across.
+    Fisheye { fov: f64, mapping: FisheyeMapping },
+    // The six 90° faces of a cube around the camera in a 3×2 grid: right, left and up in the top
+    // row, then down, back and front.
+    Cubemap,
+    // Rays traced through the surfaces of a real lens from its film, which sits at the camera
+    // center.
+    Lens(Arc<RealisticLens>),
+}
+
+// How a fisheye lens maps the angle from its axis to the distance from the image center.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum FisheyeMapping {
+    // Distance proportional to the angle.
+    Equidistant,
+    // Distance proportional to sin(θ/2), which preserves solid angle.
+    Equisolid,
+}
+
+// How the two eyes of a stereo pair are written out.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum StereoLayout {
+    // One file per eye, named after the output with `_left` and `_right` added.
+    Separate,
+    // Left eye in the left half of a double width image.
+    SideBySide,
+    // Left eye in the top half of a double height image.
+    OverUnder,
+    // Red from the left eye and green and blue from the right, for red-cyan glasses.
+    Anaglyph,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Stereo {
+    interocular: f64,
+    // Distance in front of the camera at which the two eyes' images coincide.
+    convergence: f64,
+    layout: StereoLayout,
+}
+
+// Settings of a real camera, in the units photographers use. Scene units are meters.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PhotographicSettings {
+    // Width of the sensor in millimeters, 36 for full frame.
+    pub(crate) sensor_width: f64,
+    pub(crate) focal_length: f64,
+    pub(crate) f_number: f64,
+    // Time the shutter stays open, in seconds.
+    pub(crate) shutter_time: f64,
+    pub(crate) iso: f64,
+}
+
+// Keyframed parts of the view, which take over from the fixed ones in a sequence of frames.
+#[derive(Clone, Default)]
+struct Animation {
+    look_from: Option<Track<Point3>>,
+    look_at: Option<Track<Point3>>,
+    vfov: Option<Track<f64>>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Camera {
+    samples_per_pixel: usize,
+    image_width: usize,
+    image_height: usize,
+    center: Point3,
+    vup: Vec3,
+    // Vertical field of view of the perspective projection, in degrees.
+    vfov: f64,
+    // Camera frame: right, up, and backwards from the view direction.
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    projection: Projection,
+    // Distance from the center to the perspective viewport, where `look_at` is.
+    focus_distance: f64,
+    // Radius of the thin lens of a perspective camera, zero for a pinhole.
+    defocus_radius: f64,
+    // Angle in degrees of the cone of rays through a pixel when the lens was given one, which
+    // keeps it as the focus moves. A lens from photographic settings keeps its radius instead.
+    defocus_angle: Option<f64>,
+    aperture: Aperture,
+    // Interval over which the shutter is open. Each ray is sent at a random instant within it.
+    shutter_open: f64,
+    shutter_close: f64,
+    // How far the opening of the lens barrel slides off the aperture towards the image edges,
+    // in aperture radii.
+    cats_eye: f64,
+    // Scale from scene radiance to pixel values.
+    exposure: f64,
+    pixel00_loc: Point3,
+    pixel_delta_u: Vec3,
+    pixel_delta_v: Vec3,
+    spectral: bool,
+    stereo: Option<Stereo>,
+    animation: Animation,
+    aovs: Vec<Aov>,
+    // Whether the passes go into the image's file as layers, rather than files of their own.
+    aov_layers: bool,
+    denoiser: Option<Denoiser>,
+    filter: Filter,
+    filter_radius: f64,
+    // Pixel edges of the part of the image rendered, and whether the written image is cut down
+    // to it rather than left black around it.
+    region: Option<[usize; 4]>,
+    crop: bool,
+    threads: usize,
+    // Seed of the random numbers of each row, which otherwise differ from run to run.
+    seed: Option<u64>,
+}
+
+impl Camera {
+    pub(crate) fn new(
+        samples_per_pixel: usize,
+        vfov: f64,
+        aspect_ratio: f64,
+        image_width: usize,
+        look_from: Point3,
+        look_at: Point3,
+        vup: Vec3,
+    ) -> Self {
+        let image_height = ((image_width as f64) / aspect_ratio) as usize;
+
+        let mut camera = Camera {
+            samples_per_pixel,
+            image_width,
+            image_height,
+            center: Point3::default(),
+            vup,
+            vfov,
+            u: Vec3::default(),
+            v: Vec3::default(),
+            w: Vec3::default(),
+            projection: Projection::Perspective,
+            focus_distance: 0.0,
+            defocus_radius: 0.0,
+            defocus_angle: None,
+            aperture: Aperture::Circle,
+            cats_eye: 0.0,
+            exposure: 1.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            pixel00_loc: Point3::default(),
+            pixel_delta_u: Vec3::default(),
+            pixel_delta_v: Vec3::default(),
+            spectral: false,
+            stereo: None,
+            animation: Animation::default(),
+            aovs: Vec::new(),
+            aov_layers: false,
+            denoiser: None,
+            filter: Filter::Box,
+            filter_radius: 0.5,
+            region: None,
+            crop: false,
+            threads: 1,
+            seed: None,
+        };
+        camera.look(look_from, look_at, vfov);
+        camera
+    }
+
+    pub(crate) fn image_size(&self) -> (usize, usize) {
+        (self.image_width, self.image_height)
+    }
+
+    pub(crate) fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    // Places the camera at `look_from` looking towards `look_at`, with the perspective viewport
+    // there covering `vfov` degrees vertically.
+    fn look(&mut self, look_from: Point3, look_at: Point3, vfov: f64) {
+        // Determine viewport dimensions.
+        let focal_length = (&look_from - &look_at).length();
+        let theta = degrees_to_radians(vfov);
+        let h = (theta / 2.0).tan();
+        let viewport_height = 2.0 * h * focal_length;
+        let viewport_width =
+            viewport_height * ((self.image_width as f64) / (self.image_height as f64));
+
+        // Calculate the u,v,w unit basis vectors for the camera coordinate frame.
+        self.w = (&look_from - look_at).unit();
+        self.u = self.vup.cross(&self.w).unit();
+        self.v = self.w.cross(&self.u);
+
+        self.center = look_from;
+        self.vfov = vfov;
+        self.focus_distance = focal_length;
+        self.set_defocus_radius();
+        match self.projection {
+            Projection::Orthographic { view_width } => self.set_orthographic_viewport(view_width),
+            _ => self.set_viewport(viewport_width, viewport_height, focal_length),
+        }
+    }
+
+    fn set_orthographic_viewport(&mut self, view_width: f64) {
+        let view_height = view_width * (self.image_height as f64) / (self.image_width as f64);
+        self.set_viewport(view_width, view_height, 0.0);
+    }
+
+    // Refocuses a lens camera on `look_at` with a film of the image's shape, once either has
+    // changed. Fails when the lens can't focus that close.
+    fn refit_lens(&mut self) -> Result<(), Error> {
+        if let Projection::Lens(lens) = &self.projection {
+            let aspect_ratio = self.image_width as f64 / self.image_height as f64;
+            let lens = lens.refocused(self.focus_distance, aspect_ratio)?;
+            self.projection = Projection::Lens(Arc::new(lens));
+        }
+        Ok(())
+    }
+
+    // Gives the image the shape a panoramic projection needs, keeping its width as far as it
+    // can. Other projections take images of any shape.
+    fn fit_panorama(&mut self) {
+        match self.projection {
+            Projection::Equirectangular { ipd: None } => {
+                self.image_height = (self.image_width / 2).max(1);
+            }
+            Projection::Equirectangular { ipd: Some(_) } | Projection::Fisheye { .. } => {
+                self.image_height = self.image_width;
+            }
+            Projection::Cubemap => {
+                self.image_width = (self.image_width / 3).max(1) * 3;
+                self.image_height = self.image_width * 2 / 3;
+            }
+            _ => {}
+        }
+    }
+
+    // Places the pixel grid on a viewport of the given size, centered on the view direction at
+    // `distance` in front of the camera.
+    fn set_viewport(&mut self, viewport_width: f64, viewport_height: f64, distance: f64) {
+        // Calculate the vectors across the horizontal and down the vertical viewport edges.
+        let viewport_u = viewport_width * self.u.clone();
+        let viewport_v = viewport_height * -&self.v;
+
+        // Calculate the horizontal and vertical delta vectors from pixel to pixel.
+        self.pixel_delta_u = &viewport_u / (self.image_width as f64);
+        self.pixel_delta_v = &viewport_v / (self.image_height as f64);
+
+        // Calculate the location of the upper left pixel.
+        let viewport_upper_left =
+            &self.center - (distance * &self.w) - viewport_u / 2.0 - viewport_v / 2.0;
+        self.pixel00_loc = viewport_upper_left + 0.5 * (&self.pixel_delta_u + &self.pixel_delta_v);
+    }
+
+    // Give the perspective camera a thin lens focused on `look_at`, with rays through a pixel
+    // spreading over a cone `defocus_angle` degrees wide at the camera.
+    pub(crate) fn with_defocus(mut self, defocus_angle: f64) -> Self {
+        self.defocus_angle = Some(defocus_angle);
+        self.set_defocus_radius();
+        self
+    }
+
+    fn set_defocus_radius(&mut self) {
+        if let Some(angle) = self.defocus_angle {
+            self.defocus_radius = self.focus_distance * degrees_to_radians(angle / 2.0).tan();
+        }
+    }
+
+    // Shape of the thin lens's aperture, circular by default.
+    pub(crate) fn with_aperture(mut self, aperture: Aperture) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    // Clip the thin lens's aperture with the opening of the lens barrel, a disc of the same size
+    // that slides off center towards the edges of the image, by `amount` aperture radii at the
+    // middle of each edge. Out of focus highlights there are cut into cat's eyes and the corners
+    // darken.
+    pub(crate) fn with_cats_eye(mut self, amount: f64) -> Self {
+        self.cats_eye = amount;
+        self
+    }
+
+    // Keep the shutter open from time `open` to `close`, blurring objects that move meanwhile.
+    pub(crate) fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    // Take the field of view, the thin lens and the shutter of a camera with these settings, and
+    // expose as it would. The lens stays focused on `look_at`. An orthographic or lens camera
+    // keeps its framing, which the field of view doesn't apply to. Exposure follows the saturation
+    // based ISO rating, in which a luminance of 1.2 · 100 N² / (t S) cd/m² just reaches a pixel
+    // value of one.
+    pub(crate) fn with_settings(mut self, settings: &PhotographicSettings) -> Self {
+        let sensor_height =
+            settings.sensor_width * (self.image_height as f64) / (self.image_width as f64);
+        let vfov = 2.0
+            * (sensor_height / (2.0 * settings.focal_length))
+                .atan()
+                .to_degrees();
+        let look_at = &self.center - self.focus_distance * &self.w;
+        self.look(self.center.clone(), look_at, vfov);
+
+        // The entrance pupil, converted from millimeters.
+        self.defocus_radius = 0.001 * settings.focal_length / (2.0 * settings.f_number);
+        self.defocus_angle = None;
+        self.shutter_close = self.shutter_open + settings.shutter_time;
+
+        let saturation =
+            120.0 * settings.f_number * settings.f_number / (settings.shutter_time * settings.iso);
+        self.exposure = 1.0 / (saturation * LUMINANCE_SCALE);
+        self
+    }
+
+    // Switch to a parallel projection showing `view_width` scene units across the image, for
+    // technical drawings and isometric shots. The framing no longer depends on the field of view
+    // or the distance to `look_at`.
+    pub(crate) fn with_orthographic(mut self, view_width: f64) -> Self {
+        self.set_orthographic_viewport(view_width);
+        self.projection = Projection::Orthographic { view_width };
+        self
+    }
+
+    // Switch to a full spherical panorama. The image becomes twice as wide as it is high.
+    pub(crate) fn with_equirectangular(mut self) -> Self {
+        self.projection = Projection::Equirectangular { ipd: None };
+        self.fit_panorama();
+        self
+    }
+
+    // Switch to an omni-directional stereo panorama for eyes `ipd` scene units apart, stacking
+    // the two eyes' square images top and bottom.
+    pub(crate) fn with_stereo_equirectangular(mut self, ipd: f64) -> Self {
+        self.projection = Projection::Equirectangular { ipd: Some(ipd) };
+        self.fit_panorama();
+        self
+    }
+
+    // Switch to a fisheye lens covering `fov` degrees across a square image. Pixels outside the
+    // image circle stay black.
+    pub(crate) fn with_fisheye(mut self, fov: f64, mapping: FisheyeMapping) -> Self {
+        self.projection = Projection::Fisheye {
+            fov: degrees_to_radians(fov.clamp(1.0, 360.0)),
+            mapping,
+        };
+        self.fit_panorama();
+        self
+    }
+
+    // Switch to a cubemap, with square faces a third of the image width across.
+    pub(crate) fn with_cubemap(mut self) -> Self {
+        self.projection = Projection::Cubemap;
+        self.fit_panorama();
+        self
+    }
+
+    // Switch to a camera tracing rays through `lens`, focused on `look_at`, with a sensor
+    // `sensor_width` millimeters across. The field of view follows from the lens's focal length
+    // and the sensor size. Fails when the lens can't focus that close.
+    pub(crate) fn with_lens(mut self, lens: &LensSystem, sensor_width: f64) -> Result<Self, Error> {
+        let sensor_height = sensor_width * (self.image_height as f64) / (self.image_width as f64);
+        let lens = RealisticLens::new(lens, sensor_width, sensor_height, self.focus_distance)?;
+        self.projection = Projection::Lens(Arc::new(lens));
+        Ok(self)
+    }
+
+    // Change the image size to `width` pixels across and `height` down, or as many as keep the
+    // aspect ratio, keeping the projection. A panorama takes the shape its projection needs,
+    // and fails when `height` doesn't fit it. A lens camera also fails when its refitted film
+    // lets no light through.
+    pub(crate) fn with_resolution(
+        mut self,
+        width: usize,
+        height: Option<usize>,
+    ) -> Result<Self, Error> {
+        let aspect_ratio = self.image_width as f64 / self.image_height as f64;
+        self.image_width = width.max(1);
+        self.image_height = height
+            .unwrap_or((width as f64 / aspect_ratio) as usize)
+            .max(1);
+        self.fit_panorama();
+        if let Some(height) = height.filter(|&h| (width, h) != self.image_size()) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "resolution: the projection needs a {}x{} image, not {}x{}",
+                    self.image_width, self.image_height, width, height
+                ),
+            ));
+        }
+
+        let look_at = &self.center - self.focus_distance * &self.w;
+        self.look(self.center.clone(), look_at, self.vfov);
+        self.refit_lens()?;
+        Ok(self)
+    }
+
+    // Change whichever of the position, target, up direction and vertical field of view are
+    // given, keeping the others and the projection. Fails when a lens can't focus on the new
+    // `look_at`.
+    pub(crate) fn with_view(
+        mut self,
+        look_from: Option<Point3>,
+        look_at: Option<Point3>,
+        vup: Option<Vec3>,
+        vfov: Option<f64>,
+    ) -> Result<Self, Error> {
+        let look_at = look_at.unwrap_or_else(|| &self.center - self.focus_distance * &self.w);
+        let look_from = look_from.unwrap_or_else(|| self.center.clone());
+        if let Some(vup) = vup {
+            self.vup = vup;
+        }
+        self.look(look_from, look_at, vfov.unwrap_or(self.vfov));
+        self.refit_lens()?;
+        Ok(self)
+    }
+
+    pub(crate) fn with_samples_per_pixel(mut self, samples_per_pixel: usize) -> Self {
+        self.samples_per_pixel = samples_per_pixel.max(1);
+        self
+    }
+
+    // Trace rows on `threads` threads at once.
+    pub(crate) fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // Draw each row's random numbers from `seed`, so that renders repeat exactly whatever the
+    // number of threads.
+    pub(crate) fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // Trace a hero wavelength per sample instead of RGB, which is what makes dispersion and
+    // measured spectral data show up correctly.
+    pub(crate) fn with_spectral(mut self, spectral: bool) -> Self {
+        self.spectral = spectral;
+        self
+    }
+
+    // Render a stereo pair for eyes `interocular` apart on either side of the camera, both
+    // looking along the view direction. `convergence` is the distance at which objects appear at
+    // the depth of the screen, by default that of `look_at`.
+    pub(crate) fn with_stereo(
+        mut self,
+        interocular: f64,
+        convergence: Option<f64>,
+        layout: StereoLayout,
+    ) -> Self {
+        self.stereo = Some(Stereo {
+            interocular,
+            convergence: convergence.unwrap_or(self.focus_distance),
+            layout,
+        });
+        self
+    }
+
+    // Write `aovs` along with the image, as layers of an OpenEXR image or else as files named
+    // after the image's with the passes' names added. Light passes need RGB rendering, and stereo
+    // pairs are written without passes.
+    pub(crate) fn with_aovs(mut self, aovs: Vec<Aov>, layers: bool) -> Self {
+        self.aovs = aovs;
+        self.aov_layers = layers;
+        self
+    }
+
+    // Reconstruct pixels from the samples around them with `filter`, over `radius` pixels or
+    // else the radius it's usually given. Fails when the radius is under half a pixel, which
+    // would leave samples that reach no pixel.
+    pub(crate) fn with_filter(
+        mut self,
+        filter: Filter,
+        radius: Option<f64>,
+    ) -> Result<Self, Error> {
+        let radius = radius.unwrap_or(filter.default_radius());
+        if radius.is_nan() || radius < 0.5 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "filter radius must be at least half a pixel, got {}",
+                    radius
+                ),
+            ));
+        }
+        self.filter = filter;
+        self.filter_radius = radius;
+        Ok(self)
+    }
+
+    // Render only `region` of the image, writing just that part if `crop`, or else the whole
+    // image with black around it. Fails when the region is empty or outside the image.
+    pub(crate) fn with_region(mut self, region: Region, crop: bool) -> Result<Self, Error> {
+        self.region = Some(region.bounds(self.image_width, self.image_height)?);
+        self.crop = crop;
+        Ok(self)
+    }
+
+    // Filter the noise out of the image once it's rendered, before it's written. The passes the
+    // filter is guided by are rendered for it whether or not they are written.
+    pub(crate) fn with_denoiser(mut self, denoiser: Denoiser) -> Self {
+        self.denoiser = Some(denoiser);
+        self
+    }
+
+    // Move the camera along `track` over time, in place of `look_from`.
+    pub(crate) fn with_animated_look_from(mut self, track: Track<Point3>) -> Self {
+        self.animation.look_from = Some(track);
+        self
+    }
+
+    // Turn the camera to follow `track` over time, in place of `look_at`. The focus follows.
+    pub(crate) fn with_animated_look_at(mut self, track: Track<Point3>) -> Self {
+        self.animation.look_at = Some(track);
+        self
+    }
+
+    // Zoom the perspective camera along `track` over time, in place of `vfov`.
+    pub(crate) fn with_animated_vfov(mut self, track: Track<f64>) -> Self {
+        self.animation.vfov = Some(track);
+        self
+    }
+
+    // Names of the parts of the view that follow a track, whose fixed values `at` ignores.
+    pub(crate) fn animated_view(&self) -> Vec<&'static str> {
+        let animation = &self.animation;
+        [
+            ("look_from", animation.look_from.is_some()),
+            ("look_at", animation.look_at.is_some()),
+            ("vfov", animation.vfov.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, animated)| animated.then_some(name))
+        .collect()
+    }
+
+    // The camera for a frame starting at `time`: its animated view as it is then, and the
+    // shutter opening and closing relative to that time. The defocus angle or the pupil of
+    // photographic settings, the field of view of an orthographic camera and the film of a lens
+    // stay the same. Fails when a lens can't focus as far as the new `look_at`.
+    pub(crate) fn at(&self, time: f64) -> Result<Camera, Error> {
+        let mut camera = self.clone();
+        camera.shutter_open += time;
+        camera.shutter_close += time;
+        // Frames draw different random numbers, so that their noise doesn't stand still.
+        camera.seed = self.seed.map(|seed| seed ^ time.to_bits());
+
+        let animation = &self.animation;
+        if animation.look_from.is_none() && animation.look_at.is_none() && animation.vfov.is_none()
+        {
+            return Ok(camera);
+        }
+        let look_from = animation
+            .look_from
+            .as_ref()
+            .map_or_else(|| self.center.clone(), |track| track.at(time));
+        let look_at = animation.look_at.as_ref().map_or_else(
+            || &self.center - self.focus_distance * &self.w,
+            |track| track.at(time),
+        );
+        let vfov = animation
+            .vfov
+            .as_ref()
+            .map_or(self.vfov, |track| track.at(time));
+        camera.look(look_from, look_at, vfov);
+
+        camera.refit_lens()?;
+        Ok(camera)
+    }
+
+    pub(crate) fn render(
+        &self,
+        out_filename: &str,
+        world: &HittableList,
+        background: &Background,
+        lights: &LightSampler,
+    ) -> Result<(), Error> {
+        let Some(stereo) = self.stereo else {
+            if self.spectral && self.aovs.iter().any(|aov| aov.is_light()) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "light passes can't be rendered spectrally",
+                ));
+            }
+            let (image, passes) = self.render_image(world, background, lights)?;
+            return self.save(out_filename, &image, &passes);
+        };
+
+        let offset = 0.5 * stereo.interocular;
+        println!("left eye:");
+        let left = self
+            .eye(-offset, stereo.convergence)
+            .render_image(world, background, lights)?
+            .0;
+        println!("right eye:");
+        let right = self
+            .eye(offset, stereo.convergence)
+            .render_image(world, background, lights)?
+            .0;
+
+        let (width, height) = (left.width(), left.height());
+        let combined = match stereo.layout {
+            StereoLayout::Separate => {
+                left.save(&Image::suffixed_path(out_filename, "left"))?;
+                return right.save(&Image::suffixed_path(out_filename, "right"));
+            }
+            StereoLayout::SideBySide => {
+                let pixels = (0..height)
+                    .flat_map(|y| {
+                        let (left, right) = (&left, &right);
+                        (0..2 * width).map(move |x| {
+                            if x < width {
+                                left.pixel(x, y).clone()
+                            } else {
+                                right.pixel(x - width, y).clone()
+                            }
+                        })
+                    })
+                    .collect();
+                Image::new(2 * width, height, pixels)
+            }
+            StereoLayout::OverUnder => {
+                let pixels = [&left, &right]
+                    .into_iter()
+                    .flat_map(|eye| {
+                        (0..height)
+                            .flat_map(move |y| (0..width).map(move |x| eye.pixel(x, y).clone()))
+                    })
+                    .collect();
+                Image::new(width, 2 * height, pixels)
+            }
+            StereoLayout::Anaglyph => {
+                let pixels = (0..height)
+                    .flat_map(|y| (0..width).map(move |x| (x, y)))
+                    .map(|(x, y)| {
+                        let (l, r) = (left.pixel(x, y), right.pixel(x, y));
+                        Color::new(l.r(), r.g(), r.b())
+                    })
+                    .collect();
+                Image::new(width, height, pixels)
+            }
+        };
+        combined.save(out_filename)
+    }
+
+    // Traces the samples of the pixel at `i`, `j` alone, printing every bounce of their paths and
+    // the value each brings back, to track down where a stray value comes from.
+    pub(crate) fn debug_pixel(
+        &self,
+        i: usize,
+        j: usize,
+        world: &HittableList,
+        background: &Background,
+        lights: &LightSampler,
+    ) -> Result<(), Error> {
+        if i >= self.image_width || j >= self.image_height {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "pixel {}, {} is outside the {}x{} image",
+                    i, j, self.image_width, self.image_height
+                ),
+            ));
+        }
+
+        VERBOSE.set(true);
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for n in 0..self.samples_per_pixel {
+            let (dx, dy) = (random_f64() - 0.5, random_f64() - 0.5);
+            println!(
+                "sample {}, {:+.3}, {:+.3} off the pixel's center:",
+                n, dx, dy
+            );
+            let Some((r, weight)) = self.get_ray(i, j, dx, dy) else {
+                println!("  outside the projection's image");
+                continue;
+            };
+            let radiance = if self.spectral {
+                let mut lambda = SampledWavelengths::sample_uniform(random_f64());
+                println!("  wavelengths {:?}", lambda);
+                Self::ray_color_spectral(
+                    &r,
+                    world,
+                    background,
+                    lights,
+                    MAX_DEPTH,
+                    None,
+                    &mut lambda,
+                )
+                .to_rgb(&lambda)
+            } else {
+                Self::ray_color(&r, world, background, lights, MAX_DEPTH, None)
+            };
+            let value = (self.exposure * weight) * radiance;
+            println!("  value {} (camera weight {})", value, weight);
+            sum = sum + value;
+        }
+        VERBOSE.set(false);
+
+        let mean = (1.0 / self.samples_per_pixel as f64) * sum;
+        println!("pixel {}, {}: mean {}", i, j, mean);
+        Ok(())
+    }
+
+    fn save(&self, out_filename: &str, image: &Image, passes: &[Image]) -> Result<(), Error> {
+        if self.aov_layers {
+            let mut layers = vec![Layer {
+                name: "",
+                channels: &["R", "G", "B"],
+                image,
+            }];
+            layers.extend(self.aovs.iter().zip(passes).map(|(aov, pass)| Layer {
+                name: aov.name(),
+                channels: aov.channels(),
+                image: pass,
+            }));
+            return Image::save_layers(&layers, out_filename);
+        }
+
+        image.save(out_filename)?;
+        for (aov, pass) in self.aovs.iter().zip(passes) {
+            let path = Image::suffixed_path(out_filename, aov.name());
+            // OpenEXR keeps the values as they are, other formats get an image to look at.
+            if path.to_ascii_lowercase().ends_with(".exr") {
+                let layer = Layer {
+                    name: "",
+                    channels: aov.channels(),
+                    image: pass,
+                };
+                Image::save_layers(&[layer], &path)?;
+            } else {
+                aov.visualize(pass).save(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // The camera of one eye of a stereo pair, `offset` along the right axis. A perspective eye
+    // keeps the viewport where the centered camera has it at the convergence distance, so the
+    // two frustums are sheared towards each other rather than turned inwards, which would
+    // misalign the images vertically towards their edges.
+    fn eye(&self, offset: f64, convergence: f64) -> Camera {
+        let mut eye = self.clone();
+        eye.stereo = None;
+        let shift = offset * &self.u;
+        match self.projection {
+            Projection::Perspective => {
+                let scale = convergence / self.focus_distance;
+                let width = scale * self.pixel_delta_u.length() * self.image_width as f64;
+                let height = scale * self.pixel_delta_v.length() * self.image_height as f64;
+                eye.set_viewport(width, height, convergence);
+            }
+            _ => eye.pixel00_loc += &shift,
+        }
+        eye.center += &shift;
+        eye
+    }
+
+    // Traces every pixel, returning the average of its samples, denoised if asked to, and the
+    // requested passes. Rows are handed out to the camera's threads as they finish the last.
+    fn render_image(
+        &self,
+        world: &HittableList,
+        background: &Background,
+        lights: &LightSampler,
+    ) -> Result<(Image, Vec<Image>), Error> {
+        // The denoiser's guides are rendered as passes after the requested ones.
+        const GUIDES: [Aov; 5] = [
+            Aov::Albedo,
+            Aov::Normal,
+            Aov::Position,
+            Aov::Depth,
+            Aov::Variance,
+        ];
+        // Paths recurse once per bounce, deeper than the default stack of a spawned thread allows.
+        const STACK_SIZE: usize = 64 << 20;
+
+        let mut aovs = self.aovs.clone();
+        if self.denoiser.is_some() {
+            aovs.extend(GUIDES);
+        }
+
+        let [x0, y0, x1, y1] = self
+            .region
+            .unwrap_or([0, 0, self.image_width, self.image_height]);
+        let film = Mutex::new(Film::new(
+            0,
+            self.image_height,
+            self.image_width,
+            aovs.len(),
+        ));
+        // Materials are numbered up front in the order the world holds them, so the IDs are the
+        // same from one render to the next however the rows are shared out.
+        let mut material_ids = HashMap::new();
+        if aovs.contains(&Aov::MaterialId) {
+            world.materials(&mut |material| {
+                let next = material_ids.len() + 1;
+                material_ids
+                    .entry(Self::material_key(material))
+                    .or_insert(next);
+            });
+        }
+        let (next_row, remaining) = (AtomicUsize::new(y0), AtomicUsize::new(y1 - y0));
+        thread::scope(|scope| -> Result<(), Error> {
+            for _ in 0..self.threads.max(1) {
+                thread::Builder::new()
+                    .stack_size(STACK_SIZE)
+                    .spawn_scoped(scope, || loop {
+                        let j = next_row.fetch_add(1, Ordering::Relaxed);
+                        if j >= y1 {
+                            break;
+                        }
+                        let band = self.render_row(
+                            j,
+                            x0..x1,
+                            &aovs,
+                            &material_ids,
+                            world,
+                            background,
+                            lights,
+                        );
+                        film.lock().unwrap().add(&band);
+                        let left = remaining.fetch_sub(1, Ordering::Relaxed) - 1;
+                        println!("scanlines remaining: {}", left);
+                    })?;
+            }
+            Ok(())
+        })?;
+        let Film {
+            mut pixels,
+            weights,
+            mut passes,
+            ..
+        } = film.into_inner().unwrap();
+
+        // The sums of the splatted values are divided by the filter's weights. The IDs and
+        // variance are worked out from each pixel's own samples instead. A pixel whose weights
+        // nearly cancel out, which negative lobes can do, is left black rather than blown up.
+        for (p, &total) in weights.iter().enumerate() {
+            let scale = if total > 1e-6 { 1.0 / total } else { 0.0 };
+            pixels[p] = (self.exposure * scale) * pixels[p].clone();
+            for (k, aov) in aovs.iter().enumerate() {
+                let scale = match aov {
+                    Aov::ObjectId | Aov::MaterialId | Aov::Variance => continue,
+                    _ if aov.is_light() => self.exposure * scale,
+                    _ => scale,
+                };
+                passes[k][p] = scale * passes[k][p].clone();
+            }
+        }
+
+        println!("done.");
+        let image = |pixels| Image::new(self.image_width, self.image_height, pixels);
+        let mut image_out = image(pixels);
+        let mut passes: Vec<Image> = passes.into_iter().map(image).collect();
+        if self.crop {
+            let (width, height) = (x1 - x0, y1 - y0);
+            image_out = image_out.crop(x0, y0, width, height);
+            passes = passes
+                .iter()
+                .map(|pass| pass.crop(x0, y0, width, height))
+                .collect();
+        }
+        if let Some(denoiser) = self.denoiser {
+            let guides = passes.split_off(self.aovs.len());
+            println!("denoising.");
+            image_out = denoiser.denoise(
+                &image_out,
+                &Guides {
+                    albedo: &guides[0],
+                    normal: &guides[1],
+                    position: &guides[2],
+                    depth: &guides[3],
+                    variance: &guides[4],
+                },
+            );
+        }
+        Ok((image_out, passes))
+    }
+
+    // Traces the samples of the pixels `columns` of row `j`, splatting them onto the band of rows
+    // the filter reaches. `material_ids` holds the number of each material in the world.
+    #[allow(clippy::too_many_arguments)]
+    fn render_row(
+        &self,
+        j: usize,
+        columns: Range<usize>,
+        aovs: &[Aov],
+        material_ids: &HashMap<usize, usize>,
+        world: &HittableList,
+        background: &Background,
+        lights: &LightSampler,
+    ) -> Film {
+        if let Some(seed) = self.seed {
+            // Each row draws from its own sequence, whichever thread traces it.
+            seed_random(seed ^ (j as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15));
+        }
+
+        let reach = (self.filter_radius + 0.5).ceil() as usize;
+        let first_row = j.saturating_sub(reach);
+        let rows = (j + reach + 1).min(self.image_height) - first_row;
+        let mut film = Film::new(first_row, rows, self.image_width, aovs.len());
+        let split_light = aovs.iter().any(|aov| aov.is_light());
+        let black = Color::new(0.0, 0.0, 0.0);
+
+        for i in columns {
+            // Samples landing on each object and material, of which the most frequent wins.
+            let mut ids: Vec<Vec<(usize, usize)>> = vec![Vec::new(); aovs.len()];
+            // Sums of the samples' luminance and its square, for the variance.
+            let (mut luminance, mut luminance_squared) = (0.0, 0.0);
+
+            for _ in 0..self.samples_per_pixel {
+                let (dx, dy) = (random_f64() - 0.5, random_f64() - 0.5);
+                let footprint = self.footprint(i as f64 + dx, j as f64 + dy);
+                let Some((r, weight)) = self.get_ray(i, j, dx, dy) else {
+                    for &(p, w) in &footprint {
+                        film.splat(p, w, &black);
+                    }
+                    continue;
+                };
+
+                let (sample, light_passes) = if split_light {
+                    let (radiance, light) =
+                        Self::light_passes(&r, world, background, lights, MAX_DEPTH);
+                    (weight * radiance, Some(light))
+                } else if self.spectral {
+                    let mut lambda = SampledWavelengths::sample_uniform(random_f64());
+                    let radiance = Self::ray_color_spectral(
+                        &r,
+                        world,
+                        background,
+                        lights,
+                        MAX_DEPTH,
+                        None,
+                        &mut lambda,
+                    );
+                    (weight * radiance.to_rgb(&lambda), None)
+                } else {
+                    let radiance = Self::ray_color(&r, world, background, lights, MAX_DEPTH, None);
+                    (weight * radiance, None)
+                };
+                let l = self.exposure * sample.luminance();
+                luminance += l;
+                luminance_squared += l * l;
+                for &(p, w) in &footprint {
+                    film.splat(p, w, &sample);
+                }
+                if aovs.is_empty() {
+                    continue;
+                }
+
+                let hit = world.hit_object(&r, Interval::new(0.001, f64::INFINITY));
+                for (k, &aov) in aovs.iter().enumerate() {
+                    let id = match (aov, &hit) {
+                        (Aov::ObjectId, Some((object, _))) => object + 1,
+                        (Aov::MaterialId, Some((_, rec))) => material_ids
+                            .get(&Self::material_key(&rec.material))
+                            .copied()
+                            .unwrap_or(0),
+                        (Aov::ObjectId | Aov::MaterialId, None) => 0,
+                        (Aov::Variance, _) => continue,
+                        _ => {
+                            let rec = hit.as_ref().map(|(_, rec)| rec);
+                            let value = Self::pass_value(aov, &r, rec, light_passes.as_ref());
+                            for &(p, w) in &footprint {
+                                film.splat_pass(k, p, &((w * weight) * value.clone()));
+                            }
+                            continue;
+                        }
+                    };
+                    match ids[k].iter_mut().find(|(i, _)| *i == id) {
+                        Some((_, count)) => *count += 1,
+                        None => ids[k].push((id, 1)),
+                    }
+                }
+            }
+
+            let p = j * self.image_width + i;
+            let scale = 1.0 / self.samples_per_pixel as f64;
+            for (k, aov) in aovs.iter().enumerate() {
+                let value = match aov {
+                    Aov::ObjectId | Aov::MaterialId => {
+                        let id = ids[k]
+                            .iter()
+                            .max_by_key(|(_, count)| *count)
+                            .map_or(0, |(id, _)| *id) as f64;
+                        Color::new(id, id, id)
+                    }
+                    Aov::Variance => {
+                        // Of the mean of the samples, rather than of a single one.
+                        let mean = scale * luminance;
+                        let v = (scale * luminance_squared - mean * mean).max(0.0) * scale;
+                        Color::new(v, v, v)
+                    }
+                    _ => continue,
+                };
+                film.set_pass(k, p, value);
+            }
+        }
+        film
+    }
+
+    // Materials are told apart by the allocation they share.
+    fn material_key(material: &Arc<dyn Material>) -> usize {
+        Arc::as_ptr(material) as *const () as usize
+    }
+
+    // What a camera ray that meets `rec` first adds to a pass other than the IDs, given the
+    // split of the light it brings back.
+    fn pass_value(aov: Aov, r: &Ray, rec: Option<&HitRecord>, light: Option<&[Color; 5]>) -> Color {
+        if let (true, Some(light)) = (aov.is_light(), light) {
+            // The light passes are declared in the order of the split.
+            return light[aov as usize - Aov::Emission as usize].clone();
+        }
+        let Some(rec) = rec else {
+            return Color::new(0.0, 0.0, 0.0);
+        };
+        match aov {
+            Aov::Depth => {
+                let depth = rec.t * r.direction().length();
+                Color::new(depth, depth, depth)
+            }
+            Aov::Normal => rec.normal.clone().into(),
+            Aov::Position => rec.p.clone().into(),
+            Aov::Albedo => rec.material.albedo(rec),
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    // The pixels within the filter's radius of the image position `x`, `y`, in pixels from the
+    // top left pixel's center, as indices along with the filter's weight for each. Only pixels
+    // in the region rendered are reached, so that those around it stay black.
+    fn footprint(&self, x: f64, y: f64) -> Vec<(usize, f64)> {
+        let radius = self.filter_radius;
+        let [x0, y0, x1, y1] = self
+            .region
+            .unwrap_or([0, 0, self.image_width, self.image_height]);
+        let span = |v: f64, start: usize, end: usize| {
+            let first = (v - radius).ceil().max(start as f64) as usize;
+            let last = ((v + radius).floor() as isize).min(end as isize - 1);
+            (first as isize..=last)
+                .map(|k| (k as usize, self.filter.evaluate(k as f64 - v, radius)))
+                .collect::<Vec<_>>()
+        };
+        let columns = span(x, x0, x1);
+        span(y, y0, y1)
+            .into_iter()
+            .flat_map(|(row, wy)| {
+                columns
+                    .iter()
+                    .map(move |&(column, wx)| (row * self.image_width + column, wx * wy))
+            })
+            .collect()
+    }
+
+    // Get a camera ray for the pixel at location i,j, through the point `dx`, `dy` pixels off
+    // its center, and the weight of its contribution, or None where the point falls outside the
+    // projection's image.
+    fn get_ray(&self, i: usize, j: usize, dx: f64, dy: f64) -> Option<(Ray, f64)> {
+        let pixel_sample = &self.pixel00_loc
+            + ((i as f64 + dx) * &self.pixel_delta_u)
+            + ((j as f64 + dy) * &self.pixel_delta_v);
+
+        // Position of the sample in the image, both coordinates in [0, 1] from the top left.
+        let x = (i as f64 + 0.5 + dx) / self.image_width as f64;
+        let y = (j as f64 + 0.5 + dy) / self.image_height as f64;
+        let time = self.shutter_open + random_f64() * (self.shutter_close - self.shutter_open);
+
+        let ray = match &self.projection {
+            Projection::Perspective => {
+                let origin = if self.defocus_radius > 0.0 {
+                    let (a, b) = self.aperture.sample();
+                    let (cx, cy) = (
+                        self.cats_eye * (2.0 * x - 1.0),
+                        self.cats_eye * (1.0 - 2.0 * y),
+                    );
+                    if (a - cx).powi(2) + (b - cy).powi(2) > 1.0 {
+                        return None;
+                    }
+                    &self.center + self.defocus_radius * (a * &self.u + b * &self.v)
+                } else {
+                    self.center.clone()
+                };
+                let ray_direction = pixel_sample - &origin;
+                Ray::new(&origin, &ray_direction, time)
+            }
+            Projection::Orthographic { .. } => Ray::new(&pixel_sample, &-&self.w, time),
+            Projection::Equirectangular { ipd } => {
+                let (y, eye) = match ipd {
+                    Some(ipd) if y < 0.5 => (2.0 * y, -0.5 * ipd),
+                    Some(ipd) => (2.0 * y - 1.0, 0.5 * ipd),
+                    None => (y, 0.0),
+                };
+                let phi = 2.0 * PI * (x - 0.5);
+                let theta = PI * y;
+                let direction = theta.cos() * &self.v
+                    + theta.sin() * (phi.sin() * &self.u - phi.cos() * &self.w);
+                // Each eye sits on a circle around the center, offset sideways from the
+                // horizontal direction it looks in.
+                let sideways = phi.cos() * &self.u + phi.sin() * &self.w;
+                Ray::new(&(&self.center + eye * sideways), &direction, time)
+            }
+            Projection::Fisheye { fov, mapping } => {
+                let a = 2.0 * x - 1.0;
+                let b = 1.0 - 2.0 * y;
+                let r = (a * a + b * b).sqrt();
+                if r > 1.0 {
+                    return None;
+                }
+                let fov = *fov;
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * 0.5 * fov,
+                    FisheyeMapping::Equisolid => {
+                        2.0 * (r * (0.25 * fov).sin()).clamp(-1.0, 1.0).asin()
+                    }
+                };
+                let phi = b.atan2(a);
+                let direction = theta.cos() * -&self.w
+                    + theta.sin() * (phi.cos() * &self.u + phi.sin() * &self.v);
+                Ray::new(&self.center, &direction, time)
+            }
+            Projection::Cubemap => {
+                let (column, row) = ((3.0 * x).min(2.999), (2.0 * y).min(1.999));
+                let face = column as usize + 3 * row as usize;
+                let a = 2.0 * column.fract() - 1.0;
+                let b = 2.0 * row.fract() - 1.0;
+                let (u, v, w) = (&self.u, &self.v, &self.w);
+                let (forward, up) = match face {
+                    0 => (u.clone(), v.clone()),
+                    1 => (-u, v.clone()),
+                    2 => (v.clone(), w.clone()),
+                    3 => (-v, -w),
+                    4 => (w.clone(), v.clone()),
+                    _ => (-w, v.clone()),
+                };
+                let right = forward.cross(&up);
+                let direction = forward + a * right - b * up;
+                Ray::new(&self.center, &direction, time)
+            }
+            Projection::Lens(lens) => {
+                let (ray, weight) = lens.sample_ray(x, y)?;
+                let (o, d) = (ray.origin(), ray.direction());
+                let origin = &self.center + o.x() * &self.u + o.y() * &self.v + o.z() * &self.w;
+                let direction = d.x() * &self.u + d.y() * &self.v + d.z() * &self.w;
+                return Some((Ray::new(&origin, &direction, time), weight));
+            }
+        };
+        Some((ray, 1.0))
+    }
+
+    fn ray_color(
+        r: &Ray,
+        world: &dyn Hittable,
+        background: &Background,
+        lights: &LightSampler,
+        depth: u8,
+        previous: Option<Bounce>,
+    ) -> Color {
+        let (emitted, scattered) = Self::trace(r, world, background, lights, depth, previous);
+        emitted + scattered
+    }
+
+    // Light arriving along `r`, as what the surface it meets or the background emits and what
+    // that surface scatters.
+    fn trace(
+        r: &Ray,
+        world: &dyn Hittable,
+        background: &Background,
+        lights: &LightSampler,
+        depth: u8,
+        previous: Option<Bounce>,
+    ) -> (Color, Color) {
+        let black = Color::new(0.0, 0.0, 0.0);
+        if depth == 0 {
+            log(depth, || "path cut off".to_string());
+            return (black.clone(), black);
+        }
+
+        let Some(rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) else {
+            let weight = previous.map_or(1.0, |bounce| {
+                power_heuristic(bounce.pdf, background.pdf(&r.direction()))
+            });
+            let radiance = background.radiance(&r.direction());
+            log(depth, || {
+                format!("background: radiance {}, weight {}", radiance, weight)
+            });
+            return (weight * radiance, black);
+        };
+        Self::log_hit(r, &rec, depth);
+
+        let emitted = Self::emission_weight(&rec, lights, previous) * rec.material.emitted(&rec);
+        if emitted.luminance() > 0.0 {
+            log(depth, || format!("emitted {}", emitted));
+        }
+        let Some((scattered, attenuation)) = Self::scatter(r, &rec) else {
+            log(depth, || "absorbed".to_string());
+            return (emitted, black);
+        };
+
+        let direct = Self::sample_direct(r, &rec, world, background, lights).fold(
+            black,
+            |direct, (f, light, weight)| {
+                log(depth, || {
+                    format!(
+                        "light from {}: radiance {}, f {}, weight {}",
+                        light.wi, light.radiance, f, weight
+                    )
+                });
+                direct + weight * (f * light.radiance)
+            },
+        );
+        log(depth, || {
+            format!(
+                "scattered towards {}, attenuation {}",
+                scattered.direction(),
+                attenuation
+            )
+        });
+        let bounce = Self::bounce(r, &rec, &scattered);
+        let indirect =
+            attenuation * Self::ray_color(&scattered, world, background, lights, depth - 1, bounce);
+        (emitted, direct + indirect)
+    }
+
+    // Light arriving along a camera ray, along with its split into emission, then direct
+    // diffuse, direct specular, indirect diffuse and indirect specular light. Light counts as
+    // direct when the first surface scatters it straight from a light, whether found by light
+    // sampling or by the scattered ray.
+    fn light_passes(
+        r: &Ray,
+        world: &dyn Hittable,
+        background: &Background,
+        lights: &LightSampler,
+        depth: u8,
+    ) -> (Color, [Color; 5]) {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let mut passes: [Color; 5] = std::array::from_fn(|_| black.clone());
+
+        let Some(rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) else {
+            passes[0] = background.radiance(&r.direction());
+            return (passes[0].clone(), passes);
+        };
+        passes[0] = rec.material.emitted(&rec);
+        if let Some((scattered, attenuation)) = Self::scatter(r, &rec) {
+            let mut split = |light: Color, pass: usize, wi: &Vec3| {
+                let diffuse = rec.material.diffuse_fraction(r, &rec, wi);
+                passes[pass] = passes[pass].clone() + diffuse * light.clone();
+                passes[pass + 1] = passes[pass + 1].clone() + (1.0 - diffuse) * light;
+            };
+
+            for (f, light, weight) in Self::sample_direct(r, &rec, world, background, lights) {
+                split(weight * (f * light.radiance), 1, &light.wi);
+            }
+            let bounce = Self::bounce(r, &rec, &scattered);
+            let (found, further) =
+                Self::trace(&scattered, world, background, lights, depth - 1, bounce);
+            let wi = scattered.direction().unit();
+            split(attenuation.clone() * found, 1, &wi);
+            split(attenuation * further, 3, &wi);
+        }
+
+        let radiance = passes.iter().fold(black, |sum, pass| sum + pass.clone());
+        (radiance, passes)
+    }
+
+    fn ray_color_spectral(
+        r: &Ray,
+        world: &dyn Hittable,
+        background: &Background,
+        lights: &LightSampler,
+        depth: u8,
+        previous: Option<Bounce>,
+        lambda: &mut SampledWavelengths,
+    ) -> SampledSpectrum {
+        if depth == 0 {
+            log(depth, || "path cut off".to_string());
+            return SampledSpectrum::splat(0.0);
+        }
+
+        let Some(rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) else {
+            let weight = previous.map_or(1.0, |bounce| {
+                power_heuristic(bounce.pdf, background.pdf(&r.direction()))
+            });
+            let radiance =
+                SampledSpectrum::from_illuminant(&background.radiance(&r.direction()), lambda);
+            log(depth, || {
+                format!("background: radiance {:?}, weight {}", radiance, weight)
+            });
+            return weight * radiance;
+        };
+        Self::log_hit(r, &rec, depth);
+
+        let emitted = Self::emission_weight(&rec, lights, previous)
+            * rec.material.emitted_spectral(&rec, lambda);
+        let Some((scattered, attenuation)) = rec.material.scatter_spectral(r, &rec, lambda) else {
+            log(depth, || "absorbed".to_string());
+            return emitted;
+        };
+        log(depth, || {
+            format!(
+                "emitted {:?}, scattered towards {}, attenuation {:?}",
+                emitted,
+                scattered.direction(),
+                attenuation
+            )
+        });
+
+        let direct = Self::sample_direct(r, &rec, world, background, lights).fold(
+            SampledSpectrum::splat(0.0),
+            |direct, (f, light, weight)| {
+                // Emitting surfaces may have a spectrum of their own.
+                let radiance = match &light.surface {
+                    Some(surface) => surface.material.emitted_spectral(surface, lambda),
+                    None => SampledSpectrum::from_illuminant(&light.radiance, lambda),
+                };
+                direct + weight * (SampledSpectrum::from_albedo(&f, lambda) * radiance)
+            },
+        );
+        let bounce = Self::bounce(r, &rec, &scattered);
+        emitted
+            + direct
+            + attenuation
+                * Self::ray_color_spectral(
+                    &scattered,
+                    world,
+                    background,
+                    lights,
+                    depth - 1,
+                    bounce,
+                    lambda,
+                )
+    }
+
+    fn log_hit(r: &Ray, rec: &HitRecord, depth: u8) {
+        log(depth, || {
+            format!(
+                "hit {} at {} (t = {}), normal {}{}, from {} along {}",
+                rec.material.name(),
+                rec.p,
+                rec.t,
+                rec.normal,
+                if rec.front_face { "" } else { " (back face)" },
+                r.origin(),
+                r.direction()
+            )
+        });
+    }
+
+    // Weight of the emission found at `rec` against the chance that the previous bounce sampled
+    // the same light directly.
+    fn emission_weight(rec: &HitRecord, lights: &LightSampler, previous: Option<Bounce>) -> f64 {
+        match (previous, &rec.light) {
+            (Some(bounce), Some(light)) => {
+                let light_pdf = lights.pmf(&bounce.p, light) * light.pdf(&bounce.p, rec);
+                power_heuristic(bounce.pdf, light_pdf)
+            }
+            _ => 1.0,
+        }
+    }
+
+    // Next event estimation: samples the background and one light picked by the light sampler.
+    // Each unblocked sample is returned as the scattering function, the light sample and the
+    // combined weight of the sample, its multiple importance sampling weight divided by the
+    // density of having picked it.
+    fn sample_direct(
+        r: &Ray,
+        rec: &HitRecord,
+        world: &dyn Hittable,
+        background: &Background,
+        lights: &LightSampler,
+    ) -> impl Iterator<Item = (Color, LightSample, f64)> {
+        let from_background = background.sample().and_then(|light| {
+            let (f, pdf) = rec.material.eval(r, rec, &light.wi)?;
+            let weight = power_heuristic(light.pdf, pdf) / light.pdf;
+            Self::unoccluded(world, r, rec, f, light, weight)
+        });
+
+        // Points and directions are never hit by scattered rays, so only samples on emitting
+        // surfaces need weighting against the material's.
+        let from_light = lights.sample(&rec.p).and_then(|(light, probability)| {
+            let sample = light.sample(&rec.p)?;
+            let (f, pdf) = rec.material.eval(r, rec, &sample.wi)?;
+            let light_pdf = probability * sample.pdf;
+            let weight = if sample.surface.is_some() {
+                power_heuristic(light_pdf, pdf) / light_pdf
+            } else {
+                1.0 / light_pdf
+            };
+            Self::unoccluded(world, r, rec, f, sample, weight)
+        });
+
+        [from_background, from_light].into_iter().flatten()
+    }
+
+    // Scatters the ray off the material hit, keeping the scattered ray on the colour channel the
+    // path follows once dispersion has picked one.
+    fn scatter(r: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let (scattered, attenuation) = rec.material.scatter(r, rec)?;
+        let channel = scattered.channel().or(r.channel());
+        Some((scattered.with_channel(channel), attenuation))
+    }
+
+    fn unoccluded(
+        world: &dyn Hittable,
+        r: &Ray,
+        rec: &HitRecord,
+        f: Color,
+        light: LightSample,
+        weight: f64,
+    ) -> Option<(Color, LightSample, f64)> {
+        if f.luminance() <= 0.0 {
+            return None;
+        }
+
+        // Stop short of the sampled point, which may lie on the light's own surface. Media along
+        // the way dim the light instead of blocking it.
+        let shadow_ray = Ray::new(&rec.p, &light.wi, r.time());
+        let transmittance =
+            world.transmittance(&shadow_ray, Interval::new(0.001, light.distance - 0.001));
+        if transmittance <= 0.0 {
+            return None;
+        }
+        Some((f, light, weight * transmittance))
+    }
+
+    // The bounce at `rec` for materials that take part in next event estimation. A zero density
+    // means the direction came from a part of the material that `eval` leaves out, which light
+    // sampling can't have covered.
+    fn bounce(r: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Bounce> {
+        rec.material
+            .eval(r, rec, &scattered.direction().unit())
+            .map(|(_, pdf)| pdf)
+            .filter(|&pdf| pdf > 0.0)
+            .map(|pdf| Bounce {
+                p: rec.p.clone(),
+                pdf,
+            })
+    }
+}
@@ -0,0 +1,102 @@
+use std::io::{Error, ErrorKind};
+
+// Part of the image to render, as its left, top, right and bottom edges, the right and bottom
+// ones exclusive.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Region {
+    Pixels([usize; 4]),
+    // As fractions of the image's width and height, rounded to the nearest pixel edges.
+    Normalized([f64; 4]),
+}
+
+impl Region {
+    // The region's edges in pixels in an image of `width` by `height` pixels. Fails when it's
+    // empty or reaches outside the image.
+    pub(crate) fn bounds(self, width: usize, height: usize) -> Result<[usize; 4], Error> {
+        let bounds = match self {
+            Region::Pixels(bounds) => bounds,
+            Region::Normalized([x0, y0, x1, y1]) => {
+                if [x0, y0, x1, y1].iter().any(|v| !(0.0..=1.0).contains(v)) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "region: normalized coordinates must lie in [0, 1]",
+                    ));
+                }
+                let scale = |v: f64, size: usize| (v * size as f64).round() as usize;
+                [
+                    scale(x0, width),
+                    scale(y0, height),
+                    scale(x1, width),
+                    scale(y1, height),
+                ]
+            }
+        };
+
+        let [x0, y0, x1, y1] = bounds;
+        if x0 >= x1 || y0 >= y1 || x1 > width || y1 > height {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "region: {},{} to {},{} is empty or outside the {}x{} image",
+                    x0, y0, x1, y1, width, height
+                ),
+            ));
+        }
+        Ok(bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_pixel_bounds() {
+        let bounds = Region::Pixels([10, 20, 30, 40]).bounds(100, 50).unwrap();
+        assert_eq!(bounds, [10, 20, 30, 40]);
+        let whole = Region::Pixels([0, 0, 100, 50]).bounds(100, 50).unwrap();
+        assert_eq!(whole, [0, 0, 100, 50]);
+    }
+
+    #[test]
+    fn rounds_normalized_bounds_to_pixel_edges() {
+        let bounds = Region::Normalized([0.0, 0.25, 0.504, 1.0])
+            .bounds(100, 50)
+            .unwrap();
+        assert_eq!(bounds, [0, 13, 50, 50]);
+    }
+
+    #[test]
+    fn rejects_empty_and_outlying_regions() {
+        for (region, message) in [
+            (
+                Region::Normalized([0.0, 0.0, 1.5, 1.0]),
+                "region: normalized coordinates must lie in [0, 1]",
+            ),
+            (
+                Region::Normalized([-0.1, 0.0, 0.5, 1.0]),
+                "region: normalized coordinates must lie in [0, 1]",
+            ),
+            (
+                Region::Normalized([0.5, 0.0, 0.5, 1.0]),
+                "region: 50,0 to 50,50 is empty or outside the 100x50 image",
+            ),
+            (
+                Region::Pixels([30, 20, 10, 40]),
+                "region: 30,20 to 10,40 is empty or outside the 100x50 image",
+            ),
+            (
+                Region::Pixels([0, 0, 101, 50]),
+                "region: 0,0 to 101,50 is empty or outside the 100x50 image",
+            ),
+            (
+                Region::Pixels([0, 0, 100, 51]),
+                "region: 0,0 to 100,51 is empty or outside the 100x50 image",
+            ),
+        ] {
+            let e = region.bounds(100, 50).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidInput);
+            assert_eq!(e.to_string(), message);
+        }
+    }
+}
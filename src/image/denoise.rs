@@ -0,0 +1,172 @@
+use crate::{image::Image, physics::Color};
+
+// Iterations of the à-trous filter, whose footprint doubles with each.
+const ATROUS_ITERATIONS: u32 = 5;
+
+// Radius in pixels of the joint bilateral filter, and the standard deviation of its Gaussian.
+const BILATERAL_RADIUS: isize = 6;
+const BILATERAL_SIGMA: f64 = 3.0;
+
+// How sharply the filters stop at changes in the surface: the power of the cosine between
+// normals, the distance off the tangent plane relative to the distance from the camera, and
+// the change in luminance relative to its standard deviation.
+const NORMAL_POWER: i32 = 64;
+const SIGMA_PLANE: f64 = 0.02;
+const SIGMA_LUMINANCE: f64 = 4.0;
+
+// Filter that smooths the noise of a render while keeping edges, guided by buffers of the
+// surfaces the camera sees.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Denoiser {
+    // A single wide pass weighing every neighbour.
+    Bilateral,
+    // Edge-avoiding à-trous wavelet filter, as in SVGF: repeated passes of a 5×5 kernel with
+    // its taps spread further apart each time.
+    Atrous,
+}
+
+// Per-pixel buffers guiding the filter: the first surface's albedo, normal, position and
+// distance from the camera, and the variance of the pixel's luminance estimate.
+pub(crate) struct Guides<'a> {
+    pub(crate) albedo: &'a Image,
+    pub(crate) normal: &'a Image,
+    pub(crate) position: &'a Image,
+    pub(crate) depth: &'a Image,
+    pub(crate) variance: &'a Image,
+}
+
+impl Denoiser {
+    // Filters the lighting with the albedo divided out, so that texture detail passes through
+    // untouched, and multiplies it back in.
+    pub(crate) fn denoise(self, image: &Image, guides: &Guides) -> Image {
+        let (width, height) = (image.width(), image.height());
+        let modulation = |x: usize, y: usize| {
+            let a = guides.albedo.pixel(x, y);
+            let channel = |v: f64| if v > 0.01 { v } else { 1.0 };
+            Color::new(channel(a.r()), channel(a.g()), channel(a.b()))
+        };
+
+        let mut color = Vec::with_capacity(width * height);
+        let mut variance = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let albedo = modulation(x, y);
+                let c = image.pixel(x, y);
+                color.push(Color::new(
+                    c.r() / albedo.r(),
+                    c.g() / albedo.g(),
+                    c.b() / albedo.b(),
+                ));
+                variance.push(guides.variance.pixel(x, y).r() / albedo.luminance().powi(2));
+            }
+        }
+
+        match self {
+            Denoiser::Bilateral => {
+                let taps: Vec<(isize, f64)> = (-BILATERAL_RADIUS..=BILATERAL_RADIUS)
+                    .map(|d| {
+                        let d2 = (d * d) as f64;
+                        (d, (-d2 / (2.0 * BILATERAL_SIGMA * BILATERAL_SIGMA)).exp())
+                    })
+                    .collect();
+                color = filter_pass(&color, &variance, guides, 1, &taps).0;
+            }
+            Denoiser::Atrous => {
+                let taps = [
+                    (-2, 1.0 / 16.0),
+                    (-1, 1.0 / 4.0),
+                    (0, 3.0 / 8.0),
+                    (1, 1.0 / 4.0),
+                    (2, 1.0 / 16.0),
+                ];
+                for i in 0..ATROUS_ITERATIONS {
+                    (color, variance) = filter_pass(&color, &variance, guides, 1 << i, &taps);
+                }
+            }
+        }
+
+        let pixels = color
+            .into_iter()
+            .enumerate()
+            .map(|(i, c)| modulation(i % width, i / width) * c)
+            .collect();
+        Image::new(width, height, pixels)
+    }
+}
+
+// One pass of a kernel that is the product of the 1D `taps` along each axis, the taps `step`
+// pixels apart, with each neighbour's weight reduced by how unlike the center pixel its
+// surface and luminance are. Returns the filtered colours and their variances.
+fn filter_pass(
+    color: &[Color],
+    variance: &[f64],
+    guides: &Guides,
+    step: isize,
+    taps: &[(isize, f64)],
+) -> (Vec<Color>, Vec<f64>) {
+    let (width, height) = (guides.normal.width(), guides.normal.height());
+    let mut out_color = Vec::with_capacity(color.len());
+    let mut out_variance = Vec::with_capacity(variance.len());
+
+    for y in 0..height {
+        for x in 0..width {
+            let p = y * width + x;
+            let normal = guides.normal.pixel(x, y);
+            let position = guides.position.pixel(x, y);
+            let depth = guides.depth.pixel(x, y).r();
+            let luminance = color[p].luminance();
+            // Pixels showing the background have no surface to compare with.
+            let surface = depth > 0.0;
+            let sigma_luminance = SIGMA_LUMINANCE * variance[p].max(0.0).sqrt() + 1e-6;
+
+            let mut sum = Color::new(0.0, 0.0, 0.0);
+            let mut sum_variance = 0.0;
+            let mut total = 0.0;
+            for &(dy, hy) in taps {
+                let qy = y as isize + dy * step;
+                if qy < 0 || qy >= height as isize {
+                    continue;
+                }
+                for &(dx, hx) in taps {
+                    let qx = x as isize + dx * step;
+                    if qx < 0 || qx >= width as isize {
+                        continue;
+                    }
+                    let (qx, qy) = (qx as usize, qy as usize);
+                    let q = qy * width + qx;
+
+                    let mut weight = hx * hy;
+                    if q != p {
+                        let q_surface = guides.depth.pixel(qx, qy).r() > 0.0;
+                        if surface != q_surface {
+                            continue;
+                        }
+                        if surface {
+                            let q_normal = guides.normal.pixel(qx, qy);
+                            let cosine = normal.r() * q_normal.r()
+                                + normal.g() * q_normal.g()
+                                + normal.b() * q_normal.b();
+                            let q_position = guides.position.pixel(qx, qy);
+                            let offset = normal.r() * (q_position.r() - position.r())
+                                + normal.g() * (q_position.g() - position.g())
+                                + normal.b() * (q_position.b() - position.b());
+                            weight *= cosine.max(0.0).powi(NORMAL_POWER)
+                                * (-offset.abs() / (SIGMA_PLANE * depth)).exp();
+                        }
+                        weight *=
+                            (-(color[q].luminance() - luminance).abs() / sigma_luminance).exp();
+                    }
+
+                    sum = sum + weight * color[q].clone();
+                    sum_variance += weight * weight * variance[q];
+                    total += weight;
+                }
+            }
+
+            out_color.push((1.0 / total) * sum);
+            out_variance.push(sum_variance / (total * total));
+        }
+    }
+
+    (out_color, out_variance)
+}
@@ -0,0 +1,119 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Error, ErrorKind, Write},
+};
+
+use crate::image::Image;
+
+// One layer of a multi-layer image: the first `channels.len()` components of each of the
+// image's colours, stored as `name.channel`, or as just the channel names for an unnamed layer.
+pub(crate) struct Layer<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) channels: &'a [&'a str],
+    pub(crate) image: &'a Image,
+}
+
+// Writes an uncompressed single-part scanline OpenEXR file of 32-bit float channels.
+pub(crate) fn save(image: &Image, path: &str) -> Result<(), Error> {
+    save_layers(
+        &[Layer {
+            name: "",
+            channels: &["R", "G", "B"],
+            image,
+        }],
+        path,
+    )
+}
+
+pub(crate) fn save_layers(layers: &[Layer], path: &str) -> Result<(), Error> {
+    let (width, height) = (layers[0].image.width(), layers[0].image.height());
+    if layers
+        .iter()
+        .any(|l| l.image.width() != width || l.image.height() != height)
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{}: layers differ in size", path),
+        ));
+    }
+
+    // Channels are stored in alphabetical order, each with the component of the pixel colours
+    // it takes.
+    let mut channels: Vec<(String, &Image, usize)> = layers
+        .iter()
+        .flat_map(|layer| {
+            layer.channels.iter().enumerate().map(|(k, channel)| {
+                let name = if layer.name.is_empty() {
+                    channel.to_string()
+                } else {
+                    format!("{}.{}", layer.name, channel)
+                };
+                (name, layer.image, k)
+            })
+        })
+        .collect();
+    channels.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut header = Vec::new();
+    header.extend(20000630u32.to_le_bytes());
+    // Version 2, allowing names longer than 31 bytes.
+    header.extend(0x402u32.to_le_bytes());
+
+    let mut chlist = Vec::new();
+    for (name, _, _) in &channels {
+        chlist.extend(name.as_bytes());
+        chlist.push(0);
+        // 32-bit float, not perceptually linear, reserved bytes, no subsampling.
+        chlist.extend(2i32.to_le_bytes());
+        chlist.extend([0, 0, 0, 0]);
+        chlist.extend(1i32.to_le_bytes());
+        chlist.extend(1i32.to_le_bytes());
+    }
+    chlist.push(0);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let attributes: [(&str, &str, Vec<u8>); 8] = [
+        ("channels", "chlist", chlist),
+        ("compression", "compression", vec![0]),
+        ("dataWindow", "box2i", window.clone()),
+        ("displayWindow", "box2i", window),
+        ("lineOrder", "lineOrder", vec![0]),
+        ("pixelAspectRatio", "float", 1.0f32.to_le_bytes().to_vec()),
+        ("screenWindowCenter", "v2f", [0u8; 8].to_vec()),
+        ("screenWindowWidth", "float", 1.0f32.to_le_bytes().to_vec()),
+    ];
+    for (name, kind, value) in attributes {
+        header.extend(name.as_bytes());
+        header.push(0);
+        header.extend(kind.as_bytes());
+        header.push(0);
+        header.extend((value.len() as i32).to_le_bytes());
+        header.extend(value);
+    }
+    header.push(0);
+
+    // One scanline per chunk, located through a table of offsets after the header.
+    let line_size = 4 * width * channels.len();
+    let chunk_size = 8 + line_size;
+    let first_chunk = header.len() + 8 * height;
+
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&header)?;
+    for y in 0..height {
+        out.write_all(&((first_chunk + y * chunk_size) as u64).to_le_bytes())?;
+    }
+    for y in 0..height {
+        out.write_all(&(y as i32).to_le_bytes())?;
+        out.write_all(&(line_size as i32).to_le_bytes())?;
+        for (_, image, k) in &channels {
+            for x in 0..width {
+                let color = image.pixel(x, y);
+                let value = [color.r(), color.g(), color.b()][*k];
+                out.write_all(&(value as f32).to_le_bytes())?;
+            }
+        }
+    }
+    out.flush()
+}
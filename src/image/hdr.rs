@@ -0,0 +1,120 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+};
+
+use crate::{image::Image, physics::Color};
+
+// Reads Radiance RGBE (.hdr) images, both flat and with the adaptive run-length encoding that
+// most tools write. Only the standard top-to-bottom, left-to-right orientation is supported.
+pub(crate) fn load(path: &str) -> Result<Image, Error> {
+    let bytes = fs::read(path)?;
+    let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, msg));
+
+    let mut pos = 0;
+    let mut next_line = || -> Option<String> {
+        let start = pos;
+        let end = start + bytes[start..].iter().position(|&b| b == b'\n')?;
+        pos = end + 1;
+        Some(String::from_utf8_lossy(&bytes[start..end]).into_owned())
+    };
+
+    let magic = next_line().ok_or_else(|| invalid("missing header"))?;
+    if !magic.starts_with("#?") {
+        return Err(invalid("not a Radiance HDR file"));
+    }
+    // Header variables end at the first empty line.
+    loop {
+        let line = next_line().ok_or_else(|| invalid("unterminated header"))?;
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format.trim() != "32-bit_rle_rgbe" {
+                return Err(invalid("unsupported pixel format"));
+            }
+        }
+    }
+
+    let resolution = next_line().ok_or_else(|| invalid("missing resolution"))?;
+    let tokens: Vec<&str> = resolution.split_whitespace().collect();
+    let (height, width) = match tokens.as_slice() {
+        ["-Y", h, "+X", w] => (
+            h.parse::<usize>().map_err(|_| invalid("bad height"))?,
+            w.parse::<usize>().map_err(|_| invalid("bad width"))?,
+        ),
+        _ => return Err(invalid("unsupported image orientation")),
+    };
+
+    let data = &bytes[pos..];
+    let mut rgbe = vec![0u8; width * height * 4];
+    let mut at = 0;
+    for row in rgbe.chunks_exact_mut(width * 4) {
+        at = read_scanline(data, at, row, width).ok_or_else(|| invalid("truncated pixel data"))?;
+    }
+
+    let pixels = rgbe
+        .chunks_exact(4)
+        .map(|p| {
+            if p[3] == 0 {
+                Color::new(0.0, 0.0, 0.0)
+            } else {
+                let scale = 2f64.powi(p[3] as i32 - 136);
+                Color::new(
+                    (p[0] as f64 + 0.5) * scale,
+                    (p[1] as f64 + 0.5) * scale,
+                    (p[2] as f64 + 0.5) * scale,
+                )
+            }
+        })
+        .collect();
+
+    Ok(Image::new(width, height, pixels))
+}
+
+// Decodes one scanline of RGBE pixels into `row`, returning the position after it.
+fn read_scanline(data: &[u8], mut at: usize, row: &mut [u8], width: usize) -> Option<usize> {
+    let header = data.get(at..at + 4)?;
+    let is_rle = (8..0x8000).contains(&width)
+        && header[0] == 2
+        && header[1] == 2
+        && ((header[2] as usize) << 8 | header[3] as usize) == width;
+
+    if !is_rle {
+        row.copy_from_slice(data.get(at..at + width * 4)?);
+        return Some(at + width * 4);
+    }
+
+    // Each channel is stored separately as runs or literal spans.
+    at += 4;
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *data.get(at)? as usize;
+            at += 1;
+            if count > 128 {
+                let count = count - 128;
+                let value = *data.get(at)?;
+                at += 1;
+                if x + count > width {
+                    return None;
+                }
+                for i in 0..count {
+                    row[(x + i) * 4 + channel] = value;
+                }
+                x += count;
+            } else {
+                if count == 0 || x + count > width {
+                    return None;
+                }
+                let values = data.get(at..at + count)?;
+                at += count;
+                for (i, value) in values.iter().enumerate() {
+                    row[(x + i) * 4 + channel] = *value;
+                }
+                x += count;
+            }
+        }
+    }
+    Some(at)
+}
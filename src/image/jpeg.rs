@@ -0,0 +1,60 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+};
+
+use jpeg_decoder::{Decoder, PixelFormat};
+
+use crate::{image::Image, physics::Color};
+
+// Reads baseline, progressive and lossless JPEG images in greyscale or RGB.
+pub(crate) fn load(path: &str) -> Result<Image, Error> {
+    decode(&fs::read(path)?)
+}
+
+// Decodes a JPEG image held in memory.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Image, Error> {
+    let invalid = |msg: String| Error::new(ErrorKind::InvalidData, msg);
+
+    let mut decoder = Decoder::new(bytes);
+    let data = decoder.decode().map_err(|e| invalid(e.to_string()))?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| invalid("missing image header".to_string()))?;
+    let (width, height) = (info.width as usize, info.height as usize);
+
+    if info.pixel_format == PixelFormat::CMYK32 {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "CMYK JPEG images are not supported",
+        ));
+    }
+    if data.len() < width * height * info.pixel_format.pixel_bytes() {
+        return Err(invalid("truncated pixel data".to_string()));
+    }
+
+    let pixel = |p: usize| match info.pixel_format {
+        PixelFormat::L8 => {
+            let v = data[p] as f64 / 255.0;
+            Color::new(v, v, v)
+        }
+        // Samples come out in native byte order.
+        PixelFormat::L16 => {
+            let v = u16::from_ne_bytes([data[2 * p], data[2 * p + 1]]) as f64 / 65535.0;
+            Color::new(v, v, v)
+        }
+        PixelFormat::RGB24 => Color::new(
+            data[3 * p] as f64 / 255.0,
+            data[3 * p + 1] as f64 / 255.0,
+            data[3 * p + 2] as f64 / 255.0,
+        ),
+        // Rejected above.
+        PixelFormat::CMYK32 => unreachable!(),
+    };
+
+    Ok(Image::new(
+        width,
+        height,
+        (0..width * height).map(pixel).collect(),
+    ))
+}
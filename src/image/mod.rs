@@ -0,0 +1,149 @@
+mod denoise;
+mod exr;
+mod hdr;
+mod jpeg;
+mod png;
+mod pnm;
+
+use std::{
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+use crate::physics::Color;
+
+pub(crate) use denoise::{Denoiser, Guides};
+pub(crate) use exr::Layer;
+
+// In-memory image of floating point colours, stored row by row from the top left.
+#[derive(Clone, Debug)]
+pub(crate) struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    pub(crate) fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    // Loads an image, picking the decoder from the file extension. Values are returned as stored
+    // in the file, so 8-bit images still carry their sRGB encoding while HDR images are linear.
+    pub(crate) fn load(path: &str) -> Result<Self, Error> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        let image = match extension.as_deref() {
+            Some("ppm") | Some("pgm") | Some("pnm") => pnm::load(path),
+            Some("hdr") | Some("pic") => hdr::load(path),
+            Some("png") => png::load(path),
+            Some("jpg") | Some("jpeg") => jpeg::load(path),
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{}: unsupported image format", path),
+            )),
+        };
+        // Errors from reading the file don't name it yet.
+        image.map_err(|e| {
+            if e.to_string().starts_with(path) {
+                e
+            } else {
+                Error::new(e.kind(), format!("{}: {}", path, e))
+            }
+        })
+    }
+
+    // Decodes an image held in memory, such as one embedded in a model, given its MIME type.
+    pub(crate) fn decode(bytes: &[u8], mime_type: &str) -> Result<Self, Error> {
+        match mime_type {
+            "image/png" => png::decode(bytes),
+            "image/jpeg" => jpeg::decode(bytes),
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("unsupported image type {}", mime_type),
+            )),
+        }
+    }
+
+    // Saves a linear image, picking the encoder from the file extension.
+    pub(crate) fn save(&self, path: &str) -> Result<(), Error> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("ppm") => pnm::save(self, path),
+            Some("exr") => exr::save(self, path),
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{}: unsupported image format", path),
+            )),
+        }
+    }
+
+    // Saves images of the same size together as the layers of one OpenEXR file.
+    pub(crate) fn save_layers(layers: &[Layer], path: &str) -> Result<(), Error> {
+        if !path.to_ascii_lowercase().ends_with(".exr") {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{}: layers can only be saved to OpenEXR files", path),
+            ));
+        }
+        exr::save_layers(layers, path)
+    }
+
+    // The file name `path` with `suffix` added before its extension, for images written
+    // alongside one another.
+    pub(crate) fn suffixed_path(path: &str, suffix: &str) -> String {
+        let path = Path::new(path);
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("ppm");
+        path.with_file_name(format!("{}_{}.{}", stem, suffix, extension))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    pub(crate) fn pixel(&self, x: usize, y: usize) -> &Color {
+        &self.pixels[y * self.width + x]
+    }
+
+    // The `width` by `height` pixels with their top left corner at `x`, `y`.
+    pub(crate) fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        let pixels = (y..y + height)
+            .flat_map(|row| (x..x + width).map(move |column| self.pixel(column, row).clone()))
+            .collect();
+        Image::new(width, height, pixels)
+    }
+
+    // Decodes sRGB encoded values to linear ones, for images holding colours rather than data.
+    pub(crate) fn srgb_to_linear(mut self) -> Self {
+        let decode = |c: f64| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        for pixel in &mut self.pixels {
+            *pixel = Color::new(decode(pixel.r()), decode(pixel.g()), decode(pixel.b()));
+        }
+        self
+    }
+}
@@ -0,0 +1,54 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+};
+
+use ::png::{BitDepth, ColorType, Decoder, Transformations};
+
+use crate::{image::Image, physics::Color};
+
+// Reads PNG images of any colour type and bit depth. Palettes and bit depths below eight are
+// expanded, and the alpha channel is dropped.
+pub(crate) fn load(path: &str) -> Result<Image, Error> {
+    decode(&fs::read(path)?)
+}
+
+// Decodes a PNG image held in memory.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Image, Error> {
+    let invalid = |e: ::png::DecodingError| Error::new(ErrorKind::InvalidData, e.to_string());
+
+    let mut decoder = Decoder::new(bytes);
+    decoder.set_transformations(Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(invalid)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer).map_err(invalid)?;
+    let (width, height) = (frame.width as usize, frame.height as usize);
+
+    let channels = match frame.color_type {
+        ColorType::Grayscale => 1,
+        ColorType::GrayscaleAlpha => 2,
+        ColorType::Rgb => 3,
+        ColorType::Rgba => 4,
+        // Expanded to RGB by the transformations above.
+        ColorType::Indexed => unreachable!(),
+    };
+    let sample = |i: usize| match frame.bit_depth {
+        BitDepth::Sixteen => {
+            u16::from_be_bytes([buffer[2 * i], buffer[2 * i + 1]]) as f64 / 65535.0
+        }
+        _ => buffer[i] as f64 / 255.0,
+    };
+
+    let pixels = (0..width * height)
+        .map(|p| {
+            let i = p * channels;
+            if channels < 3 {
+                let v = sample(i);
+                Color::new(v, v, v)
+            } else {
+                Color::new(sample(i), sample(i + 1), sample(i + 2))
+            }
+        })
+        .collect();
+    Ok(Image::new(width, height, pixels))
+}
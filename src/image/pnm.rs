@@ -0,0 +1,111 @@
+use std::{
+    fs::{self, File},
+    io::{Error, ErrorKind, Write},
+};
+
+use crate::{
+    image::Image,
+    physics::{write_color, Color},
+};
+
+// Reads binary and ASCII PPM and PGM files (P2, P3, P5 and P6).
+pub(crate) fn load(path: &str) -> Result<Image, Error> {
+    let bytes = fs::read(path)?;
+    let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, msg));
+
+    // Header tokens are whitespace separated and may be interleaved with '#' comments.
+    let mut pos = 0;
+    let mut next_token = || -> Option<String> {
+        loop {
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if pos < bytes.len() && bytes[pos] == b'#' {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+            break;
+        }
+        let start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        (start < pos).then(|| String::from_utf8_lossy(&bytes[start..pos]).into_owned())
+    };
+
+    let magic = next_token().ok_or_else(|| invalid("missing magic number"))?;
+    let mut header = [0usize; 3];
+    for value in &mut header {
+        *value = next_token()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| invalid("bad header"))?;
+    }
+    let [width, height, max_value] = header;
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid("bad maximum value"));
+    }
+    let scale = 1.0 / max_value as f64;
+
+    let channels = match magic.as_str() {
+        "P2" | "P5" => 1,
+        "P3" | "P6" => 3,
+        _ => return Err(invalid("unsupported PNM variant")),
+    };
+    let count = width * height * channels;
+
+    let samples: Vec<f64> = if magic == "P2" || magic == "P3" {
+        (0..count)
+            .map(|_| {
+                next_token()
+                    .and_then(|t| t.parse::<f64>().ok())
+                    .map(|v| v * scale)
+                    .ok_or_else(|| invalid("truncated pixel data"))
+            })
+            .collect::<Result<_, _>>()?
+    } else {
+        // A single whitespace byte separates the header from the raster.
+        let data = &bytes[(pos + 1).min(bytes.len())..];
+        let bytes_per_sample = if max_value < 256 { 1 } else { 2 };
+        if data.len() < count * bytes_per_sample {
+            return Err(invalid("truncated pixel data"));
+        }
+        (0..count)
+            .map(|i| {
+                let v = if bytes_per_sample == 1 {
+                    data[i] as f64
+                } else {
+                    u16::from_be_bytes([data[2 * i], data[2 * i + 1]]) as f64
+                };
+                v * scale
+            })
+            .collect()
+    };
+
+    let pixels = samples
+        .chunks_exact(channels)
+        .map(|c| {
+            if channels == 1 {
+                Color::new(c[0], c[0], c[0])
+            } else {
+                Color::new(c[0], c[1], c[2])
+            }
+        })
+        .collect();
+
+    Ok(Image::new(width, height, pixels))
+}
+
+// Writes a gamma encoded ASCII PPM file (P3).
+pub(crate) fn save(image: &Image, path: &str) -> Result<(), Error> {
+    let mut out_file = File::create(path)?;
+    writeln!(out_file, "P3")?;
+    writeln!(out_file, "{} {} 255", image.width(), image.height())?;
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            write_color(&mut out_file, image.pixel(x, y), 1)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use std::f64::consts::PI;
+
+use crate::{
+    physics::{Point3, Vec3},
+    shapes::Aabb,
+};
+
+// Conservative description of where a light is, which way it emits and how much, from which
+// the light hierarchy estimates its contribution to a point. Emission leaves the surface
+// normals inside the cone of half angle θo around `w`, and spreads up to θe beyond them.
+#[derive(Clone, Debug)]
+pub(crate) struct LightBounds {
+    bounds: Aabb,
+    // Luminance of the emitted flux.
+    phi: f64,
+    w: Vec3,
+    cos_theta_o: f64,
+    cos_theta_e: f64,
+    two_sided: bool,
+}
+
+impl LightBounds {
+    pub(crate) fn new(
+        bounds: Aabb,
+        phi: f64,
+        w: Vec3,
+        cos_theta_o: f64,
+        cos_theta_e: f64,
+        two_sided: bool,
+    ) -> Self {
+        LightBounds {
+            bounds,
+            phi,
+            w: w.unit(),
+            cos_theta_o,
+            cos_theta_e,
+            two_sided,
+        }
+    }
+
+    // Bounds of a light emitting in all directions from within `bounds`.
+    pub(crate) fn omnidirectional(bounds: Aabb, phi: f64) -> Self {
+        Self::new(bounds, phi, Vec3::new(0.0, 0.0, 1.0), -1.0, 0.0, false)
+    }
+
+    pub(crate) fn phi(&self) -> f64 {
+        self.phi
+    }
+
+    pub(crate) fn centroid(&self) -> Point3 {
+        0.5 * (self.bounds.min() + self.bounds.max())
+    }
+
+    pub(crate) fn union(a: &LightBounds, b: &LightBounds) -> Self {
+        let (w, cos_theta_o) = cone_union((&a.w, a.cos_theta_o), (&b.w, b.cos_theta_o));
+        LightBounds {
+            bounds: Aabb::surrounding(&a.bounds, &b.bounds),
+            phi: a.phi + b.phi,
+            w,
+            cos_theta_o,
+            cos_theta_e: a.cos_theta_e.min(b.cos_theta_e),
+            two_sided: a.two_sided || b.two_sided,
+        }
+    }
+
+    // Estimate of the light arriving at `p` from everything inside the bounds: the power over the
+    // squared distance, times the cosine of the smallest angle between the emission cone and the
+    // direction to `p` that the bounds allow (Conty Estevez and Kulla 2018).
+    pub(crate) fn importance(&self, p: &Point3) -> f64 {
+        let pc = self.centroid();
+        let half_diagonal = 0.5 * (self.bounds.max() - self.bounds.min()).length();
+        let d2 = (p - &pc).length_squared().max(half_diagonal);
+
+        let wi = p - &pc;
+        let wi = if wi.length_squared() > 0.0 {
+            wi.unit()
+        } else {
+            self.w.clone()
+        };
+        let mut cos_theta_w = self.w.dot(&wi);
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        let sin_theta_w = safe_sqrt(1.0 - cos_theta_w * cos_theta_w);
+
+        // Directions from the bounds towards `p`, as a cone around `wi`.
+        let cos_theta_b = self.cos_subtended(p, &pc);
+        let sin_theta_b = safe_sqrt(1.0 - cos_theta_b * cos_theta_b);
+
+        let sin_theta_o = safe_sqrt(1.0 - self.cos_theta_o * self.cos_theta_o);
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+
+        (self.phi * cos_theta_p / d2).max(0.0)
+    }
+
+    // Cosine of the half angle of the cone from `p` containing the bounding sphere of the bounds.
+    fn cos_subtended(&self, p: &Point3, pc: &Point3) -> f64 {
+        let radius = 0.5 * (self.bounds.max() - self.bounds.min()).length();
+        let d2 = (p - pc).length_squared();
+        if d2 < radius * radius {
+            return -1.0;
+        }
+        safe_sqrt(1.0 - radius * radius / d2)
+    }
+}
+
+// Smallest cone containing two cones given by their axes and the cosines of their half angles.
+fn cone_union(a: (&Vec3, f64), b: (&Vec3, f64)) -> (Vec3, f64) {
+    let theta_a = a.1.clamp(-1.0, 1.0).acos();
+    let theta_b = b.1.clamp(-1.0, 1.0).acos();
+    let theta_d = a.0.dot(b.0).clamp(-1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return (a.0.clone(), a.1);
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return (b.0.clone(), b.1);
+    }
+
+    let theta_o = 0.5 * (theta_a + theta_d + theta_b);
+    let axis = a.0.cross(b.0);
+    if theta_o >= PI || axis.length_squared() == 0.0 {
+        return (a.0.clone(), -1.0);
+    }
+
+    // Turn the axis of `a` towards `b` until the cone reaches around both.
+    let theta_r = theta_o - theta_a;
+    let k = axis.unit();
+    let w = theta_r.cos() * a.0
+        + theta_r.sin() * k.cross(a.0)
+        + (1.0 - theta_r.cos()) * k.dot(a.0) * &k;
+    (w.unit(), theta_o.cos())
+}
+
+// Cosine of the difference of two angles, or one when it would be negative.
+fn cos_sub_clamped(sin_a: f64, cos_a: f64, sin_b: f64, cos_b: f64) -> f64 {
+    if cos_a > cos_b {
+        return 1.0;
+    }
+    cos_a * cos_b + sin_a * sin_b
+}
+
+// Sine of the difference of two angles, or zero when it would be negative.
+fn sin_sub_clamped(sin_a: f64, cos_a: f64, sin_b: f64, cos_b: f64) -> f64 {
+    if cos_a > cos_b {
+        return 0.0;
+    }
+    sin_a * cos_b - cos_a * sin_b
+}
+
+fn safe_sqrt(x: f64) -> f64 {
+    x.max(0.0).sqrt()
+}
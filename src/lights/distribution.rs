@@ -0,0 +1,170 @@
+// Piecewise-constant 1D distribution over [0, 1) proportional to the given function values.
+#[derive(Clone, Debug)]
+pub(crate) struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub(crate) fn new(func: Vec<f64>) -> Self {
+        let n = func.len();
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for (i, f) in func.iter().enumerate() {
+            cdf.push(cdf[i] + f.abs() / n as f64);
+        }
+
+        // A function that is zero everywhere is sampled uniformly.
+        let integral = cdf[n];
+        if integral > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        } else {
+            cdf.iter_mut()
+                .enumerate()
+                .for_each(|(i, c)| *c = i as f64 / n as f64);
+        }
+
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub(crate) fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // Maps a uniform sample to a point in [0, 1), returning it with its density and the index of
+    // the segment it falls in.
+    pub(crate) fn sample(&self, xi: f64) -> (f64, f64, usize) {
+        let n = self.func.len();
+        let offset = self.cdf.partition_point(|&c| c <= xi).clamp(1, n) - 1;
+
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (xi - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+
+        ((offset as f64 + du) / n as f64, self.pdf(offset), offset)
+    }
+
+    // Density of the segment with the given index.
+    pub(crate) fn pdf(&self, index: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[index].abs() / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+// Piecewise-constant 2D distribution over [0, 1)^2, sampled by picking a row from the marginal
+// distribution and then a column from that row's conditional distribution.
+#[derive(Clone, Debug)]
+pub(crate) struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // `func` holds `width * height` values stored row by row.
+    pub(crate) fn new(func: &[f64], width: usize, height: usize) -> Self {
+        let conditional: Vec<Distribution1D> = func
+            .chunks_exact(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|c| c.integral()).collect());
+
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    // Returns the sampled (u, v), where v selects the row, and its density.
+    pub(crate) fn sample(&self, xi: (f64, f64)) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(xi.1);
+        let (u, pdf_u, _) = self.conditional[row].sample(xi.0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub(crate) fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = ((v * self.conditional.len() as f64) as usize).min(self.conditional.len() - 1);
+        let conditional = &self.conditional[row];
+        let column = ((u * conditional.func.len() as f64) as usize).min(conditional.func.len() - 1);
+
+        if self.marginal.integral() > 0.0 {
+            conditional.func[column].abs() / self.marginal.integral()
+        } else {
+            1.0
+        }
+    }
+}
+
+// Discrete distribution proportional to the given weights that picks an index in constant time
+// (Walker's alias method, built with Vose's algorithm).
+#[derive(Clone, Debug)]
+pub(crate) struct AliasTable {
+    // For each bin, the probability of keeping its own index rather than taking the alias, the
+    // alias, and the probability of the bin's own index.
+    bins: Vec<(f64, usize, f64)>,
+}
+
+impl AliasTable {
+    // Returns None when the weights are empty or sum to zero.
+    pub(crate) fn new(weights: &[f64]) -> Option<Self> {
+        let sum: f64 = weights.iter().map(|w| w.max(0.0)).sum();
+        if weights.is_empty() || sum <= 0.0 {
+            return None;
+        }
+
+        let n = weights.len();
+        let pmf: Vec<f64> = weights.iter().map(|w| w.max(0.0) / sum).collect();
+        let mut bins: Vec<(f64, usize, f64)> = pmf.iter().map(|&p| (1.0, 0, p)).collect();
+
+        // Scale so that the average bin holds one, then let each under-full bin borrow the rest of
+        // its space from an over-full one.
+        let mut scaled: Vec<f64> = pmf.iter().map(|p| p * n as f64).collect();
+        let (mut under, mut over): (Vec<usize>, Vec<usize>) =
+            (0..n).partition(|&i| scaled[i] < 1.0);
+        while let (Some(&u), Some(&o)) = (under.last(), over.last()) {
+            under.pop();
+            bins[u].0 = scaled[u];
+            bins[u].1 = o;
+            scaled[o] -= 1.0 - scaled[u];
+            if scaled[o] < 1.0 {
+                over.pop();
+                under.push(o);
+            }
+        }
+        // Whatever is left is full up to rounding.
+        for i in under.into_iter().chain(over) {
+            bins[i].0 = 1.0;
+        }
+
+        Some(AliasTable { bins })
+    }
+
+    // Maps a uniform sample to an index, returned with its probability.
+    pub(crate) fn sample(&self, xi: f64) -> (usize, f64) {
+        let n = self.bins.len();
+        let x = xi * n as f64;
+        let bin = (x as usize).min(n - 1);
+        let up = x - bin as f64;
+        let index = if up < self.bins[bin].0 {
+            bin
+        } else {
+            self.bins[bin].1
+        };
+        (index, self.bins[index].2)
+    }
+
+    pub(crate) fn pmf(&self, index: usize) -> f64 {
+        self.bins[index].2
+    }
+}
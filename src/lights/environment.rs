@@ -0,0 +1,173 @@
+use std::{f64::consts::PI, io::Error, path::Path};
+
+use crate::{
+    image::Image,
+    lights::{Distribution2D, LightSample, PhysicalSky},
+    physics::{Color, Vec3},
+    utils::random_f64,
+};
+
+// What a ray sees when it leaves the scene.
+#[derive(Debug)]
+pub(crate) enum Background {
+    // White-to-blue sky gradient. It is not sampled directly, so it only lights the scene
+    // through scattered rays.
+    Gradient,
+    // The same colour in every direction, lighting the scene like the gradient.
+    Uniform(Color),
+    Map(EnvironmentMap),
+    Sky(Box<PhysicalSky>),
+}
+
+impl Background {
+    pub(crate) fn radiance(&self, direction: &Vec3) -> Color {
+        match self {
+            Background::Gradient => {
+                let unit_direction = direction.unit();
+                let a = 0.5 * (unit_direction.y() + 1.0);
+
+                (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
+            }
+            Background::Uniform(color) => color.clone(),
+            Background::Map(map) => map.radiance(direction),
+            Background::Sky(sky) => sky.radiance(direction),
+        }
+    }
+
+    // Samples a direction towards the background for next event estimation.
+    pub(crate) fn sample(&self) -> Option<LightSample> {
+        match self {
+            Background::Gradient | Background::Uniform(_) => None,
+            Background::Map(map) => map.sample(),
+            Background::Sky(sky) => sky.sample(),
+        }
+    }
+
+    // Solid angle density with which `sample` picks the given direction.
+    pub(crate) fn pdf(&self, direction: &Vec3) -> f64 {
+        match self {
+            Background::Gradient | Background::Uniform(_) => 0.0,
+            Background::Map(map) => map.pdf(direction),
+            Background::Sky(sky) => sky.pdf(direction),
+        }
+    }
+}
+
+// Distant light from an equirectangular image surrounding the scene, with +y up and the centre
+// of the image towards -z. Directions are importance sampled in proportion to the luminance of
+// the pixels, so that a small bright sun is found by shadow rays instead of by chance.
+#[derive(Debug)]
+pub(crate) struct EnvironmentMap {
+    image: Image,
+    // Rotation about the vertical axis in radians.
+    rotation: f64,
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub(crate) fn new(image: Image) -> Self {
+        let (width, height) = (image.width(), image.height());
+
+        // Rows near the poles cover less solid angle than those at the horizon.
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            for x in 0..width {
+                func.push(image.pixel(x, y).luminance().max(0.0) * sin_theta);
+            }
+        }
+        let distribution = Distribution2D::new(&func, width, height);
+
+        EnvironmentMap {
+            image,
+            rotation: 0.0,
+            intensity: 1.0,
+            distribution,
+        }
+    }
+
+    // Loads a Radiance HDR image, or an 8-bit one which is decoded from sRGB.
+    pub(crate) fn load(path: &str) -> Result<Self, Error> {
+        let is_hdr = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("hdr") || e.eq_ignore_ascii_case("pic"));
+
+        let image = Image::load(path)?;
+        Ok(Self::new(if is_hdr {
+            image
+        } else {
+            image.srgb_to_linear()
+        }))
+    }
+
+    pub(crate) fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees.to_radians();
+        self
+    }
+
+    pub(crate) fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    // Image coordinates in [0, 1)^2 of a world direction, with v running down from the top row.
+    fn direction_to_uv(&self, direction: &Vec3) -> (f64, f64) {
+        let d = direction.unit();
+        let (sin_r, cos_r) = self.rotation.sin_cos();
+        let (x, z) = (cos_r * d.x() + sin_r * d.z(), cos_r * d.z() - sin_r * d.x());
+
+        let phi = x.atan2(-z);
+        let theta = d.y().clamp(-1.0, 1.0).acos();
+        ((0.5 + phi / (2.0 * PI)).rem_euclid(1.0), theta / PI)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3 {
+        let phi = 2.0 * PI * (u - 0.5);
+        let theta = PI * v;
+        let (x, z) = (theta.sin() * phi.sin(), -theta.sin() * phi.cos());
+
+        let (sin_r, cos_r) = self.rotation.sin_cos();
+        Vec3::new(cos_r * x - sin_r * z, theta.cos(), cos_r * z + sin_r * x)
+    }
+
+    // Nearest pixel lookup, so that the radiance is constant over each cell of the sampling
+    // distribution.
+    fn lookup(&self, u: f64, v: f64) -> Color {
+        let (width, height) = (self.image.width(), self.image.height());
+        let x = ((u * width as f64) as usize).min(width - 1);
+        let y = ((v * height as f64) as usize).min(height - 1);
+        self.intensity * self.image.pixel(x, y).clone()
+    }
+
+    pub(crate) fn radiance(&self, direction: &Vec3) -> Color {
+        let (u, v) = self.direction_to_uv(direction);
+        self.lookup(u, v)
+    }
+
+    pub(crate) fn sample(&self) -> Option<LightSample> {
+        let ((u, v), map_pdf) = self.distribution.sample((random_f64(), random_f64()));
+        let sin_theta = (PI * v).sin();
+        if map_pdf <= 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            wi: self.uv_to_direction(u, v),
+            radiance: self.lookup(u, v),
+            pdf: map_pdf / (2.0 * PI * PI * sin_theta),
+            distance: f64::INFINITY,
+            surface: None,
+        })
+    }
+
+    pub(crate) fn pdf(&self, direction: &Vec3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
@@ -0,0 +1,362 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+};
+
+use crate::physics::{Onb, Vec3};
+
+// Luminous intensity distribution of a real fixture, read from an IES LM-63 photometric file.
+// Only type C photometry is supported, which is what nearly all architectural fixtures use:
+// vertical angles run from the nadir at 0° to the zenith at 180°, and horizontal angles turn
+// around the fixture's vertical axis.
+#[derive(Debug)]
+pub(crate) struct IesProfile {
+    // Vertical and horizontal angles in degrees, both increasing.
+    vertical: Vec<f64>,
+    horizontal: Vec<f64>,
+    // Candela for each horizontal angle in turn, one value per vertical angle.
+    candela: Vec<f64>,
+    peak: f64,
+}
+
+impl IesProfile {
+    pub(crate) fn load(path: &str) -> Result<Self, Error> {
+        Self::parse(&fs::read_to_string(path)?, path)
+    }
+
+    // Parses the text of an IES file. `name` only appears in error messages.
+    pub(crate) fn parse(text: &str, name: &str) -> Result<Self, Error> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, format!("{}: {}", name, msg));
+
+        // The header is free-form keyword lines up to the TILT line, after which everything is
+        // numbers separated by whitespace or commas.
+        let mut lines = text.lines();
+        let tilt = lines
+            .find_map(|line| line.trim().strip_prefix("TILT="))
+            .ok_or_else(|| invalid("missing TILT line"))?;
+        let rest: Vec<&str> = lines.collect();
+        let mut numbers = rest
+            .iter()
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f64>());
+        let mut next = || -> Result<f64, Error> {
+            numbers
+                .next()
+                .ok_or_else(|| invalid("unexpected end of file"))?
+                .map_err(|_| invalid("bad number"))
+        };
+
+        match tilt.trim() {
+            "NONE" => {}
+            // Tilt factors only matter for lamps mounted at an angle; skip over them.
+            "INCLUDE" => {
+                next()?;
+                let count = next()? as usize;
+                for _ in 0..2 * count {
+                    next()?;
+                }
+            }
+            _ => return Err(invalid("tilt data in a separate file is not supported")),
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()?;
+        // Units and luminous opening dimensions.
+        for _ in 0..4 {
+            next()?;
+        }
+        let ballast_factor = next()?;
+        // Ballast-lamp factor and input watts.
+        for _ in 0..2 {
+            next()?;
+        }
+
+        if photometric_type != 1.0 {
+            return Err(invalid("only type C photometry is supported"));
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(invalid("empty candela table"));
+        }
+
+        let vertical = (0..vertical_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let horizontal = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let candela = (0..vertical_count * horizontal_count)
+            .map(|_| next().map(|c| (c * multiplier * ballast_factor).max(0.0)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if vertical.windows(2).any(|w| w[0] >= w[1]) || horizontal.windows(2).any(|w| w[0] >= w[1])
+        {
+            return Err(invalid("angles must be increasing"));
+        }
+
+        let peak = candela.iter().cloned().fold(0.0, f64::max);
+        Ok(IesProfile {
+            vertical,
+            horizontal,
+            candela,
+            peak,
+        })
+    }
+
+    // Highest radiant intensity of the fixture in W/sr, converting candela at 683 lm/W.
+    pub(crate) fn peak_intensity(&self) -> f64 {
+        self.peak / 683.0
+    }
+
+    // Intensity towards world direction `w` relative to the peak, with the fixture oriented so
+    // that its nadir points along the frame's `w` axis and horizontal angle 0° along its `u`.
+    pub(crate) fn scale(&self, frame: &Onb, w: &Vec3) -> f64 {
+        if self.peak <= 0.0 {
+            return 0.0;
+        }
+        let local = frame.to_local(&w.unit());
+        let theta = local.z().clamp(-1.0, 1.0).acos().to_degrees();
+        let phi = local.y().atan2(local.x()).to_degrees().rem_euclid(360.0);
+        self.candela(theta, self.fold(phi)) / self.peak
+    }
+
+    // Maps a horizontal angle into the range the file covers, using the symmetry implied by its
+    // last horizontal angle.
+    fn fold(&self, phi: f64) -> f64 {
+        let last = self.horizontal[self.horizontal.len() - 1];
+        if last <= 0.0 {
+            0.0
+        } else if last <= 90.0 {
+            let phi = phi % 180.0;
+            if phi > 90.0 {
+                180.0 - phi
+            } else {
+                phi
+            }
+        } else if last <= 180.0 && phi > 180.0 {
+            360.0 - phi
+        } else {
+            phi
+        }
+    }
+
+    // Bilinear lookup in the candela table. There is no light outside the vertical range.
+    fn candela(&self, theta: f64, phi: f64) -> f64 {
+        let Some((v, tv)) = bracket(&self.vertical, theta) else {
+            return 0.0;
+        };
+        let (h, th) = bracket(&self.horizontal, phi).unwrap_or_else(|| {
+            // Past either end of the horizontal range, which for a full 360° table means the
+            // gap between the last angle and the first one again.
+            (self.horizontal.len() - 1, 0.0)
+        });
+
+        let n = self.vertical.len();
+        let at =
+            |h: usize, v: usize| self.candela[h.min(self.horizontal.len() - 1) * n + v.min(n - 1)];
+        let lerp = |h: usize| (1.0 - tv) * at(h, v) + tv * at(h, v + 1);
+        (1.0 - th) * lerp(h) + th * lerp(h + 1)
+    }
+}
+
+// Finds the interval of `angles` containing `x` and the position within it.
+fn bracket(angles: &[f64], x: f64) -> Option<(usize, f64)> {
+    let first = angles[0];
+    let last = angles[angles.len() - 1];
+    if x < first - 1e-9 || x > last + 1e-9 {
+        return None;
+    }
+    if angles.len() == 1 {
+        return Some((0, 0.0));
+    }
+    let i = angles
+        .partition_point(|&a| a <= x)
+        .clamp(1, angles.len() - 1)
+        - 1;
+    let t = ((x - angles[i]) / (angles[i + 1] - angles[i])).clamp(0.0, 1.0);
+    Some((i, t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An IES file of type C photometry with a multiplier of 2 and a ballast factor of 0.5,
+    // which cancel out. `candela` lists one row per horizontal angle.
+    fn ies(tilt: &str, vertical: &[f64], horizontal: &[f64], candela: &[&[f64]]) -> String {
+        let list = |values: &[f64]| {
+            let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+            values.join(" ")
+        };
+        let rows: Vec<String> = candela.iter().map(|row| list(row)).collect();
+        format!(
+            "IESNA:LM-63-2002\n[TEST] fixture\nTILT={}\n1 1000 2 {} {} 1 1 0 0 0\n0.5 1 100\n\
+             {}\n{}\n{}\n",
+            tilt,
+            vertical.len(),
+            horizontal.len(),
+            list(vertical),
+            list(horizontal),
+            rows.join("\n")
+        )
+    }
+
+    // Candela towards vertical angle `theta` from the nadir and horizontal angle `phi`, for a
+    // fixture whose nadir points along +z and whose 0° plane is along +x.
+    fn candela(profile: &IesProfile, theta: f64, phi: f64) -> f64 {
+        let frame = Onb::from_tangent(&Vec3::new(0.0, 0.0, 1.0), &Vec3::new(1.0, 0.0, 0.0));
+        let (theta, phi) = (theta.to_radians(), phi.to_radians());
+        let w = Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        );
+        profile.scale(&frame, &w) * profile.peak
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn parses_a_candela_table() {
+        let text = ies("NONE", &[0.0, 90.0], &[0.0], &[&[100.0, -5.0]]);
+        let profile = IesProfile::parse(&text, "test.ies").unwrap();
+        assert_eq!(profile.candela, [100.0, 0.0]);
+        assert_near(profile.peak_intensity(), 100.0 / 683.0);
+    }
+
+    #[test]
+    fn skips_included_tilt_data() {
+        let text = ies(
+            "INCLUDE\n1\n2\n0 90\n1 1",
+            &[0.0, 90.0],
+            &[0.0],
+            &[&[100.0, 50.0]],
+        );
+        let profile = IesProfile::parse(&text, "test.ies").unwrap();
+        assert_eq!(profile.candela, [100.0, 50.0]);
+    }
+
+    #[test]
+    fn interpolates_between_vertical_angles() {
+        let text = ies("NONE", &[0.0, 90.0], &[0.0], &[&[100.0, 40.0]]);
+        let profile = IesProfile::parse(&text, "test.ies").unwrap();
+        assert_near(candela(&profile, 0.0, 0.0), 100.0);
+        assert_near(candela(&profile, 45.0, 0.0), 70.0);
+        // No light above the last vertical angle.
+        assert_near(candela(&profile, 120.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn folds_an_axially_symmetric_table() {
+        let text = ies("NONE", &[0.0, 90.0], &[0.0], &[&[100.0, 40.0]]);
+        let profile = IesProfile::parse(&text, "test.ies").unwrap();
+        for phi in [0.0, 45.0, 135.0, 250.0, 359.0] {
+            assert_near(candela(&profile, 90.0, phi), 40.0);
+        }
+    }
+
+    #[test]
+    fn folds_a_quadrant_symmetric_table() {
+        let text = ies(
+            "NONE",
+            &[0.0, 90.0],
+            &[0.0, 90.0],
+            &[&[100.0, 100.0], &[100.0, 50.0]],
+        );
+        let profile = IesProfile::parse(&text, "test.ies").unwrap();
+        for (phi, expected) in [
+            (0.0, 100.0),
+            (45.0, 75.0),
+            (90.0, 50.0),
+            (135.0, 75.0),
+            (180.0, 100.0),
+            (270.0, 50.0),
+            (300.0, 100.0 - 50.0 * 60.0 / 90.0),
+        ] {
+            assert_near(candela(&profile, 90.0, phi), expected);
+        }
+    }
+
+    #[test]
+    fn folds_a_bilaterally_symmetric_table() {
+        let text = ies(
+            "NONE",
+            &[0.0, 90.0],
+            &[0.0, 90.0, 180.0],
+            &[&[100.0, 100.0], &[100.0, 50.0], &[100.0, 20.0]],
+        );
+        let profile = IesProfile::parse(&text, "test.ies").unwrap();
+        for (phi, expected) in [
+            (90.0, 50.0),
+            (180.0, 20.0),
+            (270.0, 50.0),
+            (200.0, 50.0 - 30.0 * 70.0 / 90.0),
+            (330.0, 100.0 - 50.0 * 30.0 / 90.0),
+        ] {
+            assert_near(candela(&profile, 90.0, phi), expected);
+        }
+    }
+
+    #[test]
+    fn reads_a_full_table_without_folding() {
+        let text = ies(
+            "NONE",
+            &[0.0, 90.0],
+            &[0.0, 90.0, 180.0, 270.0, 360.0],
+            &[
+                &[100.0, 100.0],
+                &[100.0, 50.0],
+                &[100.0, 20.0],
+                &[100.0, 80.0],
+                &[100.0, 100.0],
+            ],
+        );
+        let profile = IesProfile::parse(&text, "test.ies").unwrap();
+        for (phi, expected) in [(90.0, 50.0), (270.0, 80.0), (315.0, 90.0), (225.0, 50.0)] {
+            assert_near(candela(&profile, 90.0, phi), expected);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let table = |tilt: &str| ies(tilt, &[0.0, 90.0], &[0.0], &[&[100.0, 40.0]]);
+        let header = "TILT=NONE\n1 1000 1 2 1 1 1 0 0 0\n1 1 100\n";
+        for (text, message) in [
+            ("IESNA:LM-63-2002\n".to_string(), "missing TILT line"),
+            (table("lamp.tlt"), "tilt data in a separate file"),
+            (format!("{}0 90\n0\n100", header), "unexpected end of file"),
+            (format!("{}0 90\n0\n100 x", header), "bad number"),
+            (
+                header.replace("1 1 1 0 0 0", "1 2 1 0 0 0") + "0 90\n0\n100 40",
+                "only type C photometry",
+            ),
+            (
+                header.replace("1 2 1 1", "1 0 1 1") + "0\n",
+                "empty candela table",
+            ),
+            (
+                format!("{}90 0\n0\n100 40", header),
+                "angles must be increasing",
+            ),
+        ] {
+            let e = IesProfile::parse(&text, "test.ies").unwrap_err();
+            assert!(
+                e.to_string().starts_with("test.ies: ") && e.to_string().contains(message),
+                "expected {}, got {}",
+                message,
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,72 @@
+mod bounds;
+mod distribution;
+mod environment;
+mod ies;
+mod punctual;
+mod sampler;
+mod sky;
+
+pub(crate) use bounds::LightBounds;
+pub(crate) use distribution::{AliasTable, Distribution2D};
+pub(crate) use environment::{Background, EnvironmentMap};
+pub(crate) use ies::IesProfile;
+pub(crate) use punctual::{DirectionalLight, PointLight, SpotLight};
+pub(crate) use sampler::LightSampler;
+pub(crate) use sky::{PhysicalSky, LUMINANCE_SCALE};
+
+use std::sync::Arc;
+
+use crate::{
+    physics::{Color, Point3, Vec3},
+    shapes::HitRecord,
+};
+
+// Direction towards a sampled point on a light, with the radiance arriving from it and the
+// solid angle density of having picked it.
+#[derive(Clone, Debug)]
+pub(crate) struct LightSample {
+    pub(crate) wi: Vec3,
+    pub(crate) radiance: Color,
+    pub(crate) pdf: f64,
+    // Distance to the sampled point, infinite for lights at infinity.
+    pub(crate) distance: f64,
+    // The sampled point when it lies on emitting geometry, which scattered rays can find as
+    // well, so that the two ways of reaching it are weighted against each other.
+    pub(crate) surface: Option<HitRecord>,
+}
+
+// Light sampled through shadow rays: either one that exists apart from the scene's geometry, or
+// an emitting shape.
+pub(crate) trait Light: std::fmt::Debug + Send + Sync {
+    // Samples the light arriving at `p`. Lights concentrated in a point or a direction report a
+    // density of one.
+    fn sample(&self, p: &Point3) -> Option<LightSample>;
+
+    // Solid angle density with which `sample` at `p` picks the point `hit` on the light. Only
+    // emitting shapes can be hit, so the others keep the default.
+    fn pdf(&self, _p: &Point3, _hit: &HitRecord) -> f64 {
+        0.0
+    }
+
+    // Extent, direction and power of the emission, for choosing between many lights. Lights at
+    // infinity have no bounds.
+    fn bounds(&self) -> Option<LightBounds>;
+
+    // Copy of the light with `emission` applied to what it emits: the intensity of a point or
+    // spot light, or the irradiance of a directional one. Emitting shapes take theirs from their
+    // material and have no copy.
+    fn with_emission(&self, _emission: &dyn Fn(&Color) -> Color) -> Option<Arc<dyn Light>> {
+        None
+    }
+}
+
+// Power heuristic with exponent two for weighting a sample drawn with density `f` against
+// another strategy with density `g`.
+pub(crate) fn power_heuristic(f: f64, g: f64) -> f64 {
+    let (f2, g2) = (f * f, g * g);
+    if f2 + g2 > 0.0 {
+        f2 / (f2 + g2)
+    } else {
+        0.0
+    }
+}
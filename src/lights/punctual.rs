@@ -0,0 +1,233 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    lights::{IesProfile, Light, LightBounds, LightSample},
+    physics::{Color, Onb, Point3, Vec3},
+    shapes::Aabb,
+};
+
+// Lights that emit from a single point or direction and so can't be hit by scattered rays.
+// Power is given in watts and treated as radiant flux in the renderer's radiance units. Point
+// and spot lights can take a measured photometric profile, which scales their intensity by
+// direction relative to its peak.
+
+// Light emitting equally in all directions from a point.
+#[derive(Clone, Debug)]
+pub(crate) struct PointLight {
+    position: Point3,
+    // Radiant intensity, in W/sr.
+    intensity: Color,
+    profile: Option<(Arc<IesProfile>, Onb)>,
+}
+
+impl PointLight {
+    // Point light of the given colour emitting `power` watts in total.
+    pub(crate) fn new(position: Point3, color: Color, power: f64) -> Self {
+        Self::with_intensity(position, (power / (4.0 * PI)) * color)
+    }
+
+    pub(crate) fn with_intensity(position: Point3, intensity: Color) -> Self {
+        PointLight {
+            position,
+            intensity,
+            profile: None,
+        }
+    }
+
+    // Shapes the emission with a photometric profile oriented by `frame`, whose `w` axis is the
+    // fixture's nadir. Give the light the profile's peak intensity to match the fixture's output.
+    pub(crate) fn with_profile(mut self, profile: Arc<IesProfile>, frame: Onb) -> Self {
+        self.profile = Some((profile, frame));
+        self
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: &Point3) -> Option<LightSample> {
+        let scale = profile_scale(&self.profile, &(p - &self.position));
+        if scale <= 0.0 {
+            return None;
+        }
+        sample_point(&self.position, p, scale * self.intensity.clone())
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::omnidirectional(
+            Aabb::new(&self.position, &self.position),
+            4.0 * PI * self.intensity.luminance(),
+        ))
+    }
+
+    fn with_emission(&self, emission: &dyn Fn(&Color) -> Color) -> Option<Arc<dyn Light>> {
+        Some(Arc::new(PointLight {
+            intensity: emission(&self.intensity),
+            ..self.clone()
+        }))
+    }
+}
+
+// Point light restricted to a cone, fading out smoothly between the inner and outer angles.
+#[derive(Clone, Debug)]
+pub(crate) struct SpotLight {
+    position: Point3,
+    direction: Vec3,
+    intensity: Color,
+    cos_inner: f64,
+    cos_outer: f64,
+    profile: Option<(Arc<IesProfile>, Onb)>,
+}
+
+impl SpotLight {
+    // Spotlight of the given colour emitting `power` watts, with the cone's half angles in
+    // degrees.
+    pub(crate) fn new(
+        position: Point3,
+        direction: Vec3,
+        color: Color,
+        power: f64,
+        inner_angle: f64,
+        outer_angle: f64,
+    ) -> Self {
+        let light = Self::with_intensity(position, direction, color, inner_angle, outer_angle);
+        // Flux of a unit intensity cone, approximating the falloff as linear in the cosine.
+        let solid_angle = 2.0 * PI * (1.0 - 0.5 * (light.cos_inner + light.cos_outer));
+        SpotLight {
+            intensity: (power / solid_angle) * light.intensity,
+            ..light
+        }
+    }
+
+    pub(crate) fn with_intensity(
+        position: Point3,
+        direction: Vec3,
+        intensity: Color,
+        inner_angle: f64,
+        outer_angle: f64,
+    ) -> Self {
+        let outer_angle = outer_angle.clamp(0.0, 180.0);
+        let inner_angle = inner_angle.clamp(0.0, outer_angle);
+        SpotLight {
+            position,
+            direction: direction.unit(),
+            intensity,
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.to_radians().cos(),
+            profile: None,
+        }
+    }
+
+    // Shapes the emission within the cone with a photometric profile, as for point lights.
+    pub(crate) fn with_profile(mut self, profile: Arc<IesProfile>, frame: Onb) -> Self {
+        self.profile = Some((profile, frame));
+        self
+    }
+
+    fn falloff(&self, w: &Vec3) -> f64 {
+        let cos_theta = w.dot(&self.direction);
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: &Point3) -> Option<LightSample> {
+        let w = (p - &self.position).unit();
+        let falloff = self.falloff(&w) * profile_scale(&self.profile, &w);
+        if falloff <= 0.0 {
+            return None;
+        }
+        sample_point(&self.position, p, falloff * self.intensity.clone())
+    }
+
+    // Emission is full inside the inner cone and spreads out to the outer one.
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = 2.0
+            * PI
+            * self.intensity.luminance()
+            * ((1.0 - self.cos_inner) + 0.5 * (self.cos_inner - self.cos_outer));
+        let spread = self.cos_outer.acos() - self.cos_inner.acos();
+        Some(LightBounds::new(
+            Aabb::new(&self.position, &self.position),
+            phi,
+            self.direction.clone(),
+            self.cos_inner,
+            spread.cos(),
+            false,
+        ))
+    }
+
+    fn with_emission(&self, emission: &dyn Fn(&Color) -> Color) -> Option<Arc<dyn Light>> {
+        Some(Arc::new(SpotLight {
+            intensity: emission(&self.intensity),
+            ..self.clone()
+        }))
+    }
+}
+
+// Light arriving from a single direction everywhere, like the sun.
+#[derive(Clone, Debug)]
+pub(crate) struct DirectionalLight {
+    // Direction the light travels in.
+    direction: Vec3,
+    // Irradiance on a surface facing the light, in W/m².
+    irradiance: Color,
+}
+
+impl DirectionalLight {
+    pub(crate) fn new(direction: Vec3, color: Color, irradiance: f64) -> Self {
+        DirectionalLight {
+            direction: direction.unit(),
+            irradiance: irradiance * color,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _p: &Point3) -> Option<LightSample> {
+        Some(LightSample {
+            wi: -&self.direction,
+            radiance: self.irradiance.clone(),
+            pdf: 1.0,
+            distance: f64::INFINITY,
+            surface: None,
+        })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+
+    fn with_emission(&self, emission: &dyn Fn(&Color) -> Color) -> Option<Arc<dyn Light>> {
+        Some(Arc::new(DirectionalLight {
+            irradiance: emission(&self.irradiance),
+            ..self.clone()
+        }))
+    }
+}
+
+fn profile_scale(profile: &Option<(Arc<IesProfile>, Onb)>, w: &Vec3) -> f64 {
+    profile
+        .as_ref()
+        .map_or(1.0, |(profile, frame)| profile.scale(frame, w))
+}
+
+fn sample_point(position: &Point3, p: &Point3, intensity: Color) -> Option<LightSample> {
+    let to_light = position - p;
+    let distance = to_light.length();
+    if distance <= 0.0 {
+        return None;
+    }
+    Some(LightSample {
+        wi: to_light / distance,
+        radiance: (1.0 / (distance * distance)) * intensity,
+        pdf: 1.0,
+        distance,
+        surface: None,
+    })
+}
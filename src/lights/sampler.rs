@@ -0,0 +1,278 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    lights::{AliasTable, Light, LightBounds},
+    physics::Point3,
+    shapes::Aabb,
+    utils::random_f64,
+};
+
+// Picks which light to sample for next event estimation. Lights at infinity have no bounds, so
+// the power and hierarchy strategies pick them uniformly, each as likely as the whole set of
+// bounded lights together.
+#[derive(Debug)]
+pub(crate) struct LightSampler {
+    infinite: Vec<Arc<dyn Light>>,
+    bounded: Vec<Arc<dyn Light>>,
+    strategy: Strategy,
+    // Index of each bounded light in `bounded`, keyed by its address.
+    index: HashMap<usize, usize>,
+}
+
+#[derive(Debug)]
+enum Strategy {
+    // Every light, bounded or not, equally likely.
+    Uniform,
+    // Bounded lights in proportion to their power.
+    Power(Option<AliasTable>),
+    // Bounded lights by their estimated contribution to the shading point, descending a
+    // hierarchy of light bounds.
+    Bvh(LightBvh),
+}
+
+impl LightSampler {
+    pub(crate) fn uniform(lights: Vec<Arc<dyn Light>>) -> Self {
+        Self::new(lights, |_| Strategy::Uniform)
+    }
+
+    pub(crate) fn power(lights: Vec<Arc<dyn Light>>) -> Self {
+        Self::new(lights, |bounds| {
+            let power: Vec<f64> = bounds.iter().map(|b| b.phi()).collect();
+            Strategy::Power(AliasTable::new(&power))
+        })
+    }
+
+    pub(crate) fn bvh(lights: Vec<Arc<dyn Light>>) -> Self {
+        Self::new(lights, |bounds| Strategy::Bvh(LightBvh::new(bounds)))
+    }
+
+    fn new(
+        lights: Vec<Arc<dyn Light>>,
+        strategy: impl FnOnce(Vec<LightBounds>) -> Strategy,
+    ) -> Self {
+        let mut infinite = Vec::new();
+        let mut bounded = Vec::new();
+        let mut bounds = Vec::new();
+        for light in lights {
+            match light.bounds() {
+                Some(b) => {
+                    bounds.push(b);
+                    bounded.push(light);
+                }
+                None => infinite.push(light),
+            }
+        }
+        let index = bounded
+            .iter()
+            .enumerate()
+            .map(|(i, light)| (key(light), i))
+            .collect();
+
+        LightSampler {
+            infinite,
+            bounded,
+            strategy: strategy(bounds),
+            index,
+        }
+    }
+
+    // Picks a light for shading `p`, returned with the probability of having picked it.
+    pub(crate) fn sample(&self, p: &Point3) -> Option<(&Arc<dyn Light>, f64)> {
+        let p_infinite = self.infinite_probability();
+        let xi = random_f64();
+        if xi < p_infinite {
+            let n = self.infinite.len();
+            if n == 0 {
+                return None;
+            }
+            let i = ((xi / p_infinite * n as f64) as usize).min(n - 1);
+            return Some((&self.infinite[i], p_infinite / n as f64));
+        }
+
+        let (i, pmf) = match &self.strategy {
+            Strategy::Uniform => {
+                let n = self.bounded.len();
+                (
+                    ((random_f64() * n as f64) as usize).min(n - 1),
+                    1.0 / n as f64,
+                )
+            }
+            Strategy::Power(table) => table.as_ref()?.sample(random_f64()),
+            Strategy::Bvh(bvh) => bvh.sample(p)?,
+        };
+        Some((&self.bounded[i], (1.0 - p_infinite) * pmf))
+    }
+
+    // Probability that `sample` at `p` picks `light`.
+    pub(crate) fn pmf(&self, p: &Point3, light: &Arc<dyn Light>) -> f64 {
+        let p_infinite = self.infinite_probability();
+        let Some(&i) = self.index.get(&key(light)) else {
+            let n = self.infinite.len();
+            return if n > 0 { p_infinite / n as f64 } else { 0.0 };
+        };
+
+        let pmf = match &self.strategy {
+            Strategy::Uniform => 1.0 / self.bounded.len() as f64,
+            Strategy::Power(table) => table.as_ref().map_or(0.0, |t| t.pmf(i)),
+            Strategy::Bvh(bvh) => bvh.pmf(p, i),
+        };
+        (1.0 - p_infinite) * pmf
+    }
+
+    fn infinite_probability(&self) -> f64 {
+        let n = self.infinite.len() as f64;
+        if self.bounded.is_empty() {
+            return 1.0;
+        }
+        match self.strategy {
+            Strategy::Uniform => n / (n + self.bounded.len() as f64),
+            _ => n / (n + 1.0),
+        }
+    }
+}
+
+fn key(light: &Arc<dyn Light>) -> usize {
+    Arc::as_ptr(light) as *const () as usize
+}
+
+#[derive(Debug)]
+struct LightNode {
+    bounds: LightBounds,
+    // The light at a leaf, or the second child of an interior node, whose first child directly
+    // follows it.
+    index: usize,
+    leaf: bool,
+}
+
+// Binary hierarchy over the bounded lights, split at the median centroid along the longest
+// axis. Lights without power are left out and never sampled.
+#[derive(Debug)]
+struct LightBvh {
+    nodes: Vec<LightNode>,
+    // Path from the root to each light's leaf, one bit per level with a set bit for the second
+    // child, keyed by the light's index.
+    trails: HashMap<usize, u64>,
+}
+
+impl LightBvh {
+    fn new(bounds: Vec<LightBounds>) -> Self {
+        let mut lights: Vec<(usize, LightBounds)> = bounds
+            .into_iter()
+            .enumerate()
+            .filter(|(_, b)| b.phi() > 0.0)
+            .collect();
+
+        let mut bvh = LightBvh {
+            nodes: Vec::new(),
+            trails: HashMap::new(),
+        };
+        if !lights.is_empty() {
+            bvh.build(&mut lights, 0, 0);
+        }
+        bvh
+    }
+
+    fn build(
+        &mut self,
+        lights: &mut [(usize, LightBounds)],
+        trail: u64,
+        depth: u32,
+    ) -> LightBounds {
+        // Median splits keep the depth logarithmic, well within the bits of a trail.
+        if lights.len() == 1 {
+            let (light, bounds) = &lights[0];
+            self.trails.insert(*light, trail);
+            self.nodes.push(LightNode {
+                bounds: bounds.clone(),
+                index: *light,
+                leaf: true,
+            });
+            return bounds.clone();
+        }
+
+        let centroids = lights.iter().fold(Aabb::default(), |a, (_, b)| {
+            Aabb::surrounding(&a, &Aabb::new(&b.centroid(), &b.centroid()))
+        });
+        let axis = centroids.longest_axis();
+        lights.sort_by(|(_, a), (_, b)| {
+            let a = a.centroid();
+            let b = b.centroid();
+            [a.x(), a.y(), a.z()][axis].total_cmp(&[b.x(), b.y(), b.z()][axis])
+        });
+
+        let node = self.nodes.len();
+        self.nodes.push(LightNode {
+            bounds: lights[0].1.clone(),
+            index: 0,
+            leaf: false,
+        });
+        let (first, second) = lights.split_at_mut(lights.len() / 2);
+        let a = self.build(first, trail, depth + 1);
+        self.nodes[node].index = self.nodes.len();
+        let b = self.build(second, trail | (1 << depth), depth + 1);
+
+        let bounds = LightBounds::union(&a, &b);
+        self.nodes[node].bounds = bounds.clone();
+        bounds
+    }
+
+    // Probability of going to the first child of an interior node, or None when neither child
+    // can light `p`.
+    fn first_child_probability(&self, node: usize, p: &Point3) -> Option<f64> {
+        let first = self.nodes[node + 1].bounds.importance(p);
+        let second = self.nodes[self.nodes[node].index].bounds.importance(p);
+        if first + second <= 0.0 {
+            return None;
+        }
+        Some(first / (first + second))
+    }
+
+    fn sample(&self, p: &Point3) -> Option<(usize, f64)> {
+        let mut node = 0;
+        let mut pmf = 1.0;
+        loop {
+            let n = self.nodes.get(node)?;
+            if n.leaf {
+                return (n.bounds.importance(p) > 0.0).then_some((n.index, pmf));
+            }
+            let p_first = self.first_child_probability(node, p)?;
+            if random_f64() < p_first {
+                node += 1;
+                pmf *= p_first;
+            } else {
+                node = n.index;
+                pmf *= 1.0 - p_first;
+            }
+        }
+    }
+
+    fn pmf(&self, p: &Point3, light: usize) -> f64 {
+        let Some(&trail) = self.trails.get(&light) else {
+            return 0.0;
+        };
+
+        let mut node = 0;
+        let mut pmf = 1.0;
+        for depth in 0.. {
+            let n = &self.nodes[node];
+            if n.leaf {
+                return if n.bounds.importance(p) > 0.0 {
+                    pmf
+                } else {
+                    0.0
+                };
+            }
+            let Some(p_first) = self.first_child_probability(node, p) else {
+                return 0.0;
+            };
+            if trail & (1 << depth) == 0 {
+                node += 1;
+                pmf *= p_first;
+            } else {
+                node = n.index;
+                pmf *= 1.0 - p_first;
+            }
+        }
+        unreachable!()
+    }
+}
@@ -0,0 +1,327 @@
+use std::f64::consts::PI;
+
+use crate::{
+    image::Image,
+    lights::{EnvironmentMap, LightSample},
+    physics::{xyz_to_rgb, Color, Onb, Spectrum, Vec3},
+    utils::random_f64,
+};
+
+// Luminance in cd/m² that maps to unit radiance, so that ground lit by a high sun comes out
+// close to white without any exposure control. Physically calibrated radiance anywhere in a
+// scene is in these units.
+pub(crate) const LUMINANCE_SCALE: f64 = 1.0 / 20000.0;
+
+// Luminance of the sun's disk outside the atmosphere, from a solar illuminance of about 128 klx
+// spread over the disk.
+const SUN_LUMINANCE: f64 = 1.88e9;
+
+const SUN_ANGULAR_RADIUS: f64 = 0.2667 * PI / 180.0;
+
+// Resolution of the image the sky is baked into for importance sampling.
+const MAP_WIDTH: usize = 256;
+const MAP_HEIGHT: usize = 128;
+
+// Coefficients A to E of the Perez sky luminance distribution for one of Y, x and y.
+#[derive(Clone, Debug)]
+struct Perez([f64; 5]);
+
+impl Perez {
+    // Value of the distribution for a view direction at zenith angle theta and angle gamma
+    // from the sun.
+    fn eval(&self, cos_theta: f64, gamma: f64) -> f64 {
+        let [a, b, c, d, e] = self.0;
+        let cos_gamma = gamma.cos();
+        (1.0 + a * (b / cos_theta.max(1e-4)).exp())
+            * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+    }
+}
+
+// Preetham's fit of the clear sky's luminance and chromaticity over the upper hemisphere.
+#[derive(Debug)]
+struct SkyModel {
+    sun_direction: Vec3,
+    // Zenith angle of the sun, limited to the range the fit covers.
+    theta_s: f64,
+    perez: [Perez; 3],
+    // Y in cd/m², then the x and y chromaticities.
+    zenith: [f64; 3],
+}
+
+impl SkyModel {
+    fn new(sun_direction: &Vec3, t: f64) -> Self {
+        // A sun below the horizon lights the sky as if it were just rising.
+        let theta_s = sun_direction.y().clamp(0.0, 1.0).acos().min(0.499 * PI);
+
+        let perez = [
+            Perez([
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ]),
+            Perez([
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ]),
+            Perez([
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ]),
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |m: [[f64; 4]; 3]| {
+            let row = |c: [f64; 4]| {
+                c[0] * theta_s.powi(3) + c[1] * theta_s.powi(2) + c[2] * theta_s + c[3]
+            };
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let zenith = [
+            1000.0 * zenith_luminance.max(0.0),
+            chromaticity([
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ]),
+            chromaticity([
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ]),
+        ];
+
+        SkyModel {
+            sun_direction: sun_direction.clone(),
+            theta_s,
+            perez,
+            zenith,
+        }
+    }
+
+    // Radiance of the sky in a unit direction above the horizon.
+    fn radiance(&self, d: &Vec3) -> Color {
+        let gamma = d.dot(&self.sun_direction).clamp(-1.0, 1.0).acos();
+        let [big_y, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * self.perez[i].eval(d.y(), gamma)
+                / self.perez[i].eval(self.theta_s.cos(), self.theta_s)
+        });
+        if y <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let scale = LUMINANCE_SCALE * big_y;
+        xyz_to_rgb(&[scale * x / y, scale, scale * (1.0 - x - y) / y])
+    }
+}
+
+// Daylight after Preetham, Shirley and Smits (1999): an analytic clear sky over a diffuse
+// ground, with +y up and -z north. The sun's disk is attenuated by the same turbidity that
+// shapes the sky, so the two stay consistent as the sun moves.
+#[derive(Debug)]
+pub(crate) struct PhysicalSky {
+    sky: SkyModel,
+    turbidity: f64,
+    sun_radiance: Color,
+    ground: Color,
+    // Sky and ground without the sun, used to sample the diffuse part of the light.
+    map: EnvironmentMap,
+    sun_probability: f64,
+}
+
+impl PhysicalSky {
+    pub(crate) fn new(sun_direction: Vec3, turbidity: f64) -> Self {
+        Self::build(
+            sun_direction.unit(),
+            turbidity.clamp(1.7, 10.0),
+            Color::new(0.3, 0.3, 0.3),
+        )
+    }
+
+    // Sky for the sun's position seen from the given latitude and longitude in degrees, east
+    // positive, on a day of the year (1 to 365) at a local clock time in hours, in a time zone
+    // `utc_offset` hours ahead of UTC.
+    pub(crate) fn at_location(
+        latitude: f64,
+        longitude: f64,
+        utc_offset: f64,
+        day_of_year: u32,
+        time: f64,
+        turbidity: f64,
+    ) -> Self {
+        let j = day_of_year as f64;
+        let (latitude, longitude) = (latitude.to_radians(), longitude.to_radians());
+        let standard_meridian = (15.0 * utc_offset).to_radians();
+
+        // Solar time, corrected by the equation of time and the offset from the time zone's
+        // meridian.
+        let solar_time = time + 0.170 * (4.0 * PI * (j - 80.0) / 373.0).sin()
+            - 0.129 * (2.0 * PI * (j - 8.0) / 355.0).sin()
+            + 12.0 * (longitude - standard_meridian) / PI;
+        let declination = 0.4093 * (2.0 * PI * (j - 81.0) / 368.0).sin();
+        let hour_angle = PI * (solar_time - 12.0) / 12.0;
+
+        let (sin_l, cos_l) = latitude.sin_cos();
+        let (sin_d, cos_d) = declination.sin_cos();
+        let east = -cos_d * hour_angle.sin();
+        let north = cos_l * sin_d - sin_l * cos_d * hour_angle.cos();
+        let up = sin_l * sin_d + cos_l * cos_d * hour_angle.cos();
+
+        Self::new(Vec3::new(east, up, -north), turbidity)
+    }
+
+    pub(crate) fn with_ground_albedo(self, albedo: Color) -> Self {
+        Self::build(self.sky.sun_direction, self.turbidity, albedo)
+    }
+
+    // Bakes the sky into an equirectangular image, lights the ground with it and the sun, and
+    // decides how often to sample the sun rather than the image.
+    fn build(sun_direction: Vec3, turbidity: f64, ground_albedo: Color) -> Self {
+        let sky = SkyModel::new(&sun_direction, turbidity);
+        let sun_radiance = if sun_direction.y() > 0.0 {
+            sun_spectrum(sun_direction.y().acos(), turbidity).to_rgb()
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+
+        let cell = (2.0 * PI / MAP_WIDTH as f64) * (PI / MAP_HEIGHT as f64);
+        let mut pixels = Vec::with_capacity(MAP_WIDTH * MAP_HEIGHT);
+        let mut sky_irradiance = Color::new(0.0, 0.0, 0.0);
+        for y in 0..MAP_HEIGHT / 2 {
+            let theta = PI * (y as f64 + 0.5) / MAP_HEIGHT as f64;
+            for x in 0..MAP_WIDTH {
+                let phi = 2.0 * PI * ((x as f64 + 0.5) / MAP_WIDTH as f64 - 0.5);
+                let d = Vec3::new(
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                    -theta.sin() * phi.cos(),
+                );
+                let radiance = sky.radiance(&d);
+                sky_irradiance += &((theta.cos() * theta.sin() * cell) * radiance.clone());
+                pixels.push(radiance);
+            }
+        }
+
+        let sun_solid_angle = 2.0 * PI * (1.0 - SUN_ANGULAR_RADIUS.cos());
+        let sun_irradiance = (sun_solid_angle * sun_direction.y().max(0.0)) * sun_radiance.clone();
+        let ground = (1.0 / PI) * (ground_albedo.clone() * (sky_irradiance + sun_irradiance));
+        pixels.resize(MAP_WIDTH * MAP_HEIGHT, ground.clone());
+
+        let sky_power = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let theta = PI * ((i / MAP_WIDTH) as f64 + 0.5) / MAP_HEIGHT as f64;
+                p.luminance() * theta.sin() * cell
+            })
+            .sum::<f64>();
+        let sun_power = sun_radiance.luminance() * sun_solid_angle;
+        let sun_probability = if sun_power > 0.0 {
+            (sun_power / (sun_power + sky_power)).clamp(0.1, 0.9)
+        } else {
+            0.0
+        };
+
+        PhysicalSky {
+            sky,
+            turbidity,
+            sun_radiance,
+            ground,
+            map: EnvironmentMap::new(Image::new(MAP_WIDTH, MAP_HEIGHT, pixels)),
+            sun_probability,
+        }
+    }
+
+    // Radiance of the sky or the ground in a direction, leaving out the sun's disk.
+    fn sky_radiance(&self, direction: &Vec3) -> Color {
+        let d = direction.unit();
+        if d.y() < 0.0 {
+            self.ground.clone()
+        } else {
+            self.sky.radiance(&d)
+        }
+    }
+
+    fn in_sun(&self, direction: &Vec3) -> bool {
+        direction.y() >= 0.0
+            && direction.unit().dot(&self.sky.sun_direction) >= SUN_ANGULAR_RADIUS.cos()
+    }
+
+    pub(crate) fn radiance(&self, direction: &Vec3) -> Color {
+        let sky = self.sky_radiance(direction);
+        if self.in_sun(direction) {
+            sky + self.sun_radiance.clone()
+        } else {
+            sky
+        }
+    }
+
+    // Picks the sun's disk or the baked sky in proportion to their power.
+    pub(crate) fn sample(&self) -> Option<LightSample> {
+        let wi = if random_f64() < self.sun_probability {
+            let cos_theta = 1.0 - random_f64() * (1.0 - SUN_ANGULAR_RADIUS.cos());
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * PI * random_f64();
+            let frame = Onb::new(&self.sky.sun_direction);
+            frame.local(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+        } else {
+            self.map.sample()?.wi
+        };
+
+        let pdf = self.pdf(&wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            radiance: self.radiance(&wi),
+            wi,
+            pdf,
+            distance: f64::INFINITY,
+            surface: None,
+        })
+    }
+
+    pub(crate) fn pdf(&self, direction: &Vec3) -> f64 {
+        let sun_pdf = if direction.unit().dot(&self.sky.sun_direction) >= SUN_ANGULAR_RADIUS.cos() {
+            1.0 / (2.0 * PI * (1.0 - SUN_ANGULAR_RADIUS.cos()))
+        } else {
+            0.0
+        };
+        self.sun_probability * sun_pdf + (1.0 - self.sun_probability) * self.map.pdf(direction)
+    }
+}
+
+// Spectral radiance of the sun's disk seen through the atmosphere at the given zenith angle,
+// attenuated by Rayleigh scattering and by aerosols in proportion to the turbidity. Absorption
+// by ozone, water vapour and mixed gases is left out.
+fn sun_spectrum(theta_s: f64, turbidity: f64) -> Spectrum {
+    let extraterrestrial = Spectrum::Blackbody(5778.0);
+    let scale = SUN_LUMINANCE * LUMINANCE_SCALE / extraterrestrial.luminance();
+
+    // Relative optical mass of the air along the path to the sun.
+    let m = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_s.to_degrees()).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+
+    Spectrum::PiecewiseLinear(
+        (360..=830)
+            .step_by(5)
+            .map(|l| {
+                let micrometres = l as f64 / 1000.0;
+                let rayleigh = (-0.008735 * micrometres.powf(-4.08) * m).exp();
+                let aerosol = (-beta * micrometres.powf(-1.3) * m).exp();
+                (
+                    l as f64,
+                    scale * extraterrestrial.eval(l as f64) * rayleigh * aerosol,
+                )
+            })
+            .collect(),
+    )
+}
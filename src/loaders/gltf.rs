@@ -0,0 +1,1835 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    animation::{Animatable, Interpolation, Track},
+    lights::{DirectionalLight, IesProfile, Light, PointLight, SpotLight},
+    loaders::{json::Json, Model, Names, TextureCache},
+    physics::{
+        AlphaMasked, BumpMapped, Color, Material, Onb, Point3, Principled, Quaternion, Vec3,
+    },
+    shapes::{BvhNode, Hittable, HittableList, Transform, Transformed, Triangle, Vertex},
+    textures::{solid, ChannelTexture, ScaledTexture, SolidColor, Texture},
+};
+
+// Column-major 4x4 matrix, as stored by glTF.
+type Mat4 = [f64; 16];
+
+const IDENTITY: Mat4 = [
+    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+];
+
+fn mat_mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut m = [0.0; 16];
+    for col in 0..4 {
+        for row in 0..4 {
+            m[col * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum();
+        }
+    }
+    m
+}
+
+// Composes a translation, rotation and scale into T * R * S.
+fn mat_from_trs(t: &Vec3, r: &Quaternion, s: &Vec3) -> Mat4 {
+    let mut m = IDENTITY;
+    let axes = [
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+    ];
+    for (col, axis) in axes.iter().enumerate() {
+        let column = r.rotate(axis);
+        m[col * 4] = column.x() * s[col];
+        m[col * 4 + 1] = column.y() * s[col];
+        m[col * 4 + 2] = column.z() * s[col];
+    }
+    m[12] = t.x();
+    m[13] = t.y();
+    m[14] = t.z();
+    m
+}
+
+// Splits a transform into its nearest placement made of a translation, a rotation and a uniform
+// scale, found from the directions of its axes and the cube root of the volume it scales by.
+// Axes squashed flat keep the rotation given.
+fn placement(m: &Mat4, rotation: &Quaternion) -> Transform {
+    let x = transform_vector(m, &Vec3::new(1.0, 0.0, 0.0));
+    let y = transform_vector(m, &Vec3::new(0.0, 1.0, 0.0));
+    let z = transform_vector(m, &Vec3::new(0.0, 0.0, 1.0));
+    let volume = x.dot(&y.cross(&z)).abs();
+
+    let rotation = if volume > 1e-12 {
+        let x = x.unit();
+        let y = (&y - y.dot(&x) * &x).unit();
+        let z = x.cross(&y);
+        Quaternion::from_axes(&x, &y, &z)
+    } else {
+        *rotation
+    };
+    Transform::new(transform_point(m, &Point3::new(0.0, 0.0, 0.0)))
+        .with_quaternion(&rotation)
+        .with_scale(volume.cbrt().max(1e-9))
+}
+
+// What is left of `m` once `placement` is undone.
+fn residual(m: &Mat4, placement: &Transform) -> Mat4 {
+    let mut r = IDENTITY;
+    for col in 0..4 {
+        let v = Vec3::new(m[col * 4], m[col * 4 + 1], m[col * 4 + 2]);
+        let v = if col < 3 {
+            placement.inverse_vector(&v)
+        } else {
+            placement.inverse_point(&v)
+        };
+        r[col * 4] = v.x();
+        r[col * 4 + 1] = v.y();
+        r[col * 4 + 2] = v.z();
+    }
+    r
+}
+
+// A track through a value at each of `times`.
+fn track<T: Animatable>(
+    times: &[f64],
+    interpolation: Interpolation,
+    value: impl Fn(usize) -> T,
+) -> Track<T> {
+    (1..times.len()).fold(Track::new(times[0], value(0), interpolation), |track, k| {
+        track.key(times[k], value(k), interpolation)
+    })
+}
+
+fn transform_point(m: &Mat4, p: &Point3) -> Point3 {
+    Point3::new(
+        m[0] * p.x() + m[4] * p.y() + m[8] * p.z() + m[12],
+        m[1] * p.x() + m[5] * p.y() + m[9] * p.z() + m[13],
+        m[2] * p.x() + m[6] * p.y() + m[10] * p.z() + m[14],
+    )
+}
+
+fn transform_vector(m: &Mat4, v: &Vec3) -> Vec3 {
+    Vec3::new(
+        m[0] * v.x() + m[4] * v.y() + m[8] * v.z(),
+        m[1] * v.x() + m[5] * v.y() + m[9] * v.z(),
+        m[2] * v.x() + m[6] * v.y() + m[10] * v.z(),
+    )
+}
+
+// Normals transform by the inverse transpose of the upper 3x3 block, which is its cofactor
+// matrix up to a scale that the final normalization removes.
+fn transform_normal(m: &Mat4, n: &Vec3) -> Vec3 {
+    let a = |row: usize, col: usize| m[col * 4 + row];
+    let cofactor = |row: usize, col: usize| {
+        let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+        let (c0, c1) = ((col + 1) % 3, (col + 2) % 3);
+        a(r0, c0) * a(r1, c1) - a(r0, c1) * a(r1, c0)
+    };
+    let det = (0..3).map(|c| a(0, c) * cofactor(0, c)).sum::<f64>();
+    let sign = if det < 0.0 { -1.0 } else { 1.0 };
+
+    let row = |r: usize| cofactor(r, 0) * n.x() + cofactor(r, 1) * n.y() + cofactor(r, 2) * n.z();
+    (sign * Vec3::new(row(0), row(1), row(2))).unit()
+}
+
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    };
+
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+    for c in data
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        accumulator = (accumulator << 6) | value(c)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((accumulator >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+// Called with the path down to a node, the node and its transform into world space.
+type Visitor<'a> = dyn FnMut(&[usize], &Json, &Mat4) -> Result<(), Error> + 'a;
+
+struct Document {
+    json: Json,
+    buffers: Vec<Vec<u8>>,
+    directory: PathBuf,
+    path: String,
+    // Animated parts of the nodes, by node index.
+    animations: HashMap<usize, NodeAnimation>,
+}
+
+// Tracks for the parts of a node's placement that are animated. The rest keep the node's own
+// values.
+#[derive(Default)]
+struct NodeAnimation {
+    translation: Option<Track<Vec3>>,
+    rotation: Option<Track<Quaternion>>,
+    scale: Option<Track<Vec3>>,
+    // How the channels of the node move between their keyframes.
+    interpolations: Vec<Interpolation>,
+}
+
+impl NodeAnimation {
+    fn times(&self) -> Vec<f64> {
+        let mut times: Vec<f64> = (self.translation.iter().flat_map(|t| t.times()))
+            .chain(self.rotation.iter().flat_map(|t| t.times()))
+            .chain(self.scale.iter().flat_map(|t| t.times()))
+            .collect();
+        times.sort_by(f64::total_cmp);
+        times.dedup();
+        times
+    }
+}
+
+// A camera of a glTF file, aimed at the point in front of it that is as far away as the centre
+// of the model. An animated camera follows tracks of both points, keyed when the camera or any
+// node above it is; it keeps its `vup` from time zero.
+pub(crate) struct ModelCamera {
+    pub(crate) look_from: Point3,
+    pub(crate) look_at: Point3,
+    pub(crate) vup: Vec3,
+    pub(crate) vfov: f64,
+    pub(crate) aspect_ratio: Option<f64>,
+    // Width of the view of an orthographic camera.
+    pub(crate) view_width: Option<f64>,
+    pub(crate) animation: Option<(Track<Point3>, Track<Point3>)>,
+}
+
+// Loads the triangle meshes of a glTF 2.0 file (`.gltf` with external or embedded buffers, or
+// binary `.glb`), flattening the node hierarchy into world space. Metallic-roughness materials
+// and the transmission, IOR, specular, clearcoat, sheen and emissive strength extensions are
+// converted to principled materials. Point, spot and directional lights declared with the
+// KHR_lights_punctual extension and the first camera are returned alongside the triangles, as
+// are the names of the materials and of the nodes holding the lights.
+//
+// The translation, rotation and scale channels of every animation play together, in seconds.
+// A mesh under an animated node moves as a whole, placed at each of their keyframes by a
+// translation, rotation and uniform scale blended like the channels between them; what that
+// can't express, such as a non-uniform scale, is taken from the keyframe where the mesh is
+// largest. Lights stay where they are at time zero, and morph target weights aren't animated.
+pub(crate) fn load_gltf(path: &str) -> Result<Model, Error> {
+    let document = Document::load(path)?;
+
+    let mut textures = TextureCache::default();
+    let materials: Vec<Arc<dyn Material>> = document
+        .array("materials")
+        .iter()
+        .map(|m| document.material(m, &mut textures))
+        .collect::<Result<_, _>>()?;
+    let default_material: Arc<dyn Material> = Arc::new(Principled::new(Arc::new(SolidColor::new(
+        Color::new(0.8, 0.8, 0.8),
+    ))));
+    let mut names = Names::default();
+    for (m, material) in document.array("materials").iter().zip(&materials) {
+        if let Some(name) = m.get("name").and_then(|n| n.as_str()) {
+            names.materials.push((name.to_string(), material.clone()));
+        }
+    }
+
+    let mut triangles = HittableList::default();
+    let mut lights = Vec::new();
+    let mut camera = None;
+    for root in document.root_nodes() {
+        document.visit_node(
+            root,
+            &IDENTITY,
+            &mut Vec::new(),
+            &mut |path, node, transform| {
+                if let Some(mesh) = node.get("mesh").and_then(|m| m.as_usize()) {
+                    match document.keyframes(path) {
+                        None => document.add_mesh(
+                            mesh,
+                            transform,
+                            &materials,
+                            &default_material,
+                            &mut triangles,
+                        )?,
+                        Some((times, interpolation)) => {
+                            if let Some(object) = document.animated_mesh(
+                                mesh,
+                                path,
+                                &times,
+                                interpolation,
+                                &materials,
+                                &default_material,
+                            )? {
+                                triangles.add(object);
+                            }
+                        }
+                    }
+                }
+                if let Some(index) = node.get("camera").and_then(|c| c.as_usize()) {
+                    if camera.is_none() {
+                        camera = Some((index, path.to_vec()));
+                    }
+                }
+                if let Some(light) = node
+                    .get("extensions")
+                    .and_then(|e| e.get("KHR_lights_punctual"))
+                    .and_then(|l| l.get("light"))
+                    .and_then(|l| l.as_usize())
+                {
+                    if let Some(name) = node.get("name").and_then(|n| n.as_str()) {
+                        names.lights.push((name.to_string(), lights.len()));
+                    }
+                    lights.push(document.light(light, transform)?);
+                }
+                Ok(())
+            },
+        )?;
+    }
+
+    let camera = match camera {
+        Some((index, path)) => Some(document.camera(index, &path, &triangles)?),
+        None => None,
+    };
+    Ok(Model {
+        triangles,
+        lights,
+        camera,
+        names,
+    })
+}
+
+impl Document {
+    fn load(path: &str) -> Result<Self, Error> {
+        let bytes = fs::read(path)?;
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, msg));
+        let directory = Path::new(path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+
+        let read_u32 = |b: &[u8], offset: usize| {
+            b.get(offset..offset + 4)
+                .map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]) as usize)
+        };
+
+        // Binary glTF: a header followed by a JSON chunk and an optional binary chunk.
+        let (text, binary_chunk) = if bytes.starts_with(b"glTF") {
+            let mut chunks = Vec::new();
+            let mut offset = 12;
+            while let (Some(length), Some(kind)) =
+                (read_u32(&bytes, offset), read_u32(&bytes, offset + 4))
+            {
+                let data = bytes
+                    .get(offset + 8..offset + 8 + length)
+                    .ok_or_else(|| invalid("truncated GLB chunk"))?;
+                chunks.push((kind, data.to_vec()));
+                offset += 8 + length;
+            }
+            let json = chunks
+                .iter()
+                .find(|(kind, _)| *kind == 0x4e4f534a)
+                .ok_or_else(|| invalid("GLB without JSON chunk"))?;
+            let binary = chunks.iter().find(|(kind, _)| *kind == 0x004e4942);
+            (
+                String::from_utf8_lossy(&json.1).into_owned(),
+                binary.map(|(_, data)| data.clone()),
+            )
+        } else {
+            (String::from_utf8_lossy(&bytes).into_owned(), None)
+        };
+
+        let json = Json::parse(&text).map_err(|e| invalid(&e))?;
+
+        let mut buffers = Vec::new();
+        let mut binary_chunk = binary_chunk;
+        for buffer in json
+            .get("buffers")
+            .and_then(|b| b.as_array())
+            .unwrap_or(&[])
+        {
+            let data = match buffer.get("uri").and_then(|u| u.as_str()) {
+                Some(uri) if uri.starts_with("data:") => {
+                    let payload = uri.split_once(',').map_or("", |(_, p)| p);
+                    decode_base64(payload).ok_or_else(|| invalid("bad base64 buffer"))?
+                }
+                Some(uri) => fs::read(directory.join(uri))?,
+                None => binary_chunk
+                    .take()
+                    .ok_or_else(|| invalid("buffer without data"))?,
+            };
+            buffers.push(data);
+        }
+
+        let mut document = Document {
+            json,
+            buffers,
+            directory,
+            path: path.to_string(),
+            animations: HashMap::new(),
+        };
+        document.animations = document.animations()?;
+        Ok(document)
+    }
+
+    // Reads the channels of every animation that move nodes into tracks. Linear channels are
+    // blended linearly, rotations along the shortest arc, and cubic splines become Bezier
+    // curves through their keyframes, dropping the tangents stored with them.
+    fn animations(&self) -> Result<HashMap<usize, NodeAnimation>, Error> {
+        let mut animations: HashMap<usize, NodeAnimation> = HashMap::new();
+        for animation in self.array("animations") {
+            let samplers = animation
+                .get("samplers")
+                .and_then(|s| s.as_array())
+                .unwrap_or(&[]);
+            for channel in animation
+                .get("channels")
+                .and_then(|c| c.as_array())
+                .unwrap_or(&[])
+            {
+                let target = channel.get("target");
+                let property = target.and_then(|t| t.get("path")).and_then(|p| p.as_str());
+                let components = match property {
+                    Some("translation") | Some("scale") => 3,
+                    Some("rotation") => 4,
+                    Some("weights") => continue,
+                    _ => return Err(self.invalid("bad animation target path")),
+                };
+                let node = target
+                    .and_then(|t| t.get("node"))
+                    .and_then(|n| n.as_usize())
+                    .filter(|&n| n < self.array("nodes").len())
+                    .ok_or_else(|| self.invalid("bad animation target node"))?;
+                if self.array("nodes")[node].get("matrix").is_some() {
+                    return Err(self.invalid(&format!(
+                        "node {}: a node placed by a matrix can't be animated",
+                        node
+                    )));
+                }
+
+                let sampler = channel
+                    .get("sampler")
+                    .and_then(|s| s.as_usize())
+                    .and_then(|s| samplers.get(s))
+                    .ok_or_else(|| self.invalid("bad animation sampler index"))?;
+                let interpolation = match sampler.get("interpolation").and_then(|i| i.as_str()) {
+                    None | Some("LINEAR") => Interpolation::Linear,
+                    Some("STEP") => Interpolation::Step,
+                    Some("CUBICSPLINE") => Interpolation::Bezier,
+                    Some(other) => {
+                        return Err(self.invalid(&format!("unknown interpolation {}", other)))
+                    }
+                };
+                let accessor = |key: &str| {
+                    sampler
+                        .get(key)
+                        .and_then(|a| a.as_usize())
+                        .ok_or_else(|| self.invalid(&format!("animation sampler without {}", key)))
+                        .and_then(|a| self.accessor(a))
+                };
+                let (times, _) = accessor("input")?;
+                if times.is_empty()
+                    || times.iter().any(|t| !t.is_finite())
+                    || times.windows(2).any(|w| w[0] >= w[1])
+                {
+                    return Err(self.invalid("animation times must increase"));
+                }
+                // Cubic splines store an in-tangent, the value and an out-tangent per keyframe.
+                let (values, size) = accessor("output")?;
+                let stride = match interpolation {
+                    Interpolation::Bezier => 3,
+                    _ => 1,
+                };
+                if size != components || values.len() != times.len() * stride * components {
+                    return Err(self.invalid("animation output doesn't match its input"));
+                }
+                let value = |k: usize| &values[(k * stride + stride / 2) * components..];
+
+                let node = animations.entry(node).or_default();
+                let vector = |k: usize| {
+                    let v = value(k);
+                    Vec3::new(v[0], v[1], v[2])
+                };
+                match property {
+                    Some("rotation") => {
+                        node.rotation = Some(track(&times, interpolation, |k| {
+                            let v = value(k);
+                            Quaternion::new(v[0], v[1], v[2], v[3])
+                        }))
+                    }
+                    Some("scale") => node.scale = Some(track(&times, interpolation, vector)),
+                    _ => node.translation = Some(track(&times, interpolation, vector)),
+                }
+                node.interpolations.push(interpolation);
+            }
+        }
+        Ok(animations)
+    }
+
+    fn invalid(&self, msg: &str) -> Error {
+        Error::new(ErrorKind::InvalidData, format!("{}: {}", self.path, msg))
+    }
+
+    fn array(&self, key: &str) -> &[Json] {
+        self.json.get(key).and_then(|a| a.as_array()).unwrap_or(&[])
+    }
+
+    fn root_nodes(&self) -> Vec<usize> {
+        let scene = self
+            .json
+            .get("scene")
+            .and_then(|s| s.as_usize())
+            .unwrap_or(0);
+        if let Some(nodes) = self
+            .array("scenes")
+            .get(scene)
+            .and_then(|s| s.get("nodes"))
+            .and_then(|n| n.as_array())
+        {
+            return nodes.iter().filter_map(|n| n.as_usize()).collect();
+        }
+
+        // Without scenes, every node that is nobody's child is a root.
+        let nodes = self.array("nodes");
+        let children: Vec<usize> = nodes
+            .iter()
+            .filter_map(|n| n.get("children").and_then(|c| c.as_array()))
+            .flatten()
+            .filter_map(|c| c.as_usize())
+            .collect();
+        (0..nodes.len()).filter(|i| !children.contains(i)).collect()
+    }
+
+    // Visits the node and its descendants, passing each the nodes on the way down to it from the
+    // root. A node listed among its own descendants is rejected instead of recursing forever.
+    // Nodes are placed as they are at time zero.
+    fn visit_node(
+        &self,
+        index: usize,
+        parent: &Mat4,
+        ancestors: &mut Vec<usize>,
+        visit: &mut Visitor,
+    ) -> Result<(), Error> {
+        if ancestors.contains(&index) {
+            return Err(self.invalid("cycle in the node hierarchy"));
+        }
+        let node = self
+            .array("nodes")
+            .get(index)
+            .ok_or_else(|| self.invalid("bad node index"))?;
+        let transform = mat_mul(parent, &self.local_transform(index, 0.0)?);
+
+        ancestors.push(index);
+        visit(ancestors, node, &transform)?;
+        for child in node
+            .get("children")
+            .and_then(|c| c.as_array())
+            .unwrap_or(&[])
+        {
+            let child = child
+                .as_usize()
+                .ok_or_else(|| self.invalid("bad child index"))?;
+            self.visit_node(child, &transform, ancestors, visit)?;
+        }
+        ancestors.pop();
+        Ok(())
+    }
+
+    // The transform of a node relative to its parent at `time`.
+    fn local_transform(&self, index: usize, time: f64) -> Result<Mat4, Error> {
+        let node = self
+            .array("nodes")
+            .get(index)
+            .ok_or_else(|| self.invalid("bad node index"))?;
+        let vector = |key: &str, default: &[f64]| -> Result<Vec<f64>, Error> {
+            let v = node
+                .get(key)
+                .and_then(|v| v.as_f64_vec())
+                .unwrap_or_else(|| default.to_vec());
+            if v.len() != default.len() {
+                return Err(self.invalid(&format!(
+                    "node {}: {} needs {} numbers",
+                    index,
+                    key,
+                    default.len()
+                )));
+            }
+            Ok(v)
+        };
+        if node.get("matrix").is_some() {
+            let m = vector("matrix", &IDENTITY)?;
+            return Ok(std::array::from_fn(|i| m[i]));
+        }
+
+        let t = vector("translation", &[0.0, 0.0, 0.0])?;
+        let r = vector("rotation", &[0.0, 0.0, 0.0, 1.0])?;
+        let s = vector("scale", &[1.0, 1.0, 1.0])?;
+        let mut translation = Vec3::new(t[0], t[1], t[2]);
+        let mut rotation = Quaternion::new(r[0], r[1], r[2], r[3]);
+        let mut scale = Vec3::new(s[0], s[1], s[2]);
+        if let Some(animation) = self.animations.get(&index) {
+            if let Some(track) = &animation.translation {
+                translation = track.at(time);
+            }
+            if let Some(track) = &animation.rotation {
+                rotation = track.at(time);
+            }
+            if let Some(track) = &animation.scale {
+                scale = track.at(time);
+            }
+        }
+        Ok(mat_from_trs(&translation, &rotation, &scale))
+    }
+
+    // The transform into world space of the last of `path`, the nodes on the way down to it from
+    // the root, at `time`.
+    fn world_transform(&self, path: &[usize], time: f64) -> Result<Mat4, Error> {
+        path.iter().try_fold(IDENTITY, |m, &index| {
+            Ok(mat_mul(&m, &self.local_transform(index, time)?))
+        })
+    }
+
+    // The times of the keyframes of the nodes on `path`, and how to move between them: as their
+    // channels do when they all agree, and linearly otherwise. None when none is animated.
+    fn keyframes(&self, path: &[usize]) -> Option<(Vec<f64>, Interpolation)> {
+        let animations: Vec<&NodeAnimation> = path
+            .iter()
+            .filter_map(|index| self.animations.get(index))
+            .collect();
+        let mut interpolations = animations.iter().flat_map(|a| a.interpolations.iter());
+        let first = *interpolations.next()?;
+        let interpolation = if interpolations.all(|&i| i == first) {
+            first
+        } else {
+            Interpolation::Linear
+        };
+
+        let mut times: Vec<f64> = animations.iter().flat_map(|a| a.times()).collect();
+        times.sort_by(f64::total_cmp);
+        times.dedup();
+        Some((times, interpolation))
+    }
+
+    // A mesh under animated nodes, built in its own space and placed at `times` by a track.
+    fn animated_mesh(
+        &self,
+        mesh: usize,
+        path: &[usize],
+        times: &[f64],
+        interpolation: Interpolation,
+        materials: &[Arc<dyn Material>],
+        default_material: &Arc<dyn Material>,
+    ) -> Result<Option<Arc<dyn Hittable>>, Error> {
+        let transforms = times
+            .iter()
+            .map(|&time| self.world_transform(path, time))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut placements: Vec<Transform> = Vec::with_capacity(times.len());
+        for m in &transforms {
+            let previous = placements
+                .last()
+                .map_or(Quaternion::default(), |p| p.rotation());
+            placements.push(placement(m, &previous));
+        }
+
+        let largest = (0..times.len())
+            .max_by(|&a, &b| placements[a].scale().total_cmp(&placements[b].scale()))
+            .unwrap_or(0);
+        let mut local = HittableList::default();
+        self.add_mesh(
+            mesh,
+            &residual(&transforms[largest], &placements[largest]),
+            materials,
+            default_material,
+            &mut local,
+        )?;
+        if local.is_empty() {
+            return Ok(None);
+        }
+
+        let track = track(times, interpolation, |k| placements[k].clone());
+        Ok(Some(Arc::new(Transformed::animated(
+            Arc::new(BvhNode::new(local)),
+            track,
+        ))))
+    }
+
+    // The camera on the node at the end of `path`. Its aim point is as far in front of it as
+    // the centre of `model`.
+    fn camera(
+        &self,
+        index: usize,
+        path: &[usize],
+        model: &HittableList,
+    ) -> Result<ModelCamera, Error> {
+        let camera = self
+            .array("cameras")
+            .get(index)
+            .ok_or_else(|| self.invalid("bad camera index"))?;
+        let number = |kind: &str, key: &str| {
+            camera
+                .get(kind)
+                .and_then(|c| c.get(key))
+                .and_then(|v| v.as_f64())
+                .filter(|v| v.is_finite() && *v > 0.0)
+        };
+        let (vfov, aspect_ratio, view_width) = match camera.get("type").and_then(|t| t.as_str()) {
+            Some("perspective") => {
+                let yfov = number("perspective", "yfov")
+                    .filter(|&yfov| yfov < std::f64::consts::PI)
+                    .ok_or_else(|| self.invalid("bad perspective camera field of view"))?;
+                (
+                    yfov.to_degrees(),
+                    number("perspective", "aspectRatio"),
+                    None,
+                )
+            }
+            Some("orthographic") => {
+                let (xmag, ymag) = number("orthographic", "xmag")
+                    .zip(number("orthographic", "ymag"))
+                    .ok_or_else(|| self.invalid("bad orthographic camera magnification"))?;
+                (90.0, Some(xmag / ymag), Some(2.0 * xmag))
+            }
+            _ => return Err(self.invalid("unknown camera type")),
+        };
+
+        // Cameras look down their -z axis with +y up.
+        let bbox = model.bounding_box();
+        let center = 0.5 * (&bbox.min() + &bbox.max());
+        let view = |m: &Mat4| {
+            let look_from = transform_point(m, &Point3::new(0.0, 0.0, 0.0));
+            let direction = transform_vector(m, &Vec3::new(0.0, 0.0, -1.0)).unit();
+            (look_from, direction)
+        };
+        let start = self.world_transform(path, 0.0)?;
+        let (look_from, direction) = view(&start);
+        let distance = if model.is_empty() {
+            1.0
+        } else {
+            (&center - &look_from).length().max(1e-3)
+        };
+        let look_at = &look_from + distance * &direction;
+        let vup = transform_vector(&start, &Vec3::new(0.0, 1.0, 0.0));
+
+        let animation = match self.keyframes(path) {
+            Some((times, interpolation)) => {
+                let views = times
+                    .iter()
+                    .map(|&time| self.world_transform(path, time).map(|m| view(&m)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Some((
+                    track(&times, interpolation, |k| views[k].0.clone()),
+                    track(&times, interpolation, |k| {
+                        &views[k].0 + distance * &views[k].1
+                    }),
+                ))
+            }
+            None => None,
+        };
+
+        Ok(ModelCamera {
+            look_from,
+            look_at,
+            vup,
+            vfov,
+            aspect_ratio,
+            view_width,
+            animation,
+        })
+    }
+
+    // Reads an accessor as floating point values, returning them with the number of components
+    // per element.
+    fn accessor(&self, index: usize) -> Result<(Vec<f64>, usize), Error> {
+        let accessor = self
+            .array("accessors")
+            .get(index)
+            .ok_or_else(|| self.invalid("bad accessor index"))?;
+        if accessor.get("sparse").is_some() {
+            return Err(self.invalid("sparse accessors are not supported"));
+        }
+
+        let count = accessor
+            .get("count")
+            .and_then(|c| c.as_usize())
+            .ok_or_else(|| self.invalid("accessor without count"))?;
+        let components = match accessor.get("type").and_then(|t| t.as_str()) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(self.invalid("bad accessor type")),
+        };
+        let component_type = accessor
+            .get("componentType")
+            .and_then(|c| c.as_usize())
+            .unwrap_or(0);
+        let component_size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err(self.invalid("bad accessor component type")),
+        };
+        let normalized = matches!(accessor.get("normalized"), Some(Json::Bool(true)));
+
+        let Some(view_index) = accessor.get("bufferView").and_then(|v| v.as_usize()) else {
+            return Ok((vec![0.0; count * components], components));
+        };
+        let view = self
+            .array("bufferViews")
+            .get(view_index)
+            .ok_or_else(|| self.invalid("bad buffer view index"))?;
+        let buffer = view
+            .get("buffer")
+            .and_then(|b| b.as_usize())
+            .and_then(|b| self.buffers.get(b))
+            .ok_or_else(|| self.invalid("bad buffer index"))?;
+
+        let offset_of = |json: &Json| {
+            json.get("byteOffset")
+                .and_then(|o| o.as_usize())
+                .unwrap_or(0)
+        };
+        let element_size = component_size * components;
+        let stride = view
+            .get("byteStride")
+            .and_then(|s| s.as_usize())
+            .unwrap_or(element_size);
+        let base = offset_of(view) + offset_of(accessor);
+
+        let mut values = Vec::with_capacity(count * components);
+        for element in 0..count {
+            for component in 0..components {
+                let at = base + element * stride + component * component_size;
+                let bytes = buffer
+                    .get(at..at + component_size)
+                    .ok_or_else(|| self.invalid("accessor out of bounds"))?;
+                let value = match component_type {
+                    5120 => {
+                        let v = bytes[0] as i8 as f64;
+                        if normalized {
+                            (v / 127.0).max(-1.0)
+                        } else {
+                            v
+                        }
+                    }
+                    5121 => {
+                        let v = bytes[0] as f64;
+                        if normalized {
+                            v / 255.0
+                        } else {
+                            v
+                        }
+                    }
+                    5122 => {
+                        let v = i16::from_le_bytes([bytes[0], bytes[1]]) as f64;
+                        if normalized {
+                            (v / 32767.0).max(-1.0)
+                        } else {
+                            v
+                        }
+                    }
+                    5123 => {
+                        let v = u16::from_le_bytes([bytes[0], bytes[1]]) as f64;
+                        if normalized {
+                            v / 65535.0
+                        } else {
+                            v
+                        }
+                    }
+                    5125 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                    _ => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                };
+                values.push(value);
+            }
+        }
+
+        Ok((values, components))
+    }
+
+    // Punctual light placed by a node. Lights shine down the node's -z axis, and their intensity
+    // is given in candela, or in lux for directional lights, which is converted to radiometric
+    // units at the luminous efficacy of 683 lm/W that exporters assume. A point or spot light
+    // whose extras name an IES file takes its distribution from the file, pointing the fixture's
+    // nadir down -z and its 0° plane along +x, with the intensity as a multiplier.
+    fn light(&self, index: usize, transform: &Mat4) -> Result<Arc<dyn Light>, Error> {
+        let light = self
+            .json
+            .get("extensions")
+            .and_then(|e| e.get("KHR_lights_punctual"))
+            .and_then(|l| l.get("lights"))
+            .and_then(|l| l.as_array())
+            .and_then(|l| l.get(index))
+            .ok_or_else(|| self.invalid("bad light index"))?;
+
+        let number =
+            |key: &str, default: f64| light.get(key).and_then(|v| v.as_f64()).unwrap_or(default);
+        let color = light
+            .get("color")
+            .and_then(|v| v.as_f64_vec())
+            .filter(|v| v.len() >= 3)
+            .map_or(Color::new(1.0, 1.0, 1.0), |v| Color::new(v[0], v[1], v[2]));
+        let mut intensity = number("intensity", 1.0) / 683.0;
+
+        let position = transform_point(transform, &Point3::new(0.0, 0.0, 0.0));
+        let direction = transform_vector(transform, &Vec3::new(0.0, 0.0, -1.0));
+        let profile = match light
+            .get("extras")
+            .and_then(|e| e.get("ies"))
+            .and_then(|p| p.as_str())
+        {
+            Some(file) => {
+                let profile = IesProfile::load(&self.directory.join(file).to_string_lossy())?;
+                intensity = number("intensity", 1.0) * profile.peak_intensity();
+                let tangent = transform_vector(transform, &Vec3::new(1.0, 0.0, 0.0));
+                Some((Arc::new(profile), Onb::from_tangent(&direction, &tangent)))
+            }
+            None => None,
+        };
+
+        let light: Arc<dyn Light> = match light.get("type").and_then(|t| t.as_str()) {
+            Some("point") => {
+                let light = PointLight::with_intensity(position, intensity * color);
+                match profile {
+                    Some((profile, frame)) => Arc::new(light.with_profile(profile, frame)),
+                    None => Arc::new(light),
+                }
+            }
+            Some("spot") => {
+                let spot = light.get("spot");
+                let angle = |key: &str, default: f64| {
+                    spot.and_then(|s| s.get(key))
+                        .and_then(|v| v.as_f64())
+                        .unwrap_or(default)
+                        .to_degrees()
+                };
+                let light = SpotLight::with_intensity(
+                    position,
+                    direction,
+                    intensity * color,
+                    angle("innerConeAngle", 0.0),
+                    angle("outerConeAngle", std::f64::consts::FRAC_PI_4),
+                );
+                match profile {
+                    Some((profile, frame)) => Arc::new(light.with_profile(profile, frame)),
+                    None => Arc::new(light),
+                }
+            }
+            Some("directional") => Arc::new(DirectionalLight::new(direction, color, intensity)),
+            _ => return Err(self.invalid("unknown light type")),
+        };
+        Ok(light)
+    }
+
+    fn add_mesh(
+        &self,
+        mesh: usize,
+        transform: &Mat4,
+        materials: &[Arc<dyn Material>],
+        default_material: &Arc<dyn Material>,
+        triangles: &mut HittableList,
+    ) -> Result<(), Error> {
+        let mesh = self
+            .array("meshes")
+            .get(mesh)
+            .ok_or_else(|| self.invalid("bad mesh index"))?;
+
+        for primitive in mesh
+            .get("primitives")
+            .and_then(|p| p.as_array())
+            .unwrap_or(&[])
+        {
+            let mode = primitive
+                .get("mode")
+                .and_then(|m| m.as_usize())
+                .unwrap_or(4);
+            if mode != 4 {
+                eprintln!("{}: skipping non-triangle primitive", self.path);
+                continue;
+            }
+
+            let attribute = |name: &str| {
+                primitive
+                    .get("attributes")
+                    .and_then(|a| a.get(name))
+                    .and_then(|a| a.as_usize())
+            };
+            let position_accessor =
+                attribute("POSITION").ok_or_else(|| self.invalid("primitive without positions"))?;
+            let (positions, _) = self.accessor(position_accessor)?;
+            let normals = attribute("NORMAL").map(|a| self.accessor(a)).transpose()?;
+            let uvs = attribute("TEXCOORD_0")
+                .map(|a| self.accessor(a))
+                .transpose()?;
+
+            let vertex_count = positions.len() / 3;
+            // Every attribute needs an element for each vertex.
+            for (name, attribute, components) in [("NORMAL", &normals, 3), ("TEXCOORD_0", &uvs, 2)]
+            {
+                if let Some((values, _)) = attribute {
+                    if values.len() < components * vertex_count {
+                        return Err(self.invalid(&format!("{} shorter than POSITION", name)));
+                    }
+                }
+            }
+            let vertex = |i: usize| {
+                let p = Point3::new(positions[3 * i], positions[3 * i + 1], positions[3 * i + 2]);
+                let mut vertex = Vertex::new(transform_point(transform, &p));
+                if let Some((normals, _)) = &normals {
+                    let n = Vec3::new(normals[3 * i], normals[3 * i + 1], normals[3 * i + 2]);
+                    vertex.normal = Some(transform_normal(transform, &n));
+                }
+                // glTF places the texture origin at the top left, textures here expect the
+                // bottom left.
+                if let Some((uvs, _)) = &uvs {
+                    vertex.uv = Some((uvs[2 * i], 1.0 - uvs[2 * i + 1]));
+                }
+                vertex
+            };
+
+            let indices: Vec<usize> = match primitive.get("indices").and_then(|i| i.as_usize()) {
+                Some(accessor) => self
+                    .accessor(accessor)?
+                    .0
+                    .into_iter()
+                    .map(|i| i as usize)
+                    .collect(),
+                None => (0..vertex_count).collect(),
+            };
+            if indices.iter().any(|&i| i >= vertex_count) {
+                return Err(self.invalid("vertex index out of range"));
+            }
+
+            let material = primitive
+                .get("material")
+                .and_then(|m| m.as_usize())
+                .and_then(|m| materials.get(m))
+                .unwrap_or(default_material);
+
+            for corners in indices.chunks_exact(3) {
+                triangles.add(Arc::new(Triangle::new(
+                    [vertex(corners[0]), vertex(corners[1]), vertex(corners[2])],
+                    material.clone(),
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Loads the image behind a glTF textureInfo object, from a file next to the model, a `data:`
+    // URI or a buffer view. An external file that can't be read is skipped like a missing OBJ
+    // map, while broken references and embedded data fail the whole model.
+    fn texture(
+        &self,
+        info: Option<&Json>,
+        srgb: bool,
+        textures: &mut TextureCache,
+    ) -> Result<Option<Arc<dyn Texture>>, Error> {
+        let Some(info) = info else {
+            return Ok(None);
+        };
+        let source = info
+            .get("index")
+            .and_then(|t| t.as_usize())
+            .and_then(|t| self.array("textures").get(t))
+            .ok_or_else(|| self.invalid("bad texture index"))?
+            .get("source")
+            .and_then(|s| s.as_usize())
+            .ok_or_else(|| self.invalid("texture without source"))?;
+        let image = self
+            .array("images")
+            .get(source)
+            .ok_or_else(|| self.invalid("bad image index"))?;
+        let mime_type = image.get("mimeType").and_then(|m| m.as_str());
+        let name = format!("{}: image {}", self.path, source);
+
+        match image.get("uri").and_then(|u| u.as_str()) {
+            Some(uri) if uri.starts_with("data:") => {
+                let (header, payload) = uri
+                    .split_once(',')
+                    .ok_or_else(|| self.invalid("bad data URI"))?;
+                let mime_type = header["data:".len()..]
+                    .split(';')
+                    .next()
+                    .filter(|m| !m.is_empty())
+                    .or(mime_type)
+                    .unwrap_or_default();
+                let bytes =
+                    decode_base64(payload).ok_or_else(|| self.invalid("bad base64 image"))?;
+                textures.decode(&name, &bytes, mime_type, srgb).map(Some)
+            }
+            Some(uri) => Ok(textures.load(&self.directory.join(uri).to_string_lossy(), srgb)),
+            None => {
+                let view = image
+                    .get("bufferView")
+                    .and_then(|v| v.as_usize())
+                    .ok_or_else(|| self.invalid("image without data"))?;
+                let mime_type =
+                    mime_type.ok_or_else(|| self.invalid("buffer view image without MIME type"))?;
+                let bytes = self.buffer_view(view)?;
+                textures.decode(&name, bytes, mime_type, srgb).map(Some)
+            }
+        }
+    }
+
+    // The bytes of a buffer view.
+    fn buffer_view(&self, index: usize) -> Result<&[u8], Error> {
+        let view = self
+            .array("bufferViews")
+            .get(index)
+            .ok_or_else(|| self.invalid("bad buffer view index"))?;
+        let buffer = view
+            .get("buffer")
+            .and_then(|b| b.as_usize())
+            .and_then(|b| self.buffers.get(b))
+            .ok_or_else(|| self.invalid("bad buffer index"))?;
+        let offset = view
+            .get("byteOffset")
+            .and_then(|o| o.as_usize())
+            .unwrap_or(0);
+        let length = view
+            .get("byteLength")
+            .and_then(|l| l.as_usize())
+            .ok_or_else(|| self.invalid("buffer view without length"))?;
+        offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| self.invalid("buffer view out of bounds"))
+    }
+
+    // Combines a constant factor with an optional texture, optionally reading a single channel.
+    fn parameter(
+        &self,
+        factor: Color,
+        info: Option<&Json>,
+        channel: Option<usize>,
+        srgb: bool,
+        textures: &mut TextureCache,
+    ) -> Result<Arc<dyn Texture>, Error> {
+        Ok(match self.texture(info, srgb, textures)? {
+            Some(texture) => {
+                let texture = match channel {
+                    Some(channel) => Arc::new(ChannelTexture::new(texture, channel)),
+                    None => texture,
+                };
+                Arc::new(ScaledTexture::new(texture, factor))
+            }
+            None => Arc::new(SolidColor::new(factor)),
+        })
+    }
+
+    fn material(
+        &self,
+        material: &Json,
+        textures: &mut TextureCache,
+    ) -> Result<Arc<dyn Material>, Error> {
+        let number = |json: Option<&Json>, key: &str, default: f64| {
+            json.and_then(|j| j.get(key))
+                .and_then(|v| v.as_f64())
+                .unwrap_or(default)
+        };
+        let color = |json: Option<&Json>, key: &str, default: [f64; 3]| {
+            let v = json
+                .and_then(|j| j.get(key))
+                .and_then(|v| v.as_f64_vec())
+                .filter(|v| v.len() >= 3)
+                .unwrap_or_else(|| default.to_vec());
+            Color::new(v[0], v[1], v[2])
+        };
+        let scalar = |v: f64| Color::new(v, v, v);
+        let extension = |name: &str| material.get("extensions").and_then(|e| e.get(name));
+
+        let pbr = material.get("pbrMetallicRoughness");
+        let base_color = self.parameter(
+            color(pbr, "baseColorFactor", [1.0, 1.0, 1.0]),
+            pbr.and_then(|p| p.get("baseColorTexture")),
+            None,
+            true,
+            textures,
+        )?;
+
+        // Metalness is stored in the blue channel and roughness in the green one.
+        let metallic_roughness = pbr.and_then(|p| p.get("metallicRoughnessTexture"));
+        let metallic = self.parameter(
+            scalar(number(pbr, "metallicFactor", 1.0)),
+            metallic_roughness,
+            Some(2),
+            false,
+            textures,
+        )?;
+        let roughness = self.parameter(
+            scalar(number(pbr, "roughnessFactor", 1.0)),
+            metallic_roughness,
+            Some(1),
+            false,
+            textures,
+        )?;
+
+        let emissive_strength = number(
+            extension("KHR_materials_emissive_strength"),
+            "emissiveStrength",
+            1.0,
+        );
+        let emission = self.parameter(
+            emissive_strength * color(Some(material), "emissiveFactor", [0.0, 0.0, 0.0]),
+            material.get("emissiveTexture"),
+            None,
+            true,
+            textures,
+        )?;
+
+        let transmission_extension = extension("KHR_materials_transmission");
+        let transmission = self.parameter(
+            scalar(number(transmission_extension, "transmissionFactor", 0.0)),
+            transmission_extension.and_then(|t| t.get("transmissionTexture")),
+            Some(0),
+            false,
+            textures,
+        )?;
+
+        // glTF's default reflectance of 4% matches the principled default specular of 0.5.
+        let ior = number(extension("KHR_materials_ior"), "ior", 1.5);
+        let f0 = ((ior - 1.0) / (ior + 1.0)).powi(2);
+        let specular_factor = number(extension("KHR_materials_specular"), "specularFactor", 1.0);
+
+        let clearcoat_extension = extension("KHR_materials_clearcoat");
+        let sheen_color = color(
+            extension("KHR_materials_sheen"),
+            "sheenColorFactor",
+            [0.0, 0.0, 0.0],
+        );
+
+        let principled = Principled::new(base_color)
+            .with_metallic(metallic)
+            .with_roughness(roughness)
+            .with_emission(emission)
+            .with_transmission(transmission)
+            .with_ior(solid(ior))
+            .with_specular(solid(f0 / 0.08 * specular_factor))
+            .with_clearcoat(solid(number(clearcoat_extension, "clearcoatFactor", 0.0)))
+            .with_clearcoat_gloss(solid(
+                1.0 - number(clearcoat_extension, "clearcoatRoughnessFactor", 0.0),
+            ))
+            .with_sheen(solid(
+                sheen_color.r().max(sheen_color.g()).max(sheen_color.b()),
+            ));
+
+        // Normal textures are tangent-space maps whose scale multiplies the X and Y components.
+        let principled: Arc<dyn Material> = Arc::new(principled);
+        let normal_texture = material.get("normalTexture");
+        let principled: Arc<dyn Material> = match self.texture(normal_texture, false, textures)? {
+            Some(texture) => Arc::new(BumpMapped::normal_map(
+                principled,
+                texture,
+                number(normal_texture, "scale", 1.0),
+            )),
+            None => principled,
+        };
+
+        // Opacity comes from the base colour factor's alpha; images are loaded without their alpha
+        // channel.
+        let alpha = pbr
+            .and_then(|p| p.get("baseColorFactor"))
+            .and_then(|f| f.as_f64_vec())
+            .and_then(|f| f.get(3).cloned())
+            .unwrap_or(1.0);
+        Ok(match material.get("alphaMode").and_then(|m| m.as_str()) {
+            Some("MASK") => Arc::new(
+                AlphaMasked::new(principled, solid(alpha)).with_cutoff(number(
+                    Some(material),
+                    "alphaCutoff",
+                    0.5,
+                )),
+            ),
+            Some("BLEND") if alpha < 1.0 => Arc::new(AlphaMasked::new(principled, solid(alpha))),
+            _ => principled,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    // Three positions of one triangle, its u16 indices and two bytes of padding, then the
+    // keyframe times 0 and 1 and the translations (0, 0, 0) and (1, 0, 0).
+    const BUFFER: &str =
+        "AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAAAAAAAAACAPwAAAAAAAAAAAA\
+                          AAAAAAgD8AAAAAAAAAAA==";
+    // A 1x1 red PNG.
+    const PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAIAAACQd1PeAAAADElEQVR4nGP4z8AAAAMBAQDJ/pLvAAAAAElFTkSuQmCC";
+
+    // A document placing the triangle at the origin, with the top-level members in `members`
+    // added or replaced.
+    fn document(members: &[(&str, &str)]) -> String {
+        let buffers = format!(
+            r#"[{{"byteLength": 76, "uri": "data:application/octet-stream;base64,{}"}}]"#,
+            BUFFER
+        );
+        let mut document: BTreeMap<&str, String> = [
+            ("asset", r#"{"version": "2.0"}"#),
+            ("buffers", &buffers),
+            (
+                "bufferViews",
+                r#"[{"buffer": 0, "byteLength": 36},
+                    {"buffer": 0, "byteOffset": 36, "byteLength": 6},
+                    {"buffer": 0, "byteOffset": 44, "byteLength": 8},
+                    {"buffer": 0, "byteOffset": 52, "byteLength": 24}]"#,
+            ),
+            (
+                "accessors",
+                r#"[{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+                    {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"},
+                    {"bufferView": 2, "componentType": 5126, "count": 2, "type": "SCALAR"},
+                    {"bufferView": 3, "componentType": 5126, "count": 2, "type": "VEC3"}]"#,
+            ),
+            (
+                "meshes",
+                r#"[{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]}]"#,
+            ),
+            ("nodes", r#"[{"mesh": 0}]"#),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key, value.to_string()))
+        .collect();
+        for (key, value) in members {
+            document.insert(key, value.to_string());
+        }
+        let members: Vec<String> = document
+            .iter()
+            .map(|(key, value)| format!("\"{}\": {}", key, value))
+            .collect();
+        format!("{{{}}}", members.join(", "))
+    }
+
+    // Binary glTF holding the given chunks, each a type and its data.
+    fn glb(chunks: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (kind, data) in chunks {
+            body.extend((data.len() as u32).to_le_bytes());
+            body.extend(kind.to_le_bytes());
+            body.extend(*data);
+        }
+        let mut bytes = b"glTF".to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend((12 + body.len() as u32).to_le_bytes());
+        bytes.extend(body);
+        bytes
+    }
+
+    // Loads `bytes` from a file named after the test, so that tests running at once don't
+    // share one.
+    fn load(name: &str, bytes: &[u8]) -> Result<Model, Error> {
+        let path = std::env::temp_dir().join(format!(
+            "ray_tracer_gltf_{}_{}.gltf",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, bytes).unwrap();
+        let model = load_gltf(&path.to_string_lossy());
+        fs::remove_file(&path).unwrap();
+        model
+    }
+
+    // Checks that each document fails to load with an error containing its message.
+    fn assert_errors(name: &str, cases: &[(String, &str)]) {
+        for (i, (document, message)) in cases.iter().enumerate() {
+            match load(&format!("{}_{}", name, i), document.as_bytes()) {
+                Ok(_) => panic!("loaded a document that should fail with {}", message),
+                Err(e) => assert!(
+                    e.to_string().contains(message),
+                    "expected {}, got {}",
+                    message,
+                    e
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn loads_a_triangle() {
+        let model = load("triangle", document(&[]).as_bytes()).unwrap();
+        assert_eq!(model.triangles.primitives(), 1);
+        let bbox = model.triangles.bounding_box();
+        assert!((bbox.max().x() - 1.0).abs() < 1e-3 && (bbox.max().y() - 1.0).abs() < 1e-3);
+        assert!(model.lights.is_empty() && model.camera.is_none());
+    }
+
+    #[test]
+    fn places_nodes_through_the_hierarchy() {
+        let nodes = r#"[{"translation": [0, 0, 5], "scale": [2, 2, 2], "children": [1]},
+                        {"mesh": 0, "translation": [1, 0, 0]}]"#;
+        let model = load("hierarchy", document(&[("nodes", nodes)]).as_bytes()).unwrap();
+        let bbox = model.triangles.bounding_box();
+        assert!((bbox.min().x() - 2.0).abs() < 1e-3);
+        assert!((bbox.max().x() - 4.0).abs() < 1e-3);
+        assert!((bbox.min().z() - 5.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn names_materials_and_light_nodes() {
+        let document = document(&[
+            ("materials", r#"[{"name": "Red"}, {}]"#),
+            (
+                "meshes",
+                r#"[{"primitives": [{"attributes": {"POSITION": 0}, "material": 0}]}]"#,
+            ),
+            (
+                "nodes",
+                r#"[{"mesh": 0},
+                    {"name": "Lamp", "extensions": {"KHR_lights_punctual": {"light": 0}}},
+                    {"extensions": {"KHR_lights_punctual": {"light": 1}}}]"#,
+            ),
+            (
+                "extensions",
+                r#"{"KHR_lights_punctual": {"lights": [{"type": "point"},
+                                                       {"type": "directional"}]}}"#,
+            ),
+        ]);
+        let model = load("names", document.as_bytes()).unwrap();
+        assert_eq!(model.lights.len(), 2);
+        let materials: Vec<&str> = model
+            .names
+            .materials
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(materials, ["Red"]);
+        assert_eq!(model.names.lights, [("Lamp".to_string(), 0)]);
+    }
+
+    #[test]
+    fn loads_a_camera() {
+        let document = document(&[
+            (
+                "nodes",
+                r#"[{"mesh": 0}, {"camera": 0, "translation": [0, 0, 10]}]"#,
+            ),
+            (
+                "cameras",
+                r#"[{"type": "perspective", "perspective": {"yfov": 0.5, "aspectRatio": 2}}]"#,
+            ),
+        ]);
+        let camera = load("camera", document.as_bytes()).unwrap().camera.unwrap();
+        assert!((camera.vfov - 0.5f64.to_degrees()).abs() < 1e-9);
+        assert_eq!(camera.aspect_ratio, Some(2.0));
+        assert!((camera.look_from.z() - 10.0).abs() < 1e-9);
+        assert!(camera.look_at.z() < camera.look_from.z());
+    }
+
+    #[test]
+    fn animates_a_mesh() {
+        let document = document(&[(
+            "animations",
+            r#"[{"samplers": [{"input": 2, "output": 3}],
+                 "channels": [{"sampler": 0, "target": {"node": 0, "path": "translation"}}]}]"#,
+        )]);
+        let model = load("animation", document.as_bytes()).unwrap();
+        assert_eq!(model.triangles.primitives(), 1);
+        // The bounds cover the triangle at both ends of its move along +x.
+        let bbox = model.triangles.bounding_box();
+        assert!(bbox.min().x() < 1e-3 && bbox.max().x() > 2.0 - 1e-3);
+    }
+
+    #[test]
+    fn decodes_an_embedded_image() {
+        let images = format!(r#"[{{"uri": "data:image/png;base64,{}"}}]"#, PNG);
+        let document = document(&[
+            ("images", &images),
+            ("textures", r#"[{"source": 0}]"#),
+            (
+                "materials",
+                r#"[{"pbrMetallicRoughness": {"baseColorTexture": {"index": 0}}}]"#,
+            ),
+        ]);
+        assert!(load("image", document.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn loads_binary_gltf() {
+        let json = document(&[("buffers", r#"[{"byteLength": 76}]"#)]);
+        let binary = decode_base64(BUFFER).unwrap();
+        let bytes = glb(&[(0x4e4f534a, json.as_bytes()), (0x004e4942, &binary)]);
+        let model = load("glb", &bytes).unwrap();
+        assert_eq!(model.triangles.primitives(), 1);
+    }
+
+    #[test]
+    fn rejects_broken_binary_gltf() {
+        let json = document(&[]);
+        let mut truncated = glb(&[(0x4e4f534a, json.as_bytes())]);
+        truncated.truncate(truncated.len() - 1);
+        for (name, bytes, message) in [
+            ("truncated", truncated, "truncated GLB chunk"),
+            (
+                "without_json",
+                glb(&[(0x004e4942, b"data")]),
+                "GLB without JSON chunk",
+            ),
+        ] {
+            let e = load(name, &bytes).err().unwrap();
+            assert!(e.to_string().contains(message), "{}", e);
+        }
+    }
+
+    #[test]
+    fn rejects_broken_buffers() {
+        assert_errors(
+            "buffers",
+            &[
+                ("{".to_string(), "at byte"),
+                (
+                    document(&[("buffers", r#"[{"uri": "data:,A*"}]"#)]),
+                    "bad base64 buffer",
+                ),
+                (
+                    document(&[("buffers", r#"[{"byteLength": 76}]"#)]),
+                    "buffer without data",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn rejects_broken_meshes() {
+        let accessors = |position: &str| {
+            format!(
+                r#"[{}, {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}]"#,
+                position
+            )
+        };
+        let mesh = |primitive: &str| format!(r#"[{{"primitives": [{}]}}]"#, primitive);
+        assert_errors(
+            "meshes",
+            &[
+                (document(&[("nodes", r#"[{"mesh": 1}]"#)]), "bad mesh index"),
+                (
+                    document(&[("meshes", &mesh(r#"{"attributes": {}}"#))]),
+                    "primitive without positions",
+                ),
+                (
+                    document(&[("meshes", &mesh(r#"{"attributes": {"POSITION": 9}}"#))]),
+                    "bad accessor index",
+                ),
+                (
+                    document(&[(
+                        "meshes",
+                        &mesh(r#"{"attributes": {"POSITION": 0, "NORMAL": 1}}"#),
+                    )]),
+                    "NORMAL shorter than POSITION",
+                ),
+                (
+                    document(&[(
+                        "accessors",
+                        &accessors(
+                            r#"{"bufferView": 0, "componentType": 5126, "count": 2,
+                                "type": "VEC3"}"#,
+                        ),
+                    )]),
+                    "vertex index out of range",
+                ),
+                (
+                    document(&[(
+                        "accessors",
+                        &accessors(
+                            r#"{"bufferView": 0, "componentType": 5126, "count": 9,
+                                "type": "VEC3"}"#,
+                        ),
+                    )]),
+                    "accessor out of bounds",
+                ),
+                (
+                    document(&[(
+                        "accessors",
+                        &accessors(r#"{"bufferView": 0, "componentType": 5126, "type": "VEC3"}"#),
+                    )]),
+                    "accessor without count",
+                ),
+                (
+                    document(&[(
+                        "accessors",
+                        &accessors(
+                            r#"{"bufferView": 0, "componentType": 5126, "count": 3,
+                                "type": "VEC5"}"#,
+                        ),
+                    )]),
+                    "bad accessor type",
+                ),
+                (
+                    document(&[(
+                        "accessors",
+                        &accessors(
+                            r#"{"bufferView": 0, "componentType": 5130, "count": 3,
+                                "type": "VEC3"}"#,
+                        ),
+                    )]),
+                    "bad accessor component type",
+                ),
+                (
+                    document(&[(
+                        "accessors",
+                        &accessors(
+                            r#"{"bufferView": 0, "componentType": 5126, "count": 3,
+                                "type": "VEC3", "sparse": {}}"#,
+                        ),
+                    )]),
+                    "sparse accessors are not supported",
+                ),
+                (
+                    document(&[(
+                        "accessors",
+                        &accessors(
+                            r#"{"bufferView": 7, "componentType": 5126, "count": 3,
+                                "type": "VEC3"}"#,
+                        ),
+                    )]),
+                    "bad buffer view index",
+                ),
+                (
+                    document(&[(
+                        "bufferViews",
+                        r#"[{"buffer": 3, "byteLength": 36},
+                            {"buffer": 0, "byteOffset": 36, "byteLength": 6}]"#,
+                    )]),
+                    "bad buffer index",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn rejects_broken_nodes() {
+        assert_errors(
+            "nodes",
+            &[
+                (
+                    document(&[
+                        ("nodes", r#"[{"children": [1]}, {"children": [0]}]"#),
+                        ("scenes", r#"[{"nodes": [0]}]"#),
+                    ]),
+                    "cycle in the node hierarchy",
+                ),
+                (
+                    document(&[("scenes", r#"[{"nodes": [4]}]"#)]),
+                    "bad node index",
+                ),
+                (
+                    document(&[("nodes", r#"[{"children": ["one"]}]"#)]),
+                    "bad child index",
+                ),
+                (
+                    document(&[("nodes", r#"[{"mesh": 0, "translation": [1, 2]}]"#)]),
+                    "node 0: translation needs 3 numbers",
+                ),
+                (
+                    document(&[("nodes", r#"[{"mesh": 0, "matrix": [1, 0, 0, 0]}]"#)]),
+                    "node 0: matrix needs 16 numbers",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn rejects_broken_cameras_and_lights() {
+        let camera = |camera: &str| {
+            document(&[
+                ("nodes", r#"[{"mesh": 0}, {"camera": 0}]"#),
+                ("cameras", &format!("[{}]", camera)),
+            ])
+        };
+        let light = |light: &str| {
+            document(&[
+                (
+                    "nodes",
+                    r#"[{"mesh": 0}, {"extensions": {"KHR_lights_punctual": {"light": 0}}}]"#,
+                ),
+                (
+                    "extensions",
+                    &format!(r#"{{"KHR_lights_punctual": {{"lights": [{}]}}}}"#, light),
+                ),
+            ])
+        };
+        assert_errors(
+            "cameras_and_lights",
+            &[
+                (
+                    document(&[("nodes", r#"[{"mesh": 0}, {"camera": 0}]"#)]),
+                    "bad camera index",
+                ),
+                (
+                    camera(r#"{"type": "perspective", "perspective": {"yfov": 4}}"#),
+                    "bad perspective camera field of view",
+                ),
+                (
+                    camera(r#"{"type": "orthographic", "orthographic": {"xmag": 1}}"#),
+                    "bad orthographic camera magnification",
+                ),
+                (camera(r#"{"type": "fisheye"}"#), "unknown camera type"),
+                (
+                    document(&[(
+                        "nodes",
+                        r#"[{"mesh": 0}, {"extensions": {"KHR_lights_punctual": {"light": 0}}}]"#,
+                    )]),
+                    "bad light index",
+                ),
+                (light(r#"{"type": "area"}"#), "unknown light type"),
+            ],
+        );
+    }
+
+    #[test]
+    fn rejects_broken_textures() {
+        let textured = |textures: &str, images: &str| {
+            document(&[
+                ("textures", textures),
+                ("images", images),
+                (
+                    "materials",
+                    r#"[{"pbrMetallicRoughness": {"baseColorTexture": {"index": 0}}}]"#,
+                ),
+            ])
+        };
+        let with_image = |image: &str| textured(r#"[{"source": 0}]"#, &format!("[{}]", image));
+        assert_errors(
+            "textures",
+            &[
+                (textured("[]", "[]"), "bad texture index"),
+                (textured("[{}]", "[]"), "texture without source"),
+                (textured(r#"[{"source": 0}]"#, "[]"), "bad image index"),
+                (with_image(r#"{"uri": "data:image/png"}"#), "bad data URI"),
+                (
+                    with_image(r#"{"uri": "data:image/png;base64,*"}"#),
+                    "bad base64 image",
+                ),
+                (
+                    with_image(&format!(r#"{{"uri": "data:image/bmp;base64,{}"}}"#, PNG)),
+                    "image 0",
+                ),
+                (with_image("{}"), "image without data"),
+                (
+                    with_image(r#"{"bufferView": 0}"#),
+                    "buffer view image without MIME type",
+                ),
+                (
+                    with_image(r#"{"bufferView": 9, "mimeType": "image/png"}"#),
+                    "bad buffer view index",
+                ),
+                (
+                    document(&[
+                        ("bufferViews", r#"[{"buffer": 0}]"#),
+                        ("meshes", "[]"),
+                        ("nodes", "[]"),
+                        ("textures", r#"[{"source": 0}]"#),
+                        ("images", r#"[{"bufferView": 0, "mimeType": "image/png"}]"#),
+                        (
+                            "materials",
+                            r#"[{"pbrMetallicRoughness": {"baseColorTexture": {"index": 0}}}]"#,
+                        ),
+                    ]),
+                    "buffer view without length",
+                ),
+                (
+                    document(&[
+                        ("bufferViews", r#"[{"buffer": 0, "byteLength": 99}]"#),
+                        ("meshes", "[]"),
+                        ("nodes", "[]"),
+                        ("textures", r#"[{"source": 0}]"#),
+                        ("images", r#"[{"bufferView": 0, "mimeType": "image/png"}]"#),
+                        (
+                            "materials",
+                            r#"[{"pbrMetallicRoughness": {"baseColorTexture": {"index": 0}}}]"#,
+                        ),
+                    ]),
+                    "buffer view out of bounds",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn rejects_broken_animations() {
+        let animation = |sampler: &str, target: &str| {
+            document(&[
+                (
+                    "nodes",
+                    r#"[{"mesh": 0}, {"matrix": [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1]}]"#,
+                ),
+                (
+                    "animations",
+                    &format!(
+                        r#"[{{"samplers": [{}], "channels": [{{"sampler": 0, "target": {}}}]}}]"#,
+                        sampler, target
+                    ),
+                ),
+            ])
+        };
+        let sampler = r#"{"input": 2, "output": 3}"#;
+        let translation = r#"{"node": 0, "path": "translation"}"#;
+        assert_errors(
+            "animations",
+            &[
+                (
+                    animation(sampler, r#"{"node": 0, "path": "skew"}"#),
+                    "bad animation target path",
+                ),
+                (
+                    animation(sampler, r#"{"node": 5, "path": "translation"}"#),
+                    "bad animation target node",
+                ),
+                (
+                    animation(sampler, r#"{"node": 1, "path": "translation"}"#),
+                    "node 1: a node placed by a matrix can't be animated",
+                ),
+                (
+                    document(&[(
+                        "animations",
+                        r#"[{"samplers": [], "channels": [{"sampler": 0,
+                             "target": {"node": 0, "path": "translation"}}]}]"#,
+                    )]),
+                    "bad animation sampler index",
+                ),
+                (
+                    animation(
+                        r#"{"input": 2, "output": 3, "interpolation": "SMOOTH"}"#,
+                        translation,
+                    ),
+                    "unknown interpolation SMOOTH",
+                ),
+                (
+                    animation(r#"{"output": 3}"#, translation),
+                    "animation sampler without input",
+                ),
+                // The translations, read as times, repeat zero.
+                (
+                    animation(r#"{"input": 3, "output": 3}"#, translation),
+                    "animation times must increase",
+                ),
+                (
+                    animation(sampler, r#"{"node": 0, "path": "rotation"}"#),
+                    "animation output doesn't match its input",
+                ),
+                (
+                    animation(
+                        r#"{"input": 2, "output": 3, "interpolation": "CUBICSPLINE"}"#,
+                        translation,
+                    ),
+                    "animation output doesn't match its input",
+                ),
+            ],
+        );
+    }
+}
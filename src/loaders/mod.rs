@@ -0,0 +1,83 @@
+mod gltf;
+mod json;
+mod obj;
+
+pub(crate) use gltf::{load_gltf, ModelCamera};
+pub(crate) use obj::load_obj;
+
+use std::{collections::HashMap, io::Error, sync::Arc};
+
+use crate::{
+    image::Image,
+    lights::Light,
+    physics::Material,
+    shapes::HittableList,
+    textures::{ImageTexture, Texture},
+};
+
+// What a model file holds to render.
+pub(crate) struct Model {
+    pub(crate) triangles: HittableList,
+    pub(crate) lights: Vec<Arc<dyn Light>>,
+    pub(crate) camera: Option<ModelCamera>,
+    pub(crate) names: Names,
+}
+
+// Names a model file gives its materials and lights, which need not be unique.
+#[derive(Default)]
+pub(crate) struct Names {
+    pub(crate) materials: Vec<(String, Arc<dyn Material>)>,
+    // Indices into the model's lights.
+    pub(crate) lights: Vec<(String, usize)>,
+}
+
+// Image textures shared between the materials of one model, keyed by file and colour encoding.
+#[derive(Default)]
+pub(crate) struct TextureCache {
+    textures: HashMap<(String, bool), Arc<dyn Texture>>,
+}
+
+impl TextureCache {
+    // Loads a texture map once. A map that cannot be read is reported and skipped, so the material
+    // falls back to its constant value instead of failing the whole model.
+    pub(crate) fn load(&mut self, path: &str, srgb: bool) -> Option<Arc<dyn Texture>> {
+        let key = (path.to_string(), srgb);
+        if let Some(texture) = self.textures.get(&key) {
+            return Some(texture.clone());
+        }
+
+        match Image::load(path) {
+            Ok(image) => Some(self.insert(key, image)),
+            Err(e) => {
+                eprintln!("warning: {}", e);
+                None
+            }
+        }
+    }
+
+    // Decodes an image embedded in a model once, caching it under `name`. Embedded data that
+    // cannot be decoded is an error, as it leaves the model itself broken.
+    pub(crate) fn decode(
+        &mut self,
+        name: &str,
+        bytes: &[u8],
+        mime_type: &str,
+        srgb: bool,
+    ) -> Result<Arc<dyn Texture>, Error> {
+        let key = (name.to_string(), srgb);
+        if let Some(texture) = self.textures.get(&key) {
+            return Ok(texture.clone());
+        }
+
+        let image = Image::decode(bytes, mime_type)
+            .map_err(|e| Error::new(e.kind(), format!("{}: {}", name, e)))?;
+        Ok(self.insert(key, image))
+    }
+
+    fn insert(&mut self, key: (String, bool), image: Image) -> Arc<dyn Texture> {
+        let image = if key.1 { image.srgb_to_linear() } else { image };
+        let texture: Arc<dyn Texture> = Arc::new(ImageTexture::new(image));
+        self.textures.insert(key, texture.clone());
+        texture
+    }
+}
@@ -0,0 +1,443 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, ErrorKind},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    loaders::{Model, Names, TextureCache},
+    physics::{AlphaMasked, BumpMapped, Color, Material, Point3, Principled, Vec3},
+    shapes::{HittableList, Triangle, Vertex},
+    textures::{solid, ScaledTexture, SolidColor, Texture},
+};
+
+// Loads the triangles of a Wavefront OBJ file. Materials referenced through `mtllib` are
+// converted to principled materials, including the common PBR extension keywords
+// (Pr, Pm, Ps, Pc, Pcr, aniso, Ke and their maps), and keep their `newmtl` names.
+pub(crate) fn load_obj(path: &str) -> Result<Model, Error> {
+    let text = fs::read_to_string(path)?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    let invalid = |line: usize, msg: &str| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{}:{}: {}", path, line + 1, msg),
+        )
+    };
+
+    let default_material: Arc<dyn Material> = Arc::new(Principled::new(Arc::new(SolidColor::new(
+        Color::new(0.8, 0.8, 0.8),
+    ))));
+
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    let mut textures = TextureCache::default();
+    let mut current_material = default_material.clone();
+    let mut triangles = HittableList::default();
+
+    for (number, line) in text.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let rest: Vec<&str> = tokens.collect();
+        let floats = || -> Result<Vec<f64>, Error> {
+            rest.iter()
+                .map(|t| t.parse().map_err(|_| invalid(number, "bad number")))
+                .collect()
+        };
+
+        match keyword {
+            "v" => {
+                let v = floats()?;
+                if v.len() < 3 {
+                    return Err(invalid(number, "vertex needs three coordinates"));
+                }
+                positions.push(Point3::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let v = floats()?;
+                if v.len() < 3 {
+                    return Err(invalid(number, "normal needs three coordinates"));
+                }
+                normals.push(Vec3::new(v[0], v[1], v[2]).unit());
+            }
+            "vt" => {
+                let v = floats()?;
+                if v.is_empty() {
+                    return Err(invalid(number, "texture coordinate needs a value"));
+                }
+                uvs.push((v[0], v.get(1).cloned().unwrap_or(0.0)));
+            }
+            "f" => {
+                let vertices = rest
+                    .iter()
+                    .map(|corner| parse_corner(corner, &positions, &normals, &uvs))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| invalid(number, "bad face index"))?;
+                if vertices.len() < 3 {
+                    return Err(invalid(number, "face needs at least three vertices"));
+                }
+
+                // Triangulate polygons as a fan around the first vertex.
+                for i in 1..vertices.len() - 1 {
+                    triangles.add(Arc::new(Triangle::new(
+                        [
+                            vertices[0].clone(),
+                            vertices[i].clone(),
+                            vertices[i + 1].clone(),
+                        ],
+                        current_material.clone(),
+                    )));
+                }
+            }
+            "mtllib" => {
+                for library in &rest {
+                    let library_path = directory.join(library);
+                    let library_path = library_path.to_string_lossy();
+                    materials.extend(load_mtl(&library_path, &mut textures)?);
+                }
+            }
+            "usemtl" => {
+                let name = rest.join(" ");
+                current_material = match materials.get(&name) {
+                    Some(material) => material.clone(),
+                    None => {
+                        eprintln!("{}: unknown material '{}'", path, name);
+                        default_material.clone()
+                    }
+                };
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Model {
+        triangles,
+        lights: Vec::new(),
+        camera: None,
+        names: Names {
+            materials: materials.into_iter().collect(),
+            lights: Vec::new(),
+        },
+    })
+}
+
+// Resolves a face corner such as `3`, `3/1`, `3//2` or `3/1/2`, where negative indices count
+// back from the most recent element.
+fn parse_corner(
+    corner: &str,
+    positions: &[Point3],
+    normals: &[Vec3],
+    uvs: &[(f64, f64)],
+) -> Option<Vertex> {
+    fn resolve(index: &str, len: usize) -> Option<usize> {
+        let i: i64 = index.parse().ok()?;
+        let resolved = if i < 0 { len as i64 + i } else { i - 1 };
+        (0..len as i64)
+            .contains(&resolved)
+            .then_some(resolved as usize)
+    }
+
+    let mut parts = corner.split('/');
+    let p = positions[resolve(parts.next()?, positions.len())?].clone();
+    let mut vertex = Vertex::new(p);
+
+    if let Some(uv) = parts.next().filter(|s| !s.is_empty()) {
+        vertex.uv = Some(uvs[resolve(uv, uvs.len())?]);
+    }
+    if let Some(normal) = parts.next().filter(|s| !s.is_empty()) {
+        vertex.normal = Some(normals[resolve(normal, normals.len())?].clone());
+    }
+
+    Some(vertex)
+}
+
+// Material statements gathered from an MTL file before conversion.
+#[derive(Default)]
+struct MtlMaterial {
+    values: HashMap<String, Vec<f64>>,
+    maps: HashMap<String, String>,
+    illum: Option<usize>,
+}
+
+impl MtlMaterial {
+    fn scalar(&self, key: &str) -> Option<f64> {
+        self.values.get(key).and_then(|v| v.first().cloned())
+    }
+
+    fn color(&self, key: &str) -> Option<Color> {
+        self.values.get(key).map(|v| match v.len() {
+            0 => Color::new(0.0, 0.0, 0.0),
+            1 | 2 => Color::new(v[0], v[0], v[0]),
+            _ => Color::new(v[0], v[1], v[2]),
+        })
+    }
+
+    // Combines a constant with an optional texture map, multiplying them when both are given.
+    fn parameter(
+        &self,
+        constant: Option<Color>,
+        map: &str,
+        srgb: bool,
+        textures: &mut TextureCache,
+    ) -> Option<Arc<dyn Texture>> {
+        let texture = self
+            .maps
+            .get(map)
+            .and_then(|file| textures.load(file, srgb));
+        match (constant, texture) {
+            (Some(c), Some(t)) => Some(Arc::new(ScaledTexture::new(t, c))),
+            (None, Some(t)) => Some(t),
+            (Some(c), None) => Some(Arc::new(SolidColor::new(c))),
+            (None, None) => None,
+        }
+    }
+
+    fn to_material(&self, textures: &mut TextureCache) -> Arc<dyn Material> {
+        let scalar = |v: f64| Color::new(v, v, v);
+
+        let base_color = self
+            .parameter(
+                self.color("Kd").or(Some(Color::new(0.8, 0.8, 0.8))),
+                "map_Kd",
+                true,
+                textures,
+            )
+            .unwrap();
+        let mut material = Principled::new(base_color);
+
+        // Without explicit PBR roughness, derive it from the Phong exponent.
+        let roughness = self
+            .scalar("Pr")
+            .or_else(|| self.scalar("Ns").map(|ns| (2.0 / (ns + 2.0)).sqrt()));
+        if let Some(t) = self.parameter(roughness.map(scalar), "map_Pr", false, textures) {
+            material = material.with_roughness(t);
+        }
+        if let Some(t) = self.parameter(self.scalar("Pm").map(scalar), "map_Pm", false, textures) {
+            material = material.with_metallic(t);
+        }
+        if let Some(t) = self.parameter(self.scalar("Ps").map(scalar), "map_Ps", false, textures) {
+            material = material.with_sheen(t);
+        }
+        if let Some(pc) = self.scalar("Pc") {
+            material = material.with_clearcoat(solid(pc));
+        }
+        if let Some(pcr) = self.scalar("Pcr") {
+            material = material.with_clearcoat_gloss(solid(1.0 - pcr));
+        }
+        if let Some(aniso) = self.scalar("aniso") {
+            material = material.with_anisotropic(solid(aniso));
+        }
+        if let Some(ni) = self.scalar("Ni") {
+            material = material.with_ior(solid(ni));
+        }
+        if let Some(t) = self.parameter(self.color("Ke"), "map_Ke", true, textures) {
+            material = material.with_emission(t);
+        }
+
+        // Illumination models 4, 6, 7 and 9 describe refractive, glass-like surfaces.
+        if matches!(self.illum, Some(4 | 6 | 7 | 9)) {
+            material = material.with_transmission(solid(1.0));
+        }
+
+        // MTL gives bump maps no units, so heights are taken to span a hundredth of a unit before
+        // the `-bm` multiplier.
+        let material: Arc<dyn Material> = Arc::new(material);
+        let bump_multiplier = self.scalar("-bm").unwrap_or(1.0);
+        let material: Arc<dyn Material> =
+            if let Some(t) = self.maps.get("norm").and_then(|f| textures.load(f, false)) {
+                Arc::new(BumpMapped::normal_map(material, t, bump_multiplier))
+            } else if let Some(t) = ["bump", "map_Bump", "map_bump"]
+                .iter()
+                .find_map(|key| self.maps.get(*key))
+                .and_then(|f| textures.load(f, false))
+            {
+                Arc::new(BumpMapped::height_map(material, t, 0.01 * bump_multiplier))
+            } else {
+                material
+            };
+
+        // Dissolve, or its complement Tr, is the opacity; a map_d texture multiplies it.
+        let dissolve = self
+            .scalar("d")
+            .or_else(|| self.scalar("Tr").map(|tr| 1.0 - tr));
+        let has_map = self.maps.contains_key("map_d");
+        match self.parameter(dissolve.map(scalar), "map_d", false, textures) {
+            Some(opacity) if has_map || dissolve.is_some_and(|d| d < 1.0) => {
+                Arc::new(AlphaMasked::new(material, opacity))
+            }
+            _ => material,
+        }
+    }
+}
+
+fn load_mtl(
+    path: &str,
+    textures: &mut TextureCache,
+) -> Result<HashMap<String, Arc<dyn Material>>, Error> {
+    let text = fs::read_to_string(path)?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    let mut parsed: Vec<(String, MtlMaterial)> = Vec::new();
+    for line in text.lines() {
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let rest: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            parsed.push((rest.join(" "), MtlMaterial::default()));
+            continue;
+        }
+        let Some((_, current)) = parsed.last_mut() else {
+            continue;
+        };
+
+        if keyword == "illum" {
+            current.illum = rest.first().and_then(|t| t.parse().ok());
+        } else if keyword.starts_with("map_") || keyword == "norm" || keyword == "bump" {
+            // Map options such as `-bm 0.5` precede the file name, which comes last.
+            if let Some(i) = rest.iter().position(|t| *t == "-bm") {
+                if let Some(bm) = rest.get(i + 1).and_then(|t| t.parse().ok()) {
+                    current.values.insert("-bm".to_string(), vec![bm]);
+                }
+            }
+            if let Some(file) = rest.last() {
+                let file = directory.join(file).to_string_lossy().into_owned();
+                current.maps.insert(keyword.to_string(), file);
+            }
+        } else {
+            let values: Vec<f64> = rest.iter().filter_map(|t| t.parse().ok()).collect();
+            current.values.insert(keyword.to_string(), values);
+        }
+    }
+
+    Ok(parsed
+        .into_iter()
+        .map(|(name, mtl)| (name, mtl.to_material(textures)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shapes::Hittable;
+
+    use super::*;
+
+    // Writes `files`, each a name and its text, to a directory named after the test and loads
+    // the first as an OBJ file.
+    fn load(test: &str, files: &[(&str, &str)]) -> Result<Model, Error> {
+        let directory =
+            std::env::temp_dir().join(format!("ray_tracer_obj_{}_{}", std::process::id(), test));
+        fs::create_dir_all(&directory).unwrap();
+        for (name, text) in files {
+            fs::write(directory.join(name), text).unwrap();
+        }
+        let model = load_obj(&directory.join(files[0].0).to_string_lossy());
+        fs::remove_dir_all(&directory).unwrap();
+        model
+    }
+
+    #[test]
+    fn triangulates_polygons() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+        let model = load("polygon", &[("quad.obj", obj)]).unwrap();
+        assert_eq!(model.triangles.len(), 2);
+        assert!(model.lights.is_empty() && model.camera.is_none());
+    }
+
+    #[test]
+    fn resolves_corners() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1\nvt 0 1\nvn 0 0 2\n\
+                   f 1/1/1 2/2/1 3/3/1\nf 1//1 2//1 3//1\nf 1/1 2/2 3/3\n";
+        let model = load("corners", &[("corners.obj", obj)]).unwrap();
+        assert_eq!(model.triangles.len(), 3);
+    }
+
+    #[test]
+    fn counts_negative_indices_back_from_the_latest_vertex() {
+        // The face refers to the three vertices before it, not to the one that follows.
+        let obj = "v 5 5 5\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 9 9 9\n";
+        let model = load("negative", &[("negative.obj", obj)]).unwrap();
+        let bbox = model.triangles.bounding_box();
+        assert!(bbox.max().x() < 1.01 && bbox.max().y() < 1.01);
+        assert!(bbox.min().x() > -0.01 && bbox.min().y() > -0.01);
+    }
+
+    #[test]
+    fn names_materials() {
+        let obj = "mtllib scene.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                   usemtl Card Red\nf 1 2 3\nusemtl Missing\nf 1 2 3\n";
+        let mtl = "newmtl Card Red\nKd 0.8 0.1 0.1\nnewmtl Floor\nKd 0.5 0.5 0.5\nPm 1\n";
+        let model = load("materials", &[("scene.obj", obj), ("scene.mtl", mtl)]).unwrap();
+        let mut names: Vec<&str> = model
+            .names
+            .materials
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["Card Red", "Floor"]);
+
+        // The first face has the named material, the second the default one.
+        let card = &model
+            .names
+            .materials
+            .iter()
+            .find(|(n, _)| n == "Card Red")
+            .unwrap()
+            .1;
+        let mut used = Vec::new();
+        model
+            .triangles
+            .materials(&mut |m| used.push(Arc::ptr_eq(m, card)));
+        assert_eq!(used, [true, false]);
+    }
+
+    #[test]
+    fn rejects_a_missing_material_library() {
+        assert!(load("library", &[("scene.obj", "mtllib missing.mtl\n")]).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_statements() {
+        let triangle = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+        for (i, (obj, message)) in [
+            ("v 0 x 0\n".to_string(), ":1: bad number"),
+            ("v 0 0\n".to_string(), ":1: vertex needs three coordinates"),
+            ("vn 0 1\n".to_string(), ":1: normal needs three coordinates"),
+            ("vt\n".to_string(), ":1: texture coordinate needs a value"),
+            (
+                format!("{}f 1 2\n", triangle),
+                ":4: face needs at least three vertices",
+            ),
+            (format!("{}f 0 1 2\n", triangle), ":4: bad face index"),
+            (format!("{}f 1 2 4\n", triangle), ":4: bad face index"),
+            (format!("{}f -4 -2 -1\n", triangle), ":4: bad face index"),
+            (format!("{}f 1 2 x\n", triangle), ":4: bad face index"),
+            (format!("{}f 1/1 2/1 3/1\n", triangle), ":4: bad face index"),
+            (
+                format!("{}f 1//1 2//1 3//1\n", triangle),
+                ":4: bad face index",
+            ),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let e = load(&format!("malformed_{}", i), &[("bad.obj", obj)])
+                .err()
+                .unwrap();
+            assert!(
+                e.to_string().contains(message),
+                "expected {}, got {}",
+                message,
+                e
+            );
+        }
+    }
+}
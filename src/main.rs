@@ -1,99 +1,883 @@
+mod animation;
 mod camera;
+mod image;
+mod lights;
+mod loaders;
 mod physics;
+mod scenes;
 mod shapes;
+mod textures;
 mod utils;
 
-use std::{env, io::Error, rc::Rc};
+use std::{
+    env,
+    io::{Error, ErrorKind},
+    path::Path,
+    process::ExitCode,
+    str::FromStr,
+    sync::Arc,
+    thread,
+};
 
-use crate::{
-    camera::Camera,
-    physics::{Color, Dielectric, LambertianMaterial, Material, Metal, Point3, Vec3},
-    shapes::{HittableList, Sphere},
-    utils::{random_color, random_color_in_interval, random_f64, random_f64_in_interval},
+use camera::{
+    Aov, Aperture, Camera, Filter, FisheyeMapping, LensSystem, PhotographicSettings, Region,
+    StereoLayout, AOV_NAMES, FILTER_NAMES, LENS_NAMES,
 };
+use image::{Denoiser, Image};
+use lights::{Background, EnvironmentMap, Light, LightSampler};
+use scenes::{SCENE_KEYS, SCENE_NAMES};
+use shapes::{Hittable, HittableList};
+use utils::seed_random;
 
-fn generate_material() -> Rc<dyn Material> {
-    let choose_mat = random_f64();
-    if choose_mat < 0.8 {
-        // diffuse
-        let albedo = random_color() * random_color();
-        Rc::new(LambertianMaterial::new(albedo))
-    } else if choose_mat < 0.95 {
-        //metal
-        let albedo = random_color_in_interval(0.5, 1.0);
-        let fuzz = random_f64_in_interval(0.0, 0.5);
-        Rc::new(Metal::new(albedo, fuzz))
-    } else {
-        //glass
-        Rc::new(Dielectric::new(1.5))
+// Options written `--name=value` and flags written `--name` alone, as the commands accept them.
+// `--set` may also take its value as the next argument, and be given more than once.
+const OPTIONS: &[&str] = &[
+    "--output",
+    "--format",
+    "--spp",
+    "--resolution",
+    "--threads",
+    "--seed",
+    "--integrator",
+    "--set",
+    "--env",
+    "--env-rotation",
+    "--env-intensity",
+    "--light-sampler",
+    "--orthographic",
+    "--panorama",
+    "--fov",
+    "--ipd",
+    "--defocus",
+    "--cats-eye",
+    "--blades",
+    "--blade-rotation",
+    "--aperture-mask",
+    "--focal-length",
+    "--f-number",
+    "--shutter-speed",
+    "--iso",
+    "--shutter-open",
+    "--shutter-close",
+    "--frames",
+    "--fps",
+    "--lens",
+    "--sensor",
+    "--stereo",
+    "--convergence",
+    "--filter",
+    "--filter-radius",
+    "--region",
+    "--crop-window",
+    "--debug-pixel",
+    "--aovs",
+    "--denoise",
+    "--grid",
+    "--density",
+];
+const FLAGS: &[&str] = &["--aov-layers", "--crop"];
+
+// Formats images can be written in, by file extension.
+const FORMATS: &[&str] = &["ppm", "exr"];
+
+// Value of a `--name=value` option, if given.
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .find_map(|arg| arg.strip_prefix(name)?.strip_prefix('='))
+}
+
+// Value of a `--name=value` option, if given, parsed as a `T`. The error says the value
+// should have been `expected`.
+fn parse_value<T: FromStr>(
+    args: &[String],
+    name: &str,
+    expected: &str,
+) -> Result<Option<T>, Error> {
+    option(args, name)
+        .map(|value| {
+            value.parse().map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{}: expected {}, got {}", name, expected, value),
+                )
+            })
+        })
+        .transpose()
+}
+
+// Value of a `--name=value` option, if given, as a count of at least `min`.
+fn parse_count(args: &[String], name: &str, min: usize) -> Result<Option<usize>, Error> {
+    let expected = format!("a whole number of at least {}", min);
+    let count = parse_value(args, name, &expected)?;
+    match count {
+        Some(count) if count < min => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{}: expected {}, got {}", name, expected, count),
+        )),
+        _ => Ok(count),
+    }
+}
+
+fn parse_option(args: &[String], name: &str, default: f64) -> Result<f64, Error> {
+    Ok(parse_value(args, name, "a number")?.unwrap_or(default))
+}
+
+// Like `parse_option`, for values that have to be `valid`, described as `expected`.
+fn parse_checked(
+    args: &[String],
+    name: &str,
+    default: f64,
+    valid: impl Fn(f64) -> bool,
+    expected: &str,
+) -> Result<f64, Error> {
+    let value = parse_option(args, name, default)?;
+    if !valid(value) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{}: expected {}, got {}", name, expected, value),
+        ));
+    }
+    Ok(value)
+}
+
+// Like `parse_option`, for quantities that have to be positive and finite.
+fn parse_positive(args: &[String], name: &str, default: f64) -> Result<f64, Error> {
+    parse_checked(
+        args,
+        name,
+        default,
+        |v| v > 0.0 && v.is_finite(),
+        "a positive number",
+    )
+}
+
+// Values of a `--name=a,b,...` option, if given, of which there must be exactly `N`.
+fn parse_list<T: FromStr, const N: usize>(
+    args: &[String],
+    name: &str,
+) -> Result<Option<[T; N]>, Error> {
+    option(args, name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(|v| v.trim().parse().ok())
+                .collect::<Option<Vec<T>>>()
+                .and_then(|values| values.try_into().ok())
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "{}: expected {} numbers separated by commas, got {}",
+                            name, N, value
+                        ),
+                    )
+                })
+        })
+        .transpose()
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            // Mistakes on the command line point to the usage, and exit with a code of their own.
+            if error.kind() == ErrorKind::InvalidInput {
+                eprintln!("run `ray_tracer help` for usage");
+                ExitCode::from(2)
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Error> {
+    let Some((command, args)) = args.split_first() else {
+        print_usage();
+        return Err(Error::new(ErrorKind::InvalidInput, "no command given"));
+    };
+    match command.as_str() {
+        "render" => render(&setup(args)?),
+        "info" => info(&setup(args)?),
+        "validate" => validate(&setup(args)?),
+        "help" | "--help" | "-h" => {
+            print_usage();
+            Ok(())
+        }
+        other => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("unknown command {}", other),
+        )),
     }
 }
 
-fn main() -> Result<(), Error> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        println!("usage: ray_tracer <file>");
-        return Ok(());
+fn print_usage() {
+    println!("usage: ray_tracer <command> <scene | model file | volume file> [options]");
+    println!("commands:");
+    println!("  render    render the scene to the output file");
+    println!("  info      print statistics of the scene as it would be rendered");
+    println!("  validate  load the scene and check the options without rendering");
+    println!("  help      print this message");
+    println!("options:");
+    println!("  [--output=<file>] [--format=ppm|exr] [--spp=<samples per pixel>]");
+    println!("  [--resolution=<width>[x<height>]] [--threads=<count>] [--seed=<number>]");
+    println!("  [--integrator=rgb|spectral] [--set <key>=<value>]...");
+    println!("  [--env=<map.hdr> [--env-rotation=<degrees>] [--env-intensity=<scale>]]");
+    println!("  [--light-sampler=uniform|power|bvh] [--orthographic=<view width>]");
+    println!("  [--panorama=equirectangular|ods|fisheye|equisolid|cubemap]");
+    println!("  [--defocus=<degrees>] [--cats-eye=<amount>]");
+    println!("  [--blades=<count> [--blade-rotation=<degrees>] | --aperture-mask=<image>]");
+    println!("  [--focal-length=<mm>] [--f-number=<N>] [--shutter-speed=<seconds>]");
+    println!("  [--iso=<speed>] [--shutter-open=<time>] [--shutter-close=<time>]");
+    println!("  [--frames=<first>..<last> [--fps=<frames per second>]]");
+    println!("  [--lens=<name | prescription file>] [--sensor=<width in mm>]");
+    println!("  [--stereo=separate|side-by-side|over-under|anaglyph]");
+    println!("  [--fov=<fisheye degrees>] [--ipd=<eye distance>] [--convergence=<distance>]");
+    println!("  [--filter=<name>] [--filter-radius=<pixels>]");
+    println!("  [--region=<x0>,<y0>,<x1>,<y1> | --crop-window=<x0>,<y0>,<x1>,<y1> [--crop]]");
+    println!("  [--debug-pixel=<x>,<y>]");
+    println!("  [--aovs=<pass>,... [--aov-layers]] [--denoise=atrous|bilateral]");
+    println!("  [--grid=<nx>,<ny>,<nz>] [--density=<scale>]");
+    println!("scenes: {}", SCENE_NAMES.join(", "));
+    println!("model files: .obj, .gltf, .glb");
+    println!("volume files: .vol, .raw (f32 densities of a --grid resolution)");
+    println!("keys --set can override: {}", SCENE_KEYS.join(", "));
+    println!("lenses: {}", LENS_NAMES.join(", "));
+    println!("passes: {}", AOV_NAMES.join(", "));
+    println!("filters: {}", FILTER_NAMES.join(", "));
+}
+
+// The scene argument, after checking that every other argument is an option the commands know.
+fn scene_argument(args: &[String]) -> Result<&str, Error> {
+    let mut scene = None;
+    let mut seen = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--set" {
+            args.next().ok_or_else(|| {
+                Error::new(ErrorKind::InvalidInput, "--set: expected <key>=<value>")
+            })?;
+            continue;
+        }
+        if !arg.starts_with("--") {
+            if let Some(previous) = scene.replace(arg.as_str()) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("expected a single scene, got {} and {}", previous, arg),
+                ));
+            }
+            continue;
+        }
+        let (name, has_value) = arg
+            .split_once('=')
+            .map_or((arg.as_str(), false), |(n, _)| (n, true));
+        // Only `--set` may be repeated, any other option would have one value ignored.
+        if name != "--set" && seen.contains(&name) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} given more than once", name),
+            ));
+        }
+        seen.push(name);
+        match (OPTIONS.contains(&name), FLAGS.contains(&name), has_value) {
+            (true, _, true) | (_, true, false) => {}
+            (true, _, false) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{}: expected {}=<value>", name, name),
+                ))
+            }
+            (_, true, true) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{} takes no value", name),
+                ))
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("unknown option {}", name),
+                ))
+            }
+        }
     }
-    let out_filename = &args[1];
+    scene.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "no scene given"))
+}
 
-    let mut world = HittableList::default();
+// Overrides given as `--set <key>=<value>` or `--set=<key>=<value>`, in order.
+fn overrides(args: &[String]) -> Result<Vec<(&str, &str)>, Error> {
+    let mut overrides = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let setting = if arg == "--set" {
+            args.next().map(String::as_str)
+        } else if let Some(setting) = arg.strip_prefix("--set=") {
+            Some(setting)
+        } else {
+            continue;
+        };
+        let setting = setting.unwrap_or("");
+        overrides.push(setting.split_once('=').ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("--set: expected <key>=<value>, got {}", setting),
+            )
+        })?);
+    }
+    Ok(overrides)
+}
+
+// Puts the path of the file that failed to load in front of the error, unless the loader already
+// did.
+fn in_file(path: &str) -> impl Fn(Error) -> Error + '_ {
+    move |e| {
+        if e.to_string().starts_with(path) {
+            e
+        } else {
+            Error::new(e.kind(), format!("{}: {}", path, e))
+        }
+    }
+}
+
+// A scene with the camera and lights the command line asks for, ready to render.
+struct Setup {
+    scene_name: String,
+    world: HittableList,
+    background: Background,
+    camera: Camera,
+    // The scene's lights followed by its emitting shapes, which number `emitting`.
+    lights: Vec<Arc<dyn Light>>,
+    emitting: usize,
+    light_sampler: fn(Vec<Arc<dyn Light>>) -> LightSampler,
+    output: String,
+    frames: Option<(i64, i64)>,
+    fps: f64,
+    debug_pixel: Option<[usize; 2]>,
+}
+
+fn setup(args: &[String]) -> Result<Setup, Error> {
+    let scene_name = scene_argument(args)?;
+    let overrides = overrides(args)?;
 
-    let material_ground = Rc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5)));
-    world.add(Rc::new(Sphere::new(
-        Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        material_ground,
-    )));
+    // The output's extension picks the format, unless one is given.
+    let output = option(args, "--output").unwrap_or("image.ppm");
+    let output = match option(args, "--format") {
+        Some(format) if FORMATS.contains(&format) => Path::new(output)
+            .with_extension(format)
+            .to_string_lossy()
+            .into_owned(),
+        Some(format) => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "--format: expected {}, got {}",
+                    FORMATS.join(" or "),
+                    format
+                ),
+            ))
+        }
+        None => output.to_string(),
+    };
+    let extension = Path::new(&output)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    if !extension.as_deref().is_some_and(|e| FORMATS.contains(&e)) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("--output: {} isn't a {} file", output, FORMATS.join(" or ")),
+        ));
+    }
 
-    for a in (-11..11).map(|a| a as f64) {
-        for b in (-11..11).map(|b| b as f64) {
-            let center = Point3::new(a + 0.9 * random_f64(), 0.2, b + 0.9 * random_f64());
-            if (&center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let material = generate_material();
-                world.add(Rc::new(Sphere::new(center, 0.2, material)));
+    let samples_per_pixel = parse_count(args, "--spp", 1)?;
+    let resolution = option(args, "--resolution")
+        .map(|value| {
+            let (width, height) = match value.split_once('x') {
+                Some((width, height)) => (width, Some(height)),
+                None => (value, None),
+            };
+            let parse = |v: &str| v.parse::<usize>().ok().filter(|&v| v > 0);
+            let width = parse(width);
+            let height = height.map(parse);
+            match (width, height) {
+                (Some(width), None) => Ok((width, None)),
+                (Some(width), Some(Some(height))) => Ok((width, Some(height))),
+                _ => Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "--resolution: expected <width> or <width>x<height>, got {}",
+                        value
+                    ),
+                )),
             }
+        })
+        .transpose()?;
+    let threads = match parse_count(args, "--threads", 1)? {
+        Some(threads) => threads,
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    let seed: Option<u64> = parse_value(args, "--seed", "a whole number")?;
+    let spectral = match option(args, "--integrator") {
+        None => None,
+        Some("rgb") => Some(false),
+        Some("spectral") => Some(true),
+        Some(other) => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("--integrator: expected rgb or spectral, got {}", other),
+            ))
+        }
+    };
+    let environment = option(args, "--env")
+        .map(|path| -> Result<EnvironmentMap, Error> {
+            Ok(EnvironmentMap::load(path)
+                .map_err(in_file(path))?
+                .with_rotation(parse_option(args, "--env-rotation", 0.0)?)
+                .with_intensity(parse_option(args, "--env-intensity", 1.0)?))
+        })
+        .transpose()?;
+    let orthographic = option(args, "--orthographic")
+        .map(|_| parse_positive(args, "--orthographic", 1.0))
+        .transpose()?;
+    let valid_fov = |v| (1.0..=360.0).contains(&v);
+    let fov = parse_checked(args, "--fov", 180.0, valid_fov, "1 to 360 degrees")?;
+    let ipd = parse_positive(args, "--ipd", 0.064)?;
+    let panorama: Option<Box<dyn FnOnce(Camera) -> Camera>> = match option(args, "--panorama") {
+        None => None,
+        Some("equirectangular") => Some(Box::new(Camera::with_equirectangular)),
+        Some("ods") => Some(Box::new(move |c: Camera| {
+            c.with_stereo_equirectangular(ipd)
+        })),
+        Some("fisheye") => Some(Box::new(move |c: Camera| {
+            c.with_fisheye(fov, FisheyeMapping::Equidistant)
+        })),
+        Some("equisolid") => Some(Box::new(move |c: Camera| {
+            c.with_fisheye(fov, FisheyeMapping::Equisolid)
+        })),
+        Some("cubemap") => Some(Box::new(Camera::with_cubemap)),
+        Some(other) => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                "--panorama: expected equirectangular, ods, fisheye, equisolid or cubemap, got {}",
+                other
+            ),
+            ))
+        }
+    };
+    let defocus = option(args, "--defocus")
+        .map(|_| {
+            let valid = |v| (0.0..180.0).contains(&v);
+            parse_checked(args, "--defocus", 0.0, valid, "0 to 180 degrees")
+        })
+        .transpose()?;
+    let aperture = match option(args, "--aperture-mask") {
+        Some(path) => Some(Aperture::load_mask(path).map_err(in_file(path))?),
+        None => parse_count(args, "--blades", 3)?
+            .map(|blades| -> Result<Aperture, Error> {
+                Ok(Aperture::polygon(
+                    blades,
+                    parse_option(args, "--blade-rotation", 0.0)?,
+                ))
+            })
+            .transpose()?,
+    };
+    let cats_eye = option(args, "--cats-eye")
+        .map(|_| {
+            let valid = |v: f64| v >= 0.0 && v.is_finite();
+            parse_checked(args, "--cats-eye", 0.0, valid, "a number of at least 0")
+        })
+        .transpose()?;
+    // A bundled lens by name, or else a prescription file.
+    let lens = option(args, "--lens")
+        .map(|lens| {
+            LensSystem::by_name(lens)
+                .map_or_else(|| LensSystem::load(lens).map_err(in_file(lens)), Ok)
+        })
+        .transpose()?
+        .map(|lens| match &aperture {
+            Some(aperture) => lens.with_aperture(aperture.clone()),
+            None => lens,
+        });
+    // Each of these replaces the projection the others would give.
+    let projections = ["--orthographic", "--panorama", "--lens"]
+        .into_iter()
+        .filter(|name| option(args, name).is_some())
+        .collect::<Vec<_>>();
+    if projections.len() > 1 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} can't be combined", projections.join(" and ")),
+        ));
+    }
+    let sensor_width = parse_positive(args, "--sensor", 36.0)?;
+    // Photographic settings when any is given, filling in the rest from a 50 mm lens at f/8,
+    // 1/125 s and ISO 100. Shutter times may be written as fractions of a second.
+    let settings = if ["--focal-length", "--f-number", "--shutter-speed", "--iso"]
+        .iter()
+        .any(|name| option(args, name).is_some())
+    {
+        let shutter_time = match option(args, "--shutter-speed") {
+            None => 1.0 / 125.0,
+            Some(value) => value
+                .split_once('/')
+                .map_or_else(
+                    || value.parse().ok(),
+                    |(a, b)| Some(a.parse::<f64>().ok()? / b.parse::<f64>().ok()?),
+                )
+                .filter(|&t: &f64| t > 0.0 && t.is_finite())
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("--shutter-speed: expected positive seconds, got {}", value),
+                    )
+                })?,
+        };
+        Some(PhotographicSettings {
+            sensor_width,
+            focal_length: parse_positive(args, "--focal-length", 50.0)?,
+            f_number: parse_positive(args, "--f-number", 8.0)?,
+            shutter_time,
+            iso: parse_positive(args, "--iso", 100.0)?,
+        })
+    } else {
+        None
+    };
+    let shutter =
+        if option(args, "--shutter-open").is_some() || option(args, "--shutter-close").is_some() {
+            let open = parse_option(args, "--shutter-open", 0.0)?;
+            Some((open, parse_option(args, "--shutter-close", open)?))
+        } else {
+            None
+        };
+    // Inclusive range of frames to render, numbering the output files.
+    let frames = option(args, "--frames")
+        .map(|range| {
+            range
+                .split_once("..")
+                .and_then(|(first, last)| Some((first.parse().ok()?, last.parse().ok()?)))
+                .filter(|(first, last): &(i64, i64)| first <= last)
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("--frames: expected <first>..<last>, got {}", range),
+                    )
+                })
+        })
+        .transpose()?;
+    let fps = parse_positive(args, "--fps", 24.0)?;
+    let convergence = option(args, "--convergence")
+        .map(|_| parse_positive(args, "--convergence", 1.0))
+        .transpose()?;
+    let stereo = match option(args, "--stereo") {
+        None => None,
+        Some("separate") => Some(StereoLayout::Separate),
+        Some("side-by-side") => Some(StereoLayout::SideBySide),
+        Some("over-under") => Some(StereoLayout::OverUnder),
+        Some("anaglyph") => Some(StereoLayout::Anaglyph),
+        Some(other) => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "--stereo: expected separate, side-by-side, over-under or anaglyph, got {}",
+                    other
+                ),
+            ))
+        }
+    };
+    let aovs = option(args, "--aovs")
+        .map(|names| {
+            names
+                .split(',')
+                .map(|name| {
+                    Aov::by_name(name).ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("--aovs: unknown pass {}", name),
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?;
+    let aov_layers = args.iter().any(|arg| arg == "--aov-layers");
+    // Only OpenEXR images hold layers, which is better found out before rendering.
+    if aov_layers && extension.as_deref() != Some("exr") {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("--aov-layers: {} isn't an exr file", output),
+        ));
+    }
+    let filter = option(args, "--filter")
+        .map(|name| {
+            Filter::by_name(name).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("--filter: unknown filter {}", name),
+                )
+            })
+        })
+        .transpose()?;
+    let filter_radius = option(args, "--filter-radius")
+        .map(|_| parse_option(args, "--filter-radius", 0.0))
+        .transpose()?;
+    let region = match parse_list(args, "--region")? {
+        Some(bounds) => Some(Region::Pixels(bounds)),
+        None => parse_list(args, "--crop-window")?.map(Region::Normalized),
+    };
+    let crop = args.iter().any(|arg| arg == "--crop");
+    let debug_pixel: Option<[usize; 2]> = parse_list(args, "--debug-pixel")?;
+    let grid: Option<[usize; 3]> = parse_list(args, "--grid")?;
+    let density = parse_positive(args, "--density", 1.0)?;
+    let denoiser = match option(args, "--denoise") {
+        None => None,
+        Some("atrous") => Some(Denoiser::Atrous),
+        Some("bilateral") => Some(Denoiser::Bilateral),
+        Some(other) => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("--denoise: expected atrous or bilateral, got {}", other),
+            ))
         }
+    };
+    let light_sampler = match option(args, "--light-sampler").unwrap_or("bvh") {
+        "uniform" => LightSampler::uniform,
+        "power" => LightSampler::power,
+        "bvh" => LightSampler::bvh,
+        other => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "--light-sampler: expected uniform, power or bvh, got {}",
+                    other
+                ),
+            ))
+        }
+    };
+
+    // Scenes scattering random objects come out the same for the same seed.
+    if let Some(seed) = seed {
+        seed_random(seed);
+    }
+    // Model and volume files are rendered with an automatic camera, anything else names a
+    // built-in scene.
+    let has_extension = |extensions: &[&str]| {
+        extensions
+            .iter()
+            .any(|ext| scene_name.to_ascii_lowercase().ends_with(ext))
+    };
+    let mut scene = if has_extension(&[".obj", ".gltf", ".glb"]) {
+        scenes::from_model(scene_name).map_err(in_file(scene_name))?
+    } else if has_extension(&[".vol", ".raw"]) {
+        scenes::from_volume(scene_name, grid, density).map_err(in_file(scene_name))?
+    } else {
+        scenes::by_name(scene_name).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "unknown scene {}, expected a model or volume file or one of {}",
+                    scene_name,
+                    SCENE_NAMES.join(", ")
+                ),
+            )
+        })?
+    };
+    for (key, value) in overrides {
+        scene.set(key, value)?;
+    }
+
+    // An environment map given on the command line replaces the scene's own background.
+    if let Some(environment) = environment {
+        scene.background = Background::Map(environment);
+    }
+
+    // A panorama fixes the shape of the image, which an explicit resolution then has to fit.
+    let mut camera = scene.camera.with_threads(threads);
+    if let Some(panorama) = panorama {
+        camera = panorama(camera);
+    }
+    if let Some((width, height)) = resolution {
+        camera = camera.with_resolution(width, height)?;
+    }
+    if let Some(samples_per_pixel) = samples_per_pixel {
+        camera = camera.with_samples_per_pixel(samples_per_pixel);
+    }
+    if let Some(spectral) = spectral {
+        camera = camera.with_spectral(spectral);
+    }
+    if let Some(seed) = seed {
+        camera = camera.with_seed(seed);
+    }
+    if let Some(settings) = &settings {
+        camera = camera.with_settings(settings);
+    }
+    if let Some(view_width) = orthographic {
+        camera = camera.with_orthographic(view_width);
+    }
+    if let Some((open, close)) = shutter {
+        camera = camera.with_shutter(open, close);
+    }
+    if let Some(angle) = defocus {
+        camera = camera.with_defocus(angle);
+    }
+    if let Some(aperture) = aperture {
+        camera = camera.with_aperture(aperture);
+    }
+    if let Some(amount) = cats_eye {
+        camera = camera.with_cats_eye(amount);
+    }
+    if let Some(lens) = &lens {
+        camera = camera.with_lens(lens, sensor_width)?;
+    }
+    if let Some(layout) = stereo {
+        camera = camera.with_stereo(ipd, convergence, layout);
+    }
+    if let Some(aovs) = aovs {
+        camera = camera.with_aovs(aovs, aov_layers);
+    }
+    if filter.is_some() || filter_radius.is_some() {
+        camera = camera.with_filter(filter.unwrap_or(Filter::Box), filter_radius)?;
+    }
+    if let Some(denoiser) = denoiser {
+        camera = camera.with_denoiser(denoiser);
+    }
+    if let Some(region) = region {
+        camera = camera.with_region(region, crop)?;
+    }
+    // Emitting shapes in the world are sampled alongside the scene's lights.
+    let mut lights = scene.lights;
+    let emitting = scene.world.lights();
+    let emitting_count = emitting.len();
+    lights.extend(emitting);
+
+    Ok(Setup {
+        scene_name: scene_name.to_string(),
+        world: scene.world,
+        background: scene.background,
+        camera,
+        lights,
+        emitting: emitting_count,
+        light_sampler,
+        output,
+        frames,
+        fps,
+        debug_pixel,
+    })
+}
+
+fn render(setup: &Setup) -> Result<(), Error> {
+    let lights = (setup.light_sampler)(setup.lights.clone());
+    let (world, background) = (&setup.world, &setup.background);
+    if let Some([x, y]) = setup.debug_pixel {
+        return setup
+            .camera
+            .at(0.0)?
+            .debug_pixel(x, y, world, background, &lights);
     }
 
-    let material1 = Rc::new(Dielectric::new(1.5));
-    world.add(Rc::new(Sphere::new(
-        Point3::new(0.0, 1.0, 0.0),
-        1.0,
-        material1,
-    )));
-
-    let material2 = Rc::new(LambertianMaterial::new(Color::new(0.4, 0.2, 0.1)));
-    world.add(Rc::new(Sphere::new(
-        Point3::new(-4.0, 1.0, 0.0),
-        1.0,
-        material2,
-    )));
-
-    let material3 = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Rc::new(Sphere::new(
-        Point3::new(4.0, 1.0, 0.0),
-        1.0,
-        material3,
-    )));
-
-    const SAMPLES_PER_PIXEL: usize = 500;
-    const VFOV: f64 = 20.0;
-    const ASPECT_RATIO: f64 = 16.0 / 9.0;
-    const IMAGE_WIDTH: usize = 1200;
-
-    let look_from = Point3::new(13.0, 2.0, 3.0);
-    let look_at = Point3::new(0.0, 0.0, 0.0);
-    let view_up = Vec3::new(0.0, 1.0, 0.0);
-
-    let camera = Camera::new(
-        SAMPLES_PER_PIXEL,
-        VFOV,
-        ASPECT_RATIO,
-        IMAGE_WIDTH,
-        look_from,
-        look_at,
-        view_up,
+    let Some((first, last)) = setup.frames else {
+        return setup
+            .camera
+            .at(0.0)?
+            .render(&setup.output, world, background, &lights);
+    };
+    // Each frame goes to its own file, numbered after the output's name.
+    for frame in first..=last {
+        println!("frame {}:", frame);
+        setup.camera.at(frame as f64 / setup.fps)?.render(
+            &Image::suffixed_path(&setup.output, &format!("{:04}", frame)),
+            world,
+            background,
+            &lights,
+        )?;
+    }
+    Ok(())
+}
+
+fn info(setup: &Setup) -> Result<(), Error> {
+    let camera = setup.camera.at(0.0)?;
+    let (width, height) = camera.image_size();
+    let bounds = setup.world.bounding_box();
+    let background = match &setup.background {
+        Background::Gradient => "gradient",
+        Background::Uniform(_) => "uniform colour",
+        Background::Map(_) => "environment map",
+        Background::Sky(_) => "physical sky",
+    };
+    println!("scene: {}", setup.scene_name);
+    println!(
+        "image: {}x{} pixels, {} samples per pixel",
+        width,
+        height,
+        camera.samples_per_pixel()
     );
-    camera.render(out_filename, &world)
+    println!(
+        "objects: {}, made of {} primitives",
+        setup.world.len(),
+        setup.world.primitives()
+    );
+    println!("bounds: {} to {}", bounds.min(), bounds.max());
+    println!(
+        "lights: {}, of which {} emitting shapes",
+        setup.lights.len(),
+        setup.emitting
+    );
+    let mut materials: Vec<usize> = Vec::new();
+    setup.world.materials(&mut |material| {
+        let key = Arc::as_ptr(material) as *const () as usize;
+        if !materials.contains(&key) {
+            materials.push(key);
+        }
+    });
+    println!("materials: {}", materials.len());
+    println!("background: {}", background);
+    Ok(())
+}
+
+fn validate(setup: &Setup) -> Result<(), Error> {
+    // Every frame's camera, which fails where a lens can't focus on its view.
+    let (first, last) = setup.frames.unwrap_or((0, 0));
+    for frame in first..=last {
+        setup.camera.at(frame as f64 / setup.fps)?;
+    }
+    println!("{}: ok", setup.scene_name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_regions() {
+        let args = args(&["--region=10, 20,30,40", "--crop-window=0,0.5,1,1"]);
+        let pixels: Option<[usize; 4]> = parse_list(&args, "--region").unwrap();
+        assert_eq!(pixels, Some([10, 20, 30, 40]));
+        let normalized: Option<[f64; 4]> = parse_list(&args, "--crop-window").unwrap();
+        assert_eq!(normalized, Some([0.0, 0.5, 1.0, 1.0]));
+        let missing: Option<[usize; 4]> = parse_list(&args, "--debug-pixel").unwrap();
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn rejects_malformed_regions() {
+        for value in ["1,2,3", "1,2,3,4,5", "1,2,3,x", "-1,2,3,4", "1,2,,4", ""] {
+            let args = args(&[&format!("--region={}", value)]);
+            let e = parse_list::<usize, 4>(&args, "--region").unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidInput);
+            assert_eq!(
+                e.to_string(),
+                format!(
+                    "--region: expected 4 numbers separated by commas, got {}",
+                    value
+                )
+            );
+        }
+    }
 }
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use crate::{
+    physics::{
+        fraction_parameter, Color, Material, ParameterError, Ray, SampledSpectrum,
+        SampledWavelengths, Vec3,
+    },
+    shapes::HitRecord,
+    textures::{solid, Texture},
+};
+
+// Wraps any material with an opacity texture, read from the red channel. With a cutoff the
+// surface is either fully there or fully cut away; without one, fractional values let rays
+// through stochastically, giving soft edges and translucent cards.
+#[derive(Debug)]
+pub(crate) struct AlphaMasked {
+    material: Arc<dyn Material>,
+    opacity: Arc<dyn Texture>,
+    cutoff: Option<f64>,
+}
+
+impl AlphaMasked {
+    pub(crate) fn new(material: Arc<dyn Material>, opacity: Arc<dyn Texture>) -> Self {
+        AlphaMasked {
+            material,
+            opacity,
+            cutoff: None,
+        }
+    }
+
+    pub(crate) fn with_cutoff(mut self, cutoff: f64) -> Self {
+        self.cutoff = Some(cutoff);
+        self
+    }
+}
+
+impl Material for AlphaMasked {
+    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        self.material.scatter(r_in, hit_record)
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        self.material.emitted(hit_record)
+    }
+
+    fn eval(&self, r_in: &Ray, hit_record: &HitRecord, wi: &Vec3) -> Option<(Color, f64)> {
+        self.material.eval(r_in, hit_record, wi)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.material.albedo(hit_record)
+    }
+
+    fn diffuse_fraction(&self, r_in: &Ray, hit_record: &HitRecord, wi: &Vec3) -> f64 {
+        self.material.diffuse_fraction(r_in, hit_record, wi)
+    }
+
+    fn opacity(&self, hit_record: &HitRecord) -> f64 {
+        let alpha = self
+            .opacity
+            .value(hit_record.u, hit_record.v, &hit_record.p)
+            .r()
+            * self.material.opacity(hit_record);
+        match self.cutoff {
+            Some(cutoff) if alpha >= cutoff => 1.0,
+            Some(_) => 0.0,
+            None => alpha,
+        }
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        self.material.scatter_spectral(r_in, hit_record, lambda)
+    }
+
+    fn emitted_spectral(
+        &self,
+        hit_record: &HitRecord,
+        lambda: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        self.material.emitted_spectral(hit_record, lambda)
+    }
+
+    // Sets the opacity to a constant, or a parameter of the wrapped material.
+    fn with_parameter(
+        &self,
+        name: &str,
+        value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        let (material, opacity) = match name {
+            "opacity" => (self.material.clone(), solid(fraction_parameter(value)?)),
+            _ => match self.material.with_parameter(name, value) {
+                Ok(material) => (material, self.opacity.clone()),
+                Err(ParameterError::Unknown(mut names)) => {
+                    names.push("opacity");
+                    return Err(ParameterError::Unknown(names));
+                }
+                Err(e) => return Err(e),
+            },
+        };
+        Ok(Arc::new(AlphaMasked {
+            material,
+            opacity,
+            cutoff: self.cutoff,
+        }))
+    }
+}
@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use crate::{
+    physics::{
+        Color, Material, Onb, ParameterError, Ray, SampledSpectrum, SampledWavelengths, Vec3,
+    },
+    shapes::HitRecord,
+    textures::Texture,
+};
+
+// Step in texture space used to difference height maps.
+const HEIGHT_DELTA: f64 = 0.0005;
+
+#[derive(Clone, Debug)]
+enum Perturbation {
+    // Tangent-space normal map encoded in [0, 1]; strength scales the tangential components.
+    Normal {
+        texture: Arc<dyn Texture>,
+        strength: f64,
+    },
+    // Grayscale height map; scale converts texture values to world space displacement.
+    Height {
+        texture: Arc<dyn Texture>,
+        scale: f64,
+    },
+}
+
+// Wraps any material and perturbs the shading normal before handing the hit on, so surfaces get
+// fine detail without extra geometry.
+#[derive(Debug)]
+pub(crate) struct BumpMapped {
+    material: Arc<dyn Material>,
+    perturbation: Perturbation,
+}
+
+impl BumpMapped {
+    pub(crate) fn normal_map(
+        material: Arc<dyn Material>,
+        texture: Arc<dyn Texture>,
+        strength: f64,
+    ) -> Self {
+        BumpMapped {
+            material,
+            perturbation: Perturbation::Normal { texture, strength },
+        }
+    }
+
+    pub(crate) fn height_map(
+        material: Arc<dyn Material>,
+        texture: Arc<dyn Texture>,
+        scale: f64,
+    ) -> Self {
+        BumpMapped {
+            material,
+            perturbation: Perturbation::Height { texture, scale },
+        }
+    }
+
+    fn perturb(&self, hit_record: &HitRecord) -> HitRecord {
+        let mut rec = hit_record.clone();
+        let (u, v, p) = (rec.u, rec.v, &rec.p);
+        let outward = if rec.front_face {
+            rec.normal.clone()
+        } else {
+            -&rec.normal
+        };
+
+        let normal = match &self.perturbation {
+            Perturbation::Normal { texture, strength } => {
+                let c = texture.value(u, v, p);
+                let frame = Onb::from_tangent(&outward, &rec.dpdu);
+                // Texture coordinates may be mirrored, in which case dpdv points against the
+                // frame's bitangent and the green channel must be flipped.
+                let handedness = if frame.local(0.0, 1.0, 0.0).dot(&rec.dpdv) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                frame.local(
+                    strength * (2.0 * c.r() - 1.0),
+                    handedness * strength * (2.0 * c.g() - 1.0),
+                    (2.0 * c.b() - 1.0).max(0.0),
+                )
+            }
+            Perturbation::Height { texture, scale } => {
+                // Differentiate the displaced surface p + h(u, v) n, neglecting the change of n.
+                let height = |u: f64, v: f64| scale * texture.value(u, v, p).r();
+                let h = height(u, v);
+                let dhdu = (height(u + HEIGHT_DELTA, v) - h) / HEIGHT_DELTA;
+                let dhdv = (height(u, v + HEIGHT_DELTA) - h) / HEIGHT_DELTA;
+
+                let dpdu = &rec.dpdu + dhdu * &outward;
+                let dpdv = &rec.dpdv + dhdv * &outward;
+                let n = dpdu.cross(&dpdv);
+                if n.dot(&outward) < 0.0 {
+                    -n
+                } else {
+                    n
+                }
+            }
+        };
+
+        if normal.length_squared() > 1e-16 {
+            let normal = normal.unit();
+            rec.normal = if rec.front_face { normal } else { -normal };
+        }
+        rec
+    }
+}
+
+impl Material for BumpMapped {
+    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        self.material.scatter(r_in, &self.perturb(hit_record))
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        self.material.emitted(hit_record)
+    }
+
+    fn eval(&self, r_in: &Ray, hit_record: &HitRecord, wi: &Vec3) -> Option<(Color, f64)> {
+        self.material.eval(r_in, &self.perturb(hit_record), wi)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.material.albedo(hit_record)
+    }
+
+    fn diffuse_fraction(&self, r_in: &Ray, hit_record: &HitRecord, wi: &Vec3) -> f64 {
+        self.material
+            .diffuse_fraction(r_in, &self.perturb(hit_record), wi)
+    }
+
+    fn opacity(&self, hit_record: &HitRecord) -> f64 {
+        self.material.opacity(hit_record)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        self.material
+            .scatter_spectral(r_in, &self.perturb(hit_record), lambda)
+    }
+
+    fn emitted_spectral(
+        &self,
+        hit_record: &HitRecord,
+        lambda: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        self.material.emitted_spectral(hit_record, lambda)
+    }
+
+    fn with_parameter(
+        &self,
+        name: &str,
+        value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        Ok(Arc::new(BumpMapped {
+            material: self.material.with_parameter(name, value)?,
+            perturbation: self.perturbation.clone(),
+        }))
+    }
+}
@@ -12,6 +12,29 @@ impl Color {
     pub(crate) fn new(e0: f64, e1: f64, e2: f64) -> Self {
         Color(Vec3::new(e0, e1, e2))
     }
+
+    pub(crate) fn r(&self) -> f64 {
+        self.0.x()
+    }
+
+    pub(crate) fn g(&self) -> f64 {
+        self.0.y()
+    }
+
+    pub(crate) fn b(&self) -> f64 {
+        self.0.z()
+    }
+
+    // Relative luminance of linear sRGB.
+    pub(crate) fn luminance(&self) -> f64 {
+        0.2126 * self.r() + 0.7152 * self.g() + 0.0722 * self.b()
+    }
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
 }
 
 impl From<Vec3> for Color {
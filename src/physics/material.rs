@@ -1,11 +1,128 @@
+use std::{f64::consts::PI, sync::Arc};
+
 use crate::{
-    physics::{Color, Ray, Vec3},
+    physics::{
+        Color, Onb, Ray, SampledSpectrum, SampledWavelengths, Spectrum, TrowbridgeReitz, Vec3,
+    },
     shapes::HitRecord,
     utils::random_f64,
 };
 
-pub(crate) trait Material: std::fmt::Debug {
+// Representative wavelengths of the red, green and blue channels, in nanometres.
+const CHANNEL_WAVELENGTHS: [f64; 3] = [610.0, 550.0, 465.0];
+
+pub(crate) trait Material: std::fmt::Debug + Send + Sync {
     fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)>;
+
+    // Name of the material's type without its path or type parameters, for logging.
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        let name = name.split('<').next().unwrap_or(name);
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    fn emitted(&self, _hit_record: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // Value of the scattering function, cosine term included, for light arriving from the unit
+    // direction `wi`, together with the density with which `scatter` picks that direction.
+    // Materials that scatter into a few directions only, or whose density is unknown, return None
+    // and are lit through their scattered rays alone.
+    fn eval(&self, _r_in: &Ray, _hit_record: &HitRecord, _wi: &Vec3) -> Option<(Color, f64)> {
+        None
+    }
+
+    // Probability that a ray stops at this point rather than passing straight through it.
+    fn opacity(&self, _hit_record: &HitRecord) -> f64 {
+        1.0
+    }
+
+    // Colour of the surface as denoisers and compositors take it: the reflectance of a diffuse
+    // or metallic surface, and white for clear dielectrics and anything else without a colour of
+    // its own.
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    // Share of the light arriving from the unit direction `wi` that the material scatters
+    // diffusely rather than specularly, which separates the diffuse and specular render passes.
+    fn diffuse_fraction(&self, _r_in: &Ray, _hit_record: &HitRecord, _wi: &Vec3) -> f64 {
+        0.0
+    }
+
+    // Spectral counterpart of `scatter`. Uplifting the RGB attenuation is right for any material
+    // whose response does not depend on wavelength; the others override this.
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        let (scattered, attenuation) = self.scatter(r_in, hit_record)?;
+        Some((
+            scattered,
+            SampledSpectrum::from_albedo(&attenuation, lambda),
+        ))
+    }
+
+    fn emitted_spectral(
+        &self,
+        hit_record: &HitRecord,
+        lambda: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        SampledSpectrum::from_illuminant(&self.emitted(hit_record), lambda)
+    }
+
+    // Copy of the material with the parameter `name` set to `value`, which holds one number or
+    // the three channels of a colour, for overriding the materials of a scene.
+    fn with_parameter(
+        &self,
+        _name: &str,
+        _value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        Err(ParameterError::Unknown(Vec::new()))
+    }
+}
+
+// Why `Material::with_parameter` refused to set a parameter.
+#[derive(Debug)]
+pub(crate) enum ParameterError {
+    // The material has no parameter of that name, only these.
+    Unknown(Vec<&'static str>),
+    // The parameter takes values of another kind, described here.
+    Invalid(String),
+}
+
+// A colour parameter, given as one grey level or three channels.
+pub(crate) fn color_parameter(value: &[f64]) -> Result<Color, ParameterError> {
+    match *value {
+        [v] if v >= 0.0 => Ok(Color::new(v, v, v)),
+        [r, g, b] if r >= 0.0 && g >= 0.0 && b >= 0.0 => Ok(Color::new(r, g, b)),
+        _ => Err(ParameterError::Invalid(
+            "a level or <r>,<g>,<b> of at least 0".to_string(),
+        )),
+    }
+}
+
+// A number parameter, of which `expected` describes the values `valid` accepts.
+pub(crate) fn number_parameter(
+    value: &[f64],
+    valid: impl Fn(f64) -> bool,
+    expected: &str,
+) -> Result<f64, ParameterError> {
+    match *value {
+        [v] if valid(v) => Ok(v),
+        _ => Err(ParameterError::Invalid(format!("a number {}", expected))),
+    }
+}
+
+pub(crate) fn fraction_parameter(value: &[f64]) -> Result<f64, ParameterError> {
+    number_parameter(value, |v| (0.0..=1.0).contains(&v), "from 0 to 1")
+}
+
+pub(crate) fn ior_parameter(value: &[f64]) -> Result<f64, ParameterError> {
+    number_parameter(value, |v| v > 0.0, "above 0")
 }
 
 #[derive(Debug)]
@@ -20,54 +137,470 @@ impl LambertianMaterial {
 }
 
 impl Material for LambertianMaterial {
-    fn scatter(&self, _r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
         let mut scatter_direction = &hit_record.normal + Vec3::new_random_unit();
         if scatter_direction.near_zero() {
             scatter_direction = hit_record.normal.clone();
         }
 
-        let scattered = Ray::new(&hit_record.p, &scatter_direction);
+        let scattered = Ray::new(&hit_record.p, &scatter_direction, r_in.time());
         Some((scattered, self.albedo.clone()))
     }
+
+    fn eval(&self, _r_in: &Ray, hit_record: &HitRecord, wi: &Vec3) -> Option<(Color, f64)> {
+        let cosine = wi.dot(&hit_record.normal).max(0.0);
+        Some(((cosine / PI) * self.albedo.clone(), cosine / PI))
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        self.albedo.clone()
+    }
+
+    fn diffuse_fraction(&self, _r_in: &Ray, _hit_record: &HitRecord, _wi: &Vec3) -> f64 {
+        1.0
+    }
+
+    fn with_parameter(
+        &self,
+        name: &str,
+        value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        match name {
+            "color" => Ok(Arc::new(LambertianMaterial::new(color_parameter(value)?))),
+            _ => Err(ParameterError::Unknown(vec!["color"])),
+        }
+    }
 }
 
-#[derive(Debug)]
+// Complex index of refraction η + iκ of a conductor.
+#[derive(Clone, Debug)]
+pub(crate) struct ComplexIor {
+    eta: Spectrum,
+    k: Spectrum,
+}
+
+impl ComplexIor {
+    pub(crate) fn new(eta: Spectrum, k: Spectrum) -> Self {
+        ComplexIor { eta, k }
+    }
+
+    // Tabulated values below are approximate, read off published room temperature measurements.
+
+    pub(crate) fn gold() -> Self {
+        Self::tabulated(&[
+            (400.0, 1.66, 1.96),
+            (450.0, 1.38, 1.87),
+            (500.0, 0.97, 1.87),
+            (550.0, 0.43, 2.45),
+            (600.0, 0.25, 2.98),
+            (650.0, 0.17, 3.45),
+            (700.0, 0.16, 3.95),
+        ])
+    }
+
+    pub(crate) fn copper() -> Self {
+        Self::tabulated(&[
+            (400.0, 1.18, 2.21),
+            (450.0, 1.17, 2.40),
+            (500.0, 1.13, 2.56),
+            (550.0, 1.02, 2.58),
+            (600.0, 0.27, 3.41),
+            (650.0, 0.21, 3.67),
+            (700.0, 0.21, 4.05),
+        ])
+    }
+
+    pub(crate) fn silver() -> Self {
+        Self::tabulated(&[
+            (400.0, 0.17, 1.95),
+            (450.0, 0.14, 2.65),
+            (500.0, 0.13, 3.09),
+            (550.0, 0.12, 3.45),
+            (600.0, 0.12, 3.80),
+            (650.0, 0.14, 4.15),
+            (700.0, 0.14, 4.52),
+        ])
+    }
+
+    pub(crate) fn aluminium() -> Self {
+        Self::tabulated(&[
+            (400.0, 0.49, 4.86),
+            (450.0, 0.62, 5.47),
+            (500.0, 0.77, 6.08),
+            (550.0, 0.96, 6.69),
+            (600.0, 1.20, 7.26),
+            (650.0, 1.47, 7.79),
+            (700.0, 1.83, 8.31),
+        ])
+    }
+
+    fn tabulated(samples: &[(f64, f64, f64)]) -> Self {
+        Self::new(
+            Spectrum::PiecewiseLinear(samples.iter().map(|&(l, n, _)| (l, n)).collect()),
+            Spectrum::PiecewiseLinear(samples.iter().map(|&(l, _, k)| (l, k)).collect()),
+        )
+    }
+
+    // Unpolarized Fresnel reflectance of the conductor at the given wavelength.
+    pub(crate) fn reflectance(&self, cos_theta: f64, lambda: f64) -> f64 {
+        fresnel_conductor(cos_theta, self.eta.eval(lambda), self.k.eval(lambda))
+    }
+}
+
+// Exact Fresnel reflectance for an interface between a dielectric with unit index and a
+// conductor with complex index n + ik.
+pub(crate) fn fresnel_conductor(cos_theta: f64, n: f64, k: f64) -> f64 {
+    let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let n2 = n * n;
+    let k2 = k * k;
+
+    let t0 = n2 - k2 - sin2;
+    let a2b2 = (t0 * t0 + 4.0 * n2 * k2).sqrt();
+    let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+    let cos_theta = cos2.sqrt();
+
+    let t1 = a2b2 + cos2;
+    let t2 = 2.0 * a * cos_theta;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+#[derive(Clone, Debug)]
 pub(crate) struct Metal {
     albedo: Color,
     fuzz: f64,
+    ior: Option<ComplexIor>,
 }
 
 impl Metal {
     pub(crate) fn new(albedo: Color, f: f64) -> Self {
         let fuzz = if f < 1.0 { f } else { 1.0 };
-        Metal { albedo, fuzz }
+        Metal {
+            albedo,
+            fuzz,
+            ior: None,
+        }
     }
-}
 
-impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+    // Metal whose colour comes from the Fresnel reflectance of its measured complex index.
+    pub(crate) fn measured(ior: ComplexIor, f: f64) -> Self {
+        Metal {
+            ior: Some(ior),
+            ..Self::new(Color::new(1.0, 1.0, 1.0), f)
+        }
+    }
+
+    fn reflect(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<Ray> {
         let reflected = r_in.direction().unit().reflect(&hit_record.normal);
         let scattered = Ray::new(
             &hit_record.p,
             &(reflected + self.fuzz * &Vec3::new_random_unit()),
+            r_in.time(),
         );
 
         if scattered.direction().dot(&hit_record.normal) > 0.0 {
-            Some((scattered, self.albedo.clone()))
+            Some(scattered)
         } else {
             None
         }
     }
 }
 
+impl Material for Metal {
+    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        let scattered = self.reflect(r_in, hit_record)?;
+        let Some(ior) = &self.ior else {
+            return Some((scattered, self.albedo.clone()));
+        };
+
+        let cos_theta = -r_in.direction().unit().dot(&hit_record.normal);
+        let [r, g, b] = CHANNEL_WAVELENGTHS.map(|l| ior.reflectance(cos_theta, l));
+        Some((scattered, self.albedo.clone() * Color::new(r, g, b)))
+    }
+
+    // Reflectance at normal incidence for measured metals.
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        let Some(ior) = &self.ior else {
+            return self.albedo.clone();
+        };
+        let [r, g, b] = CHANNEL_WAVELENGTHS.map(|l| ior.reflectance(1.0, l));
+        self.albedo.clone() * Color::new(r, g, b)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        let scattered = self.reflect(r_in, hit_record)?;
+        let albedo = SampledSpectrum::from_albedo(&self.albedo, lambda);
+        let Some(ior) = &self.ior else {
+            return Some((scattered, albedo));
+        };
+
+        let cos_theta = -r_in.direction().unit().dot(&hit_record.normal);
+        let fresnel = SampledSpectrum::from_fn(|i| ior.reflectance(cos_theta, lambda.get(i)));
+        Some((scattered, albedo * fresnel))
+    }
+
+    fn with_parameter(
+        &self,
+        name: &str,
+        value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        let mut metal = self.clone();
+        match name {
+            "color" => metal.albedo = color_parameter(value)?,
+            "fuzz" => metal.fuzz = fraction_parameter(value)?,
+            _ => return Err(ParameterError::Unknown(vec!["color", "fuzz"])),
+        }
+        Ok(Arc::new(metal))
+    }
+}
+
+// Rough conductor whose microsurface follows the GGX distribution. Unlike the fuzz of `Metal`
+// this conserves energy apart from what is lost to masking, and is driven by the measured
+// complex index of refraction.
 #[derive(Debug)]
+pub(crate) struct GgxConductor {
+    ior: ComplexIor,
+    distribution: TrowbridgeReitz,
+}
+
+impl GgxConductor {
+    pub(crate) fn new(ior: ComplexIor, roughness: f64) -> Self {
+        Self::anisotropic(ior, roughness, roughness)
+    }
+
+    // Roughness may differ along the two tangent directions, as on brushed metal.
+    pub(crate) fn anisotropic(ior: ComplexIor, roughness_u: f64, roughness_v: f64) -> Self {
+        GgxConductor {
+            ior,
+            distribution: TrowbridgeReitz::from_roughness(roughness_u, roughness_v),
+        }
+    }
+
+    // Samples a reflected direction, returning it with the cosine needed for the Fresnel term
+    // and the masking-shadowing weight.
+    fn sample(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, f64, f64)> {
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let (wi, cos_theta, weight) = self.distribution.sample_reflection(&wo)?;
+
+        let scattered = Ray::new(
+            &hit_record.p,
+            &frame.local(wi.x(), wi.y(), wi.z()),
+            r_in.time(),
+        );
+        Some((scattered, cos_theta, weight))
+    }
+}
+
+impl Material for GgxConductor {
+    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        let (scattered, cos_theta, weight) = self.sample(r_in, hit_record)?;
+        let [r, g, b] = CHANNEL_WAVELENGTHS.map(|l| weight * self.ior.reflectance(cos_theta, l));
+        Some((scattered, Color::new(r, g, b)))
+    }
+
+    // Reflectance at normal incidence.
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        let [r, g, b] = CHANNEL_WAVELENGTHS.map(|l| self.ior.reflectance(1.0, l));
+        Color::new(r, g, b)
+    }
+
+    fn eval(&self, r_in: &Ray, hit_record: &HitRecord, wi: &Vec3) -> Option<(Color, f64)> {
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let Some((value, cos_theta, pdf)) =
+            self.distribution.eval_reflection(&wo, &frame.to_local(wi))
+        else {
+            return Some((Color::new(0.0, 0.0, 0.0), 0.0));
+        };
+
+        let [r, g, b] = CHANNEL_WAVELENGTHS.map(|l| value * self.ior.reflectance(cos_theta, l));
+        Some((Color::new(r, g, b), pdf))
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        let (scattered, cos_theta, weight) = self.sample(r_in, hit_record)?;
+        let attenuation =
+            SampledSpectrum::from_fn(|i| weight * self.ior.reflectance(cos_theta, lambda.get(i)));
+        Some((scattered, attenuation))
+    }
+
+    fn with_parameter(
+        &self,
+        name: &str,
+        value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        match name {
+            "roughness" => Ok(Arc::new(GgxConductor::new(
+                self.ior.clone(),
+                fraction_parameter(value)?,
+            ))),
+            _ => Err(ParameterError::Unknown(vec!["roughness"])),
+        }
+    }
+}
+
+// Rough glass-like interface with a GGX microsurface, reflecting or refracting through each
+// sampled microfacet according to its exact Fresnel reflectance.
+#[derive(Clone, Debug)]
+pub(crate) struct GgxDielectric {
+    ir: f64,
+    distribution: TrowbridgeReitz,
+}
+
+impl GgxDielectric {
+    pub(crate) fn new(ir: f64, roughness: f64) -> Self {
+        Self::anisotropic(ir, roughness, roughness)
+    }
+
+    pub(crate) fn anisotropic(ir: f64, roughness_u: f64, roughness_v: f64) -> Self {
+        GgxDielectric {
+            ir,
+            distribution: TrowbridgeReitz::from_roughness(roughness_u, roughness_v),
+        }
+    }
+}
+
+impl Material for GgxDielectric {
+    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(&-r_in.direction().unit());
+
+        let eta = if hit_record.front_face {
+            self.ir
+        } else {
+            1.0 / self.ir
+        };
+        let (wi, weight) = self.distribution.sample_dielectric(&wo, eta)?;
+
+        let scattered = Ray::new(
+            &hit_record.p,
+            &frame.local(wi.x(), wi.y(), wi.z()),
+            r_in.time(),
+        );
+        Some((scattered, Color::new(weight, weight, weight)))
+    }
+
+    fn with_parameter(
+        &self,
+        name: &str,
+        value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        let mut dielectric = self.clone();
+        match name {
+            "ior" => dielectric.ir = ior_parameter(value)?,
+            "roughness" => {
+                let roughness = fraction_parameter(value)?;
+                dielectric.distribution = TrowbridgeReitz::from_roughness(roughness, roughness);
+            }
+            _ => return Err(ParameterError::Unknown(vec!["ior", "roughness"])),
+        }
+        Ok(Arc::new(dielectric))
+    }
+}
+
+// Wavelength-dependent index of refraction, with wavelengths in micrometres as is customary for
+// published glass coefficients.
+#[derive(Clone, Debug)]
+pub(crate) enum Dispersion {
+    // n(λ) = a + b / λ²
+    Cauchy { a: f64, b: f64 },
+    // n²(λ) = 1 + Σ bᵢλ² / (λ² - cᵢ)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+    // Measured index over wavelength in nanometres.
+    Tabulated(Spectrum),
+}
+
+impl Dispersion {
+    pub(crate) fn bk7() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    pub(crate) fn dense_flint() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.73759695, 0.313747346, 1.89878101],
+            c: [0.013188707, 0.0623068142, 155.23629],
+        }
+    }
+
+    pub(crate) fn diamond() -> Self {
+        Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030625, 0.011236, 0.0],
+        }
+    }
+
+    pub(crate) fn water() -> Self {
+        Dispersion::Tabulated(Spectrum::PiecewiseLinear(vec![
+            (404.7, 1.3428),
+            (486.1, 1.3371),
+            (589.3, 1.3330),
+            (656.3, 1.3311),
+            (706.5, 1.3300),
+        ]))
+    }
+
+    pub(crate) fn ior(&self, wavelength_nm: f64) -> f64 {
+        let l = wavelength_nm / 1000.0;
+        let l2 = l * l;
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => (1.0
+                + b.iter()
+                    .zip(c.iter())
+                    .map(|(b, c)| b * l2 / (l2 - c))
+                    .sum::<f64>())
+            .sqrt(),
+            Dispersion::Tabulated(ior) => ior.eval(wavelength_nm),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub(crate) struct Dielectric {
     ir: f64,
+    // Interior absorption coefficients per unit distance (Beer-Lambert).
+    absorption: Color,
+    dispersion: Option<Dispersion>,
 }
 
 impl Dielectric {
     pub(crate) fn new(ir: f64) -> Self {
-        Dielectric { ir }
+        Dielectric {
+            ir,
+            absorption: Color::new(0.0, 0.0, 0.0),
+            dispersion: None,
+        }
+    }
+
+    pub(crate) fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    // When dispersive, `ir` is ignored in favour of the index at the traced wavelength.
+    pub(crate) fn with_dispersion(mut self, dispersion: Dispersion) -> Self {
+        self.dispersion = Some(dispersion);
+        self
     }
 
     fn reflectance(cosine: f64, ref_idx: f64) -> bool {
@@ -77,16 +610,19 @@ impl Dielectric {
         let refl = r0 + (1.0 - r0) * (1.0 - cosine).powi(5);
         refl > random_f64()
     }
-}
 
-impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
-        let attenuation = Color::new(1.0, 1.0, 1.0);
-        let refraction_ratio = if hit_record.front_face {
-            1.0 / self.ir
+    // Light reaching a back face has travelled through the interior, so it is attenuated by the
+    // distance covered since entering.
+    fn interior_distance(r_in: &Ray, hit_record: &HitRecord) -> f64 {
+        if hit_record.front_face {
+            0.0
         } else {
-            self.ir
-        };
+            hit_record.t * r_in.direction().length()
+        }
+    }
+
+    fn scatter_with_ior(r_in: &Ray, hit_record: &HitRecord, ir: f64) -> Ray {
+        let refraction_ratio = if hit_record.front_face { 1.0 / ir } else { ir };
 
         let unit_direction = r_in.direction().unit();
         let cos_theta = (-unit_direction.dot(&hit_record.normal)).min(1.0);
@@ -99,7 +635,259 @@ impl Material for Dielectric {
             unit_direction.refract(&hit_record.normal, refraction_ratio)
         };
 
-        let scattered = Ray::new(&hit_record.p, &direction);
+        Ray::new(&hit_record.p, &direction, r_in.time())
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        let distance = Self::interior_distance(r_in, hit_record);
+        let mut attenuation = Color::new(
+            (-self.absorption.r() * distance).exp(),
+            (-self.absorption.g() * distance).exp(),
+            (-self.absorption.b() * distance).exp(),
+        );
+
+        // A dispersive interface bends each channel differently, so the path follows a single
+        // channel from the first such interface on. It is chosen at random there and weighted by
+        // the inverse selection probability, once for the whole path.
+        let (ir, channel) = match (&self.dispersion, r_in.channel()) {
+            (Some(dispersion), Some(channel)) => {
+                (dispersion.ior(CHANNEL_WAVELENGTHS[channel]), Some(channel))
+            }
+            (Some(dispersion), None) => {
+                let channel = ((random_f64() * 3.0) as usize).min(2);
+                let mut weight = [0.0; 3];
+                weight[channel] = 3.0;
+                attenuation = attenuation * Color::new(weight[0], weight[1], weight[2]);
+                (dispersion.ior(CHANNEL_WAVELENGTHS[channel]), Some(channel))
+            }
+            (None, channel) => (self.ir, channel),
+        };
+
+        let scattered = Self::scatter_with_ior(r_in, hit_record, ir).with_channel(channel);
+        Some((scattered, attenuation))
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        let distance = Self::interior_distance(r_in, hit_record);
+        let attenuation = SampledSpectrum::from_albedo(&self.absorption, lambda)
+            .map(|absorption| (-absorption * distance).exp());
+
+        // Only the hero wavelength can follow the refracted direction.
+        let ir = match &self.dispersion {
+            Some(dispersion) => {
+                lambda.terminate_secondary();
+                dispersion.ior(lambda.hero())
+            }
+            None => self.ir,
+        };
+
+        let scattered = Self::scatter_with_ior(r_in, hit_record, ir);
         Some((scattered, attenuation))
     }
+
+    // Setting the index makes a dispersive dielectric plain.
+    fn with_parameter(
+        &self,
+        name: &str,
+        value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        let mut dielectric = self.clone();
+        match name {
+            "ior" => {
+                dielectric.ir = ior_parameter(value)?;
+                dielectric.dispersion = None;
+            }
+            "absorption" => dielectric.absorption = color_parameter(value)?,
+            _ => return Err(ParameterError::Unknown(vec!["ior", "absorption"])),
+        }
+        Ok(Arc::new(dielectric))
+    }
+}
+
+// Phase function of a participating medium that scatters uniformly in all directions.
+#[derive(Debug)]
+pub(crate) struct Isotropic {
+    albedo: Color,
+}
+
+impl Isotropic {
+    pub(crate) fn new(albedo: Color) -> Self {
+        Isotropic { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        let scattered = Ray::new(&hit_record.p, &Vec3::new_random_unit(), r_in.time());
+        Some((scattered, self.albedo.clone()))
+    }
+
+    fn eval(&self, _r_in: &Ray, _hit_record: &HitRecord, _wi: &Vec3) -> Option<(Color, f64)> {
+        let phase = 1.0 / (4.0 * PI);
+        Some((phase * self.albedo.clone(), phase))
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        self.albedo.clone()
+    }
+
+    // Scattering inside a medium counts as diffuse.
+    fn diffuse_fraction(&self, _r_in: &Ray, _hit_record: &HitRecord, _wi: &Vec3) -> f64 {
+        1.0
+    }
+
+    fn with_parameter(
+        &self,
+        name: &str,
+        value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        match name {
+            "color" => Ok(Arc::new(Isotropic::new(color_parameter(value)?))),
+            _ => Err(ParameterError::Unknown(vec!["color"])),
+        }
+    }
+}
+
+// Henyey-Greenstein phase function. Positive `g` favours forward scattering,
+// which is what makes clouds glow when lit from behind.
+#[derive(Debug)]
+pub(crate) struct HenyeyGreenstein {
+    albedo: Color,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub(crate) fn new(albedo: Color, g: f64) -> Self {
+        let g = g.clamp(-0.99, 0.99);
+        HenyeyGreenstein { albedo, g }
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        let g = self.g;
+        let xi = random_f64();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let sqr_term = (1.0 - g * g) / (1.0 + g - 2.0 * g * xi);
+            (1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_f64();
+
+        let basis = Onb::new(&r_in.direction());
+        let direction = basis.local(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+
+        let scattered = Ray::new(&hit_record.p, &direction, r_in.time());
+        Some((scattered, self.albedo.clone()))
+    }
+
+    fn eval(&self, r_in: &Ray, _hit_record: &HitRecord, wi: &Vec3) -> Option<(Color, f64)> {
+        let g = self.g;
+        let cos_theta = r_in.direction().unit().dot(wi);
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        let phase = (1.0 - g * g) / (4.0 * PI * denom * denom.max(1e-12).sqrt());
+        Some((phase * self.albedo.clone(), phase))
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        self.albedo.clone()
+    }
+
+    // Scattering inside a medium counts as diffuse, however strongly it favours the forward
+    // direction.
+    fn diffuse_fraction(&self, _r_in: &Ray, _hit_record: &HitRecord, _wi: &Vec3) -> f64 {
+        1.0
+    }
+
+    fn with_parameter(
+        &self,
+        name: &str,
+        value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        match name {
+            "color" => Ok(Arc::new(HenyeyGreenstein::new(
+                color_parameter(value)?,
+                self.g,
+            ))),
+            "g" => Ok(Arc::new(HenyeyGreenstein::new(
+                self.albedo.clone(),
+                number_parameter(value, |v| v > -1.0 && v < 1.0, "between -1 and 1")?,
+            ))),
+            _ => Err(ParameterError::Unknown(vec!["color", "g"])),
+        }
+    }
+}
+
+// Emitter that radiates equally in all directions from the front of its surface.
+#[derive(Debug)]
+pub(crate) struct DiffuseLight {
+    emit: Color,
+    spectrum: Option<Spectrum>,
+}
+
+impl DiffuseLight {
+    pub(crate) fn new(emit: Color) -> Self {
+        DiffuseLight {
+            emit,
+            spectrum: None,
+        }
+    }
+
+    // Emitter with the spectrum of a blackbody at the given temperature in kelvin, scaled to the
+    // given luminance.
+    pub(crate) fn blackbody(temperature: f64, luminance: f64) -> Self {
+        let spectrum = Spectrum::Blackbody(temperature);
+        let scale = luminance / spectrum.luminance();
+        DiffuseLight {
+            emit: scale * spectrum.to_rgb(),
+            spectrum: Some(Spectrum::PiecewiseLinear(
+                (360..=830)
+                    .step_by(5)
+                    .map(|l| (l as f64, scale * spectrum.eval(l as f64)))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: &Ray, _hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        None
+    }
+
+    fn emitted(&self, _hit_record: &HitRecord) -> Color {
+        self.emit.clone()
+    }
+
+    fn emitted_spectral(
+        &self,
+        _hit_record: &HitRecord,
+        lambda: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        match &self.spectrum {
+            Some(spectrum) => spectrum.sample(lambda),
+            None => SampledSpectrum::from_illuminant(&self.emit, lambda),
+        }
+    }
+
+    // Setting the emission replaces a blackbody spectrum with a plain colour.
+    fn with_parameter(
+        &self,
+        name: &str,
+        value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        match name {
+            "emission" => Ok(Arc::new(DiffuseLight::new(color_parameter(value)?))),
+            _ => Err(ParameterError::Unknown(vec!["emission"])),
+        }
+    }
 }
@@ -0,0 +1,193 @@
+use std::f64::consts::PI;
+
+use crate::{physics::Vec3, utils::random_f64};
+
+// Trowbridge-Reitz (GGX) microfacet distribution with Smith height-correlated masking and
+// shadowing. Directions are given in a local shading frame where the normal is +z and the
+// tangent is +x; the two alphas control roughness along the tangent and bitangent.
+#[derive(Clone, Debug)]
+pub(crate) struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub(crate) fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        // Alphas this small are numerically fragile and visually indistinguishable from a mirror.
+        TrowbridgeReitz {
+            alpha_x: alpha_x.max(1e-4),
+            alpha_y: alpha_y.max(1e-4),
+        }
+    }
+
+    // Perceptually linear roughness in [0, 1], squared as in the Disney convention.
+    pub(crate) fn from_roughness(roughness_u: f64, roughness_v: f64) -> Self {
+        Self::new(
+            roughness_u.clamp(0.0, 1.0).powi(2),
+            roughness_v.clamp(0.0, 1.0).powi(2),
+        )
+    }
+
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let z2 = w.z() * w.z();
+        if z2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let ax = self.alpha_x * w.x();
+        let ay = self.alpha_y * w.y();
+        0.5 * ((1.0 + (ax * ax + ay * ay) / z2).sqrt() - 1.0)
+    }
+
+    // Fraction of microfacets facing `w` that are visible from it.
+    pub(crate) fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Fraction of microfacets visible from both directions.
+    pub(crate) fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of microfacet normals, per unit projected area of the macrosurface.
+    pub(crate) fn d(&self, wm: &Vec3) -> f64 {
+        if wm.z() <= 0.0 {
+            return 0.0;
+        }
+        let x = wm.x() / self.alpha_x;
+        let y = wm.y() / self.alpha_y;
+        let e = x * x + y * y + wm.z() * wm.z();
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    // Evaluates reflection from `wo` into `wi`. Returns D G / (4 cos θo), which times the
+    // Fresnel reflectance is the BRDF times the cosine of `wi`, the cosine between `wo` and the
+    // microfacet normal for evaluating Fresnel, and the density with which `sample_reflection`
+    // picks `wi`.
+    pub(crate) fn eval_reflection(&self, wo: &Vec3, wi: &Vec3) -> Option<(f64, f64, f64)> {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return None;
+        }
+        let wm = wo + wi;
+        if wm.length_squared() == 0.0 {
+            return None;
+        }
+        let wm = wm.unit();
+
+        let d = self.d(&wm);
+        let value = d * self.g(wo, wi) / (4.0 * wo.z());
+        let pdf = self.g1(wo) * d / (4.0 * wo.z());
+        Some((value, wo.dot(&wm), pdf))
+    }
+
+    // Samples a microfacet normal from the distribution of normals visible from `wo`
+    // (Heitz 2018), which keeps the weight of the sampled direction close to one.
+    pub(crate) fn sample_wm(&self, wo: &Vec3) -> Vec3 {
+        let flip = if wo.z() < 0.0 { -1.0 } else { 1.0 };
+
+        // Stretch the view direction into the configuration of a unit hemisphere.
+        let vh = Vec3::new(
+            self.alpha_x * wo.x() * flip,
+            self.alpha_y * wo.y() * flip,
+            wo.z() * flip,
+        )
+        .unit();
+
+        let lensq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if lensq > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / lensq.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        // Sample the projected area of the visible hemisphere.
+        let r = random_f64().sqrt();
+        let phi = 2.0 * PI * random_f64();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        let nh = p1 * &t1 + p2 * &t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * &vh;
+
+        // Unstretch back to the ellipsoid configuration.
+        let wm = Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        );
+        flip * wm.unit()
+    }
+
+    // Samples a direction reflected off a visible microfacet. Returns the direction, the cosine
+    // between `wo` and the microfacet normal for evaluating Fresnel, and the sample weight
+    // excluding Fresnel.
+    pub(crate) fn sample_reflection(&self, wo: &Vec3) -> Option<(Vec3, f64, f64)> {
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let wm = self.sample_wm(wo);
+        let wi = (-wo).reflect(&wm);
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        let weight = self.g(wo, &wi) / self.g1(wo);
+        Some((wi, wo.dot(&wm), weight))
+    }
+
+    // Samples reflection or refraction through a visible microfacet of a dielectric interface,
+    // choosing between them by the exact Fresnel reflectance. `eta` is the ratio of the index
+    // below the surface to the one above it. Returns the direction and the sample weight.
+    pub(crate) fn sample_dielectric(&self, wo: &Vec3, eta: f64) -> Option<(Vec3, f64)> {
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let wm = self.sample_wm(wo);
+        let cos_theta_o = wo.dot(&wm);
+        let wi = if random_f64() < fresnel_dielectric(cos_theta_o, eta) {
+            let wi = (-wo).reflect(&wm);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            let cos_theta_t = (1.0 - (1.0 - cos_theta_o * cos_theta_o) / (eta * eta)).sqrt();
+            let wi = -wo / eta + (cos_theta_o / eta - cos_theta_t) * &wm;
+            if wi.z() >= 0.0 {
+                return None;
+            }
+            wi
+        };
+
+        let weight = self.g(wo, &wi) / self.g1(wo);
+        Some((wi, weight))
+    }
+}
+
+// Weight (1 - cos θ)^5 from Schlick's approximation of the Fresnel reflectance.
+pub(crate) fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+// Exact Fresnel reflectance of an interface between dielectrics, where `eta` is the ratio of
+// the index on the transmitted side to the index on the incident side.
+pub(crate) fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let (cos_theta_i, eta) = if cos_theta_i < 0.0 {
+        (-cos_theta_i, 1.0 / eta)
+    } else {
+        (cos_theta_i, eta)
+    };
+
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+
+    let r_parl = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perp = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    0.5 * (r_parl * r_parl + r_perp * r_perp)
+}
@@ -1,9 +1,27 @@
+mod alpha_mask;
+mod bump;
 mod color;
 mod material;
+mod microfacet;
+mod onb;
+mod principled;
+mod quaternion;
 mod ray;
+mod spectrum;
 mod vec3;
 
+pub(crate) use alpha_mask::AlphaMasked;
+pub(crate) use bump::BumpMapped;
 pub(crate) use color::{write_color, Color};
-pub(crate) use material::{Dielectric, LambertianMaterial, Material, Metal};
+pub(crate) use material::{
+    color_parameter, fraction_parameter, ior_parameter, number_parameter, ComplexIor, Dielectric,
+    DiffuseLight, Dispersion, GgxConductor, GgxDielectric, HenyeyGreenstein, Isotropic,
+    LambertianMaterial, Material, Metal, ParameterError,
+};
+pub(crate) use microfacet::{schlick_weight, TrowbridgeReitz};
+pub(crate) use onb::Onb;
+pub(crate) use principled::Principled;
+pub(crate) use quaternion::Quaternion;
 pub(crate) use ray::Ray;
+pub(crate) use spectrum::{xyz_to_rgb, SampledSpectrum, SampledWavelengths, Spectrum};
 pub(crate) use vec3::{Point3, Vec3};
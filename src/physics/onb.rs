@@ -0,0 +1,46 @@
+use crate::physics::Vec3;
+
+// Orthonormal basis built around a given direction `w`.
+#[derive(Clone, Debug)]
+pub(crate) struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    pub(crate) fn new(n: &Vec3) -> Self {
+        let w = n.unit();
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(&a).unit();
+        let u = w.cross(&v);
+        Onb { u, v, w }
+    }
+
+    // Basis around `n` whose first axis is the tangent `t` made orthogonal to `n`. Falls back to
+    // an arbitrary basis when the tangent is missing or parallel to `n`.
+    pub(crate) fn from_tangent(n: &Vec3, t: &Vec3) -> Self {
+        let w = n.unit();
+        let u = t - &w * t.dot(&w);
+        if u.length_squared() < 1e-16 {
+            return Onb::new(n);
+        }
+        let u = u.unit();
+        let v = w.cross(&u);
+        Onb { u, v, w }
+    }
+
+    // Transform from world space to basis coordinates.
+    pub(crate) fn to_local(&self, a: &Vec3) -> Vec3 {
+        Vec3::new(a.dot(&self.u), a.dot(&self.v), a.dot(&self.w))
+    }
+
+    // Transform from basis coordinates to world space.
+    pub(crate) fn local(&self, a: f64, b: f64, c: f64) -> Vec3 {
+        a * &self.u + b * &self.v + c * &self.w
+    }
+}
@@ -0,0 +1,413 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    physics::{
+        color_parameter, fraction_parameter, ior_parameter, number_parameter, schlick_weight,
+        Color, Material, ParameterError, Ray, TrowbridgeReitz, Vec3,
+    },
+    shapes::HitRecord,
+    textures::{solid, SolidColor, Texture},
+    utils::random_f64,
+};
+
+// Principled uber-material after Burley's Disney BRDF (2012) and its BSDF extension (2015). It
+// blends a diffuse base with sheen, a GGX specular layer shared by the dielectric and metallic
+// parts, rough transmission and a clearcoat. Every parameter is a texture so that it can be
+// painted; scalar parameters are read from the red channel.
+#[derive(Clone, Debug)]
+pub(crate) struct Principled {
+    base_color: Arc<dyn Texture>,
+    metallic: Arc<dyn Texture>,
+    roughness: Arc<dyn Texture>,
+    anisotropic: Arc<dyn Texture>,
+    specular: Arc<dyn Texture>,
+    specular_tint: Arc<dyn Texture>,
+    sheen: Arc<dyn Texture>,
+    sheen_tint: Arc<dyn Texture>,
+    clearcoat: Arc<dyn Texture>,
+    clearcoat_gloss: Arc<dyn Texture>,
+    transmission: Arc<dyn Texture>,
+    ior: Arc<dyn Texture>,
+    emission: Arc<dyn Texture>,
+}
+
+// Parameters evaluated at a hit point.
+struct Parameters {
+    base_color: Color,
+    metallic: f64,
+    roughness: f64,
+    anisotropic: f64,
+    specular: f64,
+    specular_tint: f64,
+    sheen: f64,
+    sheen_tint: f64,
+    clearcoat: f64,
+    clearcoat_gloss: f64,
+    transmission: f64,
+    ior: f64,
+}
+
+fn lerp(a: &Color, b: &Color, t: f64) -> Color {
+    (1.0 - t) * a.clone() + t * b.clone()
+}
+
+fn schlick(f0: &Color, cos_theta: f64) -> Color {
+    let white = Color::new(1.0, 1.0, 1.0);
+    lerp(f0, &white, schlick_weight(cos_theta))
+}
+
+impl Principled {
+    // Rough dielectric with the given base colour; other parameters follow Disney's defaults.
+    pub(crate) fn new(base_color: Arc<dyn Texture>) -> Self {
+        Principled {
+            base_color,
+            metallic: solid(0.0),
+            roughness: solid(0.5),
+            anisotropic: solid(0.0),
+            specular: solid(0.5),
+            specular_tint: solid(0.0),
+            sheen: solid(0.0),
+            sheen_tint: solid(0.5),
+            clearcoat: solid(0.0),
+            clearcoat_gloss: solid(1.0),
+            transmission: solid(0.0),
+            ior: solid(1.5),
+            emission: solid(0.0),
+        }
+    }
+
+    pub(crate) fn with_metallic(mut self, metallic: Arc<dyn Texture>) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    pub(crate) fn with_roughness(mut self, roughness: Arc<dyn Texture>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    pub(crate) fn with_anisotropic(mut self, anisotropic: Arc<dyn Texture>) -> Self {
+        self.anisotropic = anisotropic;
+        self
+    }
+
+    pub(crate) fn with_specular(mut self, specular: Arc<dyn Texture>) -> Self {
+        self.specular = specular;
+        self
+    }
+
+    pub(crate) fn with_specular_tint(mut self, specular_tint: Arc<dyn Texture>) -> Self {
+        self.specular_tint = specular_tint;
+        self
+    }
+
+    pub(crate) fn with_sheen(mut self, sheen: Arc<dyn Texture>) -> Self {
+        self.sheen = sheen;
+        self
+    }
+
+    pub(crate) fn with_sheen_tint(mut self, sheen_tint: Arc<dyn Texture>) -> Self {
+        self.sheen_tint = sheen_tint;
+        self
+    }
+
+    pub(crate) fn with_clearcoat(mut self, clearcoat: Arc<dyn Texture>) -> Self {
+        self.clearcoat = clearcoat;
+        self
+    }
+
+    pub(crate) fn with_clearcoat_gloss(mut self, clearcoat_gloss: Arc<dyn Texture>) -> Self {
+        self.clearcoat_gloss = clearcoat_gloss;
+        self
+    }
+
+    pub(crate) fn with_transmission(mut self, transmission: Arc<dyn Texture>) -> Self {
+        self.transmission = transmission;
+        self
+    }
+
+    pub(crate) fn with_ior(mut self, ior: Arc<dyn Texture>) -> Self {
+        self.ior = ior;
+        self
+    }
+
+    pub(crate) fn with_emission(mut self, emission: Arc<dyn Texture>) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    fn evaluate(&self, hit_record: &HitRecord) -> Parameters {
+        let (u, v, p) = (hit_record.u, hit_record.v, &hit_record.p);
+        let scalar = |t: &Arc<dyn Texture>| t.value(u, v, p).r();
+        let unit = |t: &Arc<dyn Texture>| scalar(t).clamp(0.0, 1.0);
+
+        Parameters {
+            base_color: self.base_color.value(u, v, p),
+            metallic: unit(&self.metallic),
+            roughness: unit(&self.roughness),
+            anisotropic: unit(&self.anisotropic),
+            specular: scalar(&self.specular).max(0.0),
+            specular_tint: unit(&self.specular_tint),
+            sheen: scalar(&self.sheen).max(0.0),
+            sheen_tint: unit(&self.sheen_tint),
+            clearcoat: scalar(&self.clearcoat).max(0.0),
+            clearcoat_gloss: unit(&self.clearcoat_gloss),
+            transmission: unit(&self.transmission),
+            ior: scalar(&self.ior).max(1.0),
+        }
+    }
+}
+
+impl Parameters {
+    // Base colour normalized by luminance, used to tint the specular and sheen lobes.
+    fn tint(&self) -> Color {
+        let lum = self.base_color.luminance();
+        if lum > 0.0 {
+            (1.0 / lum) * self.base_color.clone()
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    // Combined reflectance of the specular layer: dielectric specular over the opaque base and
+    // conductor reflection tinted by the base colour for metals.
+    fn specular_reflectance(&self, cos_theta: f64) -> Color {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let dielectric_f0 = (0.08 * self.specular) * lerp(&white, &self.tint(), self.specular_tint);
+
+        let dielectric = (1.0 - self.metallic) * (1.0 - self.transmission);
+        self.metallic * schlick(&self.base_color, cos_theta)
+            + dielectric * schlick(&dielectric_f0, cos_theta)
+    }
+
+    fn specular_distribution(&self) -> TrowbridgeReitz {
+        let aspect = (1.0 - 0.9 * self.anisotropic).sqrt();
+        let alpha = self.roughness * self.roughness;
+        TrowbridgeReitz::new(alpha / aspect, alpha * aspect)
+    }
+
+    fn clearcoat_distribution(&self) -> TrowbridgeReitz {
+        let alpha = 0.1 * (1.0 - self.clearcoat_gloss) + 0.001 * self.clearcoat_gloss;
+        TrowbridgeReitz::new(alpha, alpha)
+    }
+
+    fn diffuse_weight(&self) -> f64 {
+        (1.0 - self.metallic) * (1.0 - self.transmission)
+    }
+
+    fn clearcoat_weight(&self) -> f64 {
+        0.25 * self.clearcoat
+    }
+
+    // Probabilities of picking the diffuse, specular, glass and clearcoat lobes, in proportion to
+    // their estimated albedo and normalized to sum to one.
+    fn lobe_probabilities(&self, wo: &Vec3) -> Option<[f64; 4]> {
+        let glass_weight = (1.0 - self.metallic) * self.transmission;
+        let weights = [
+            self.diffuse_weight() * (self.base_color.luminance() + self.sheen),
+            self.specular_reflectance(wo.z()).luminance(),
+            glass_weight * self.base_color.luminance(),
+            self.clearcoat_weight() * (0.04 + 0.96 * schlick_weight(wo.z())),
+        ];
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+        Some(weights.map(|w| w / total))
+    }
+
+    // Values of the reflection lobes for light arriving from `wi`, cosine included: the diffuse
+    // lobe, the specular and clearcoat lobes together, and the density with which the lobes
+    // picked with `probabilities` sample `wi`.
+    fn reflection(&self, wo: &Vec3, wi: &Vec3, probabilities: &[f64; 4]) -> (Color, Color, f64) {
+        let cosine_pdf = wi.z() / PI;
+        let diffuse = cosine_pdf * self.diffuse(wo, wi);
+        let mut glossy = Color::new(0.0, 0.0, 0.0);
+        let mut pdf = probabilities[0] * cosine_pdf;
+
+        if let Some((d, cos_theta, specular_pdf)) =
+            self.specular_distribution().eval_reflection(wo, wi)
+        {
+            glossy = glossy + d * self.specular_reflectance(cos_theta);
+            pdf += probabilities[1] * specular_pdf;
+        }
+
+        if let Some((d, cos_theta, clearcoat_pdf)) =
+            self.clearcoat_distribution().eval_reflection(wo, wi)
+        {
+            let fresnel = 0.04 + 0.96 * schlick_weight(cos_theta);
+            let clearcoat = self.clearcoat_weight() * fresnel * d;
+            glossy = glossy + Color::new(clearcoat, clearcoat, clearcoat);
+            pdf += probabilities[3] * clearcoat_pdf;
+        }
+
+        (diffuse, glossy, pdf)
+    }
+
+    // Burley's diffuse retro-reflection plus sheen at grazing angles, as the ratio of the lobe's
+    // value to the cosine-weighted density it is sampled with.
+    fn diffuse(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        let h = (wo + wi).unit();
+        let cos_d = wi.dot(&h);
+
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let fd = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z()))
+            * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z()));
+        let white = Color::new(1.0, 1.0, 1.0);
+        let sheen =
+            (PI * self.sheen * schlick_weight(cos_d)) * lerp(&white, &self.tint(), self.sheen_tint);
+
+        self.diffuse_weight() * (fd * self.base_color.clone() + sheen)
+    }
+}
+
+impl Material for Principled {
+    // Picks one lobe in proportion to its estimated albedo, samples it, and divides by the
+    // probability of having picked it.
+    fn scatter(&self, r_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+        let params = self.evaluate(hit_record);
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(&-r_in.direction().unit());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let probabilities = params.lobe_probabilities(&wo)?;
+        let mut xi = random_f64();
+        let lobe = probabilities
+            .iter()
+            .position(|&p| {
+                xi -= p;
+                xi < 0.0
+            })
+            .unwrap_or(probabilities.len() - 1);
+        let pick = probabilities[lobe];
+
+        let (wi, weight) = match lobe {
+            0 => {
+                let wi = Vec3::new_random_cosine_direction();
+                let weight = params.diffuse(&wo, &wi);
+                (wi, weight)
+            }
+            1 => {
+                let distribution = params.specular_distribution();
+                let (wi, cos_theta, g) = distribution.sample_reflection(&wo)?;
+                (wi, g * params.specular_reflectance(cos_theta))
+            }
+            2 => {
+                let eta = if hit_record.front_face {
+                    params.ior
+                } else {
+                    1.0 / params.ior
+                };
+                let distribution = params.specular_distribution();
+                let (wi, g) = distribution.sample_dielectric(&wo, eta)?;
+                let glass_weight = (1.0 - params.metallic) * params.transmission;
+                (wi, (glass_weight * g) * params.base_color.clone())
+            }
+            _ => {
+                let distribution = params.clearcoat_distribution();
+                let (wi, cos_theta, g) = distribution.sample_reflection(&wo)?;
+                let fresnel = 0.04 + 0.96 * schlick_weight(cos_theta);
+                let value = params.clearcoat_weight() * fresnel * g;
+                (wi, Color::new(value, value, value))
+            }
+        };
+
+        let scattered = Ray::new(
+            &hit_record.p,
+            &frame.local(wi.x(), wi.y(), wi.z()),
+            r_in.time(),
+        );
+        Some((scattered, (1.0 / pick) * weight))
+    }
+
+    // Sums the reflection lobes. Transmissive surfaces are left to their scattered rays, as
+    // light sampling can't see through them.
+    fn eval(&self, r_in: &Ray, hit_record: &HitRecord, wi: &Vec3) -> Option<(Color, f64)> {
+        let params = self.evaluate(hit_record);
+        if params.transmission > 0.0 {
+            return None;
+        }
+
+        let black = Color::new(0.0, 0.0, 0.0);
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(wi);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Some((black, 0.0));
+        }
+        let Some(probabilities) = params.lobe_probabilities(&wo) else {
+            return Some((black, 0.0));
+        };
+
+        let (diffuse, glossy, pdf) = params.reflection(&wo, &wi, &probabilities);
+        Some((diffuse + glossy, pdf))
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.evaluate(hit_record).base_color
+    }
+
+    // The diffuse lobe's share of the reflected light. Transmitted light is all specular.
+    fn diffuse_fraction(&self, r_in: &Ray, hit_record: &HitRecord, wi: &Vec3) -> f64 {
+        let params = self.evaluate(hit_record);
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(wi);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let Some(probabilities) = params.lobe_probabilities(&wo) else {
+            return 0.0;
+        };
+
+        let (diffuse, glossy, _) = params.reflection(&wo, &wi, &probabilities);
+        let total = diffuse.luminance() + glossy.luminance();
+        if total > 0.0 {
+            diffuse.luminance() / total
+        } else {
+            0.0
+        }
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        self.emission
+            .value(hit_record.u, hit_record.v, &hit_record.p)
+    }
+
+    // Parameters set to a constant, replacing any texture.
+    fn with_parameter(
+        &self,
+        name: &str,
+        value: &[f64],
+    ) -> Result<Arc<dyn Material>, ParameterError> {
+        let mut principled = self.clone();
+        let at_least_zero = |value| number_parameter(value, |v| v >= 0.0, "of at least 0");
+        match name {
+            "color" => principled.base_color = Arc::new(SolidColor::new(color_parameter(value)?)),
+            "metallic" => principled.metallic = solid(fraction_parameter(value)?),
+            "roughness" => principled.roughness = solid(fraction_parameter(value)?),
+            "specular" => principled.specular = solid(at_least_zero(value)?),
+            "sheen" => principled.sheen = solid(at_least_zero(value)?),
+            "clearcoat" => principled.clearcoat = solid(at_least_zero(value)?),
+            "transmission" => principled.transmission = solid(fraction_parameter(value)?),
+            "ior" => principled.ior = solid(ior_parameter(value)?),
+            "emission" => principled.emission = Arc::new(SolidColor::new(color_parameter(value)?)),
+            _ => {
+                return Err(ParameterError::Unknown(vec![
+                    "color",
+                    "metallic",
+                    "roughness",
+                    "specular",
+                    "sheen",
+                    "clearcoat",
+                    "transmission",
+                    "ior",
+                    "emission",
+                ]))
+            }
+        }
+        Ok(Arc::new(principled))
+    }
+}
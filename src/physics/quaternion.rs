@@ -0,0 +1,151 @@
+use crate::{physics::Vec3, utils::degrees_to_radians};
+
+// Rotation as a unit quaternion (x, y, z, w).
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Quaternion {
+    x: f64,
+    y: f64,
+    z: f64,
+    w: f64,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion::new(0.0, 0.0, 0.0, 1.0)
+    }
+}
+
+impl Quaternion {
+    // Normalizes the given components, as stored by glTF.
+    pub(crate) fn new(x: f64, y: f64, z: f64, w: f64) -> Self {
+        let length = (x * x + y * y + z * z + w * w).sqrt();
+        if length == 0.0 {
+            return Quaternion::default();
+        }
+        Quaternion {
+            x: x / length,
+            y: y / length,
+            z: z / length,
+            w: w / length,
+        }
+    }
+
+    // The rotation taking the x, y and z axes onto the orthonormal right-handed axes `x`, `y` and
+    // `z`, branching on the largest component to keep the square root well away from zero.
+    pub(crate) fn from_axes(x: &Vec3, y: &Vec3, z: &Vec3) -> Self {
+        let trace = x.x() + y.y() + z.z();
+        if trace > 0.0 {
+            let s = 2.0 * (1.0 + trace).sqrt();
+            Quaternion::new(
+                (y.z() - z.y()) / s,
+                (z.x() - x.z()) / s,
+                (x.y() - y.x()) / s,
+                0.25 * s,
+            )
+        } else if x.x() > y.y() && x.x() > z.z() {
+            let s = 2.0 * (1.0 + x.x() - y.y() - z.z()).sqrt();
+            Quaternion::new(
+                0.25 * s,
+                (y.x() + x.y()) / s,
+                (z.x() + x.z()) / s,
+                (y.z() - z.y()) / s,
+            )
+        } else if y.y() > z.z() {
+            let s = 2.0 * (1.0 + y.y() - x.x() - z.z()).sqrt();
+            Quaternion::new(
+                (y.x() + x.y()) / s,
+                0.25 * s,
+                (z.y() + y.z()) / s,
+                (z.x() - x.z()) / s,
+            )
+        } else {
+            let s = 2.0 * (1.0 + z.z() - x.x() - y.y()).sqrt();
+            Quaternion::new(
+                (z.x() + x.z()) / s,
+                (z.y() + y.z()) / s,
+                0.25 * s,
+                (x.y() - y.x()) / s,
+            )
+        }
+    }
+
+    // Rotation by `degrees` anticlockwise about `axis`, looking down the axis towards the origin.
+    pub(crate) fn from_axis_angle(axis: &Vec3, degrees: f64) -> Self {
+        let half = 0.5 * degrees_to_radians(degrees);
+        let axis = axis.unit();
+        Quaternion::new(
+            axis.x() * half.sin(),
+            axis.y() * half.sin(),
+            axis.z() * half.sin(),
+            half.cos(),
+        )
+    }
+
+    pub(crate) fn rotate(&self, v: &Vec3) -> Vec3 {
+        let q = Vec3::new(self.x, self.y, self.z);
+        let t = 2.0 * q.cross(v);
+        v + self.w * &t + q.cross(&t)
+    }
+
+    pub(crate) fn inverse(&self) -> Self {
+        Quaternion {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+            w: self.w,
+        }
+    }
+
+    // The rotation `self` followed by `other`.
+    pub(crate) fn then(&self, other: &Quaternion) -> Self {
+        let (a, b) = (other, self);
+        Quaternion::new(
+            a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+            a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+        )
+    }
+
+    fn dot(&self, other: &Quaternion) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    // Angle in radians of the rotation taking `self` to `other` the short way round.
+    pub(crate) fn angle_to(&self, other: &Quaternion) -> f64 {
+        2.0 * self.dot(other).abs().min(1.0).acos()
+    }
+
+    // Spherical linear interpolation, turning at a constant rate the short way round.
+    pub(crate) fn slerp(&self, other: &Quaternion, t: f64) -> Self {
+        let mut cos_theta = self.dot(other);
+        let other = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            Quaternion {
+                x: -other.x,
+                y: -other.y,
+                z: -other.z,
+                w: -other.w,
+            }
+        } else {
+            *other
+        };
+
+        // Nearly identical rotations interpolate linearly, avoiding the division by sin θ.
+        let (a, b) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            (
+                ((1.0 - t) * theta).sin() / theta.sin(),
+                (t * theta).sin() / theta.sin(),
+            )
+        };
+        Quaternion::new(
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+            a * self.w + b * other.w,
+        )
+    }
+}
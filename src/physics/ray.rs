@@ -4,16 +4,28 @@ use crate::physics::{Point3, Vec3};
 pub(crate) struct Ray {
     orig: Point3,
     dir: Vec3,
+    // Instant within the camera's shutter interval at which the ray travels, which places any
+    // moving objects it meets.
+    time: f64,
+    // Colour channel the path follows since a dispersive interface split the channels apart.
+    channel: Option<usize>,
 }
 
 impl Ray {
-    pub(crate) fn new(origin: &Point3, direction: &Vec3) -> Self {
+    pub(crate) fn new(origin: &Point3, direction: &Vec3, time: f64) -> Self {
         Ray {
             orig: origin.clone(),
             dir: direction.clone(),
+            time,
+            channel: None,
         }
     }
 
+    pub(crate) fn with_channel(mut self, channel: Option<usize>) -> Self {
+        self.channel = channel;
+        self
+    }
+
     pub(crate) fn origin(&self) -> Point3 {
         self.orig.clone()
     }
@@ -22,6 +34,14 @@ impl Ray {
         self.dir.clone()
     }
 
+    pub(crate) fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub(crate) fn channel(&self) -> Option<usize> {
+        self.channel
+    }
+
     pub(crate) fn at(&self, t: f64) -> Point3 {
         &self.orig + (&self.dir * t)
     }
@@ -0,0 +1,317 @@
+use std::sync::OnceLock;
+
+use crate::physics::Color;
+
+pub(crate) const LAMBDA_MIN: f64 = 360.0;
+pub(crate) const LAMBDA_MAX: f64 = 830.0;
+
+// Number of wavelengths carried along each path: one hero wavelength plus evenly spaced
+// companions that share its path until something wavelength-dependent happens.
+pub(crate) const N_SPECTRUM_SAMPLES: usize = 4;
+
+#[derive(Clone, Debug)]
+pub(crate) struct SampledWavelengths {
+    lambda: [f64; N_SPECTRUM_SAMPLES],
+    pdf: [f64; N_SPECTRUM_SAMPLES],
+}
+
+impl SampledWavelengths {
+    pub(crate) fn sample_uniform(u: f64) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let delta = range / N_SPECTRUM_SAMPLES as f64;
+
+        let mut lambda = [0.0; N_SPECTRUM_SAMPLES];
+        lambda[0] = LAMBDA_MIN + u * range;
+        for i in 1..N_SPECTRUM_SAMPLES {
+            lambda[i] = lambda[i - 1] + delta;
+            if lambda[i] > LAMBDA_MAX {
+                lambda[i] -= range;
+            }
+        }
+
+        SampledWavelengths {
+            lambda,
+            pdf: [1.0 / range; N_SPECTRUM_SAMPLES],
+        }
+    }
+
+    pub(crate) fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    pub(crate) fn get(&self, i: usize) -> f64 {
+        self.lambda[i]
+    }
+
+    // Drops the companion wavelengths once the path has become wavelength-specific, e.g. after
+    // refraction through a dispersive interface.
+    pub(crate) fn terminate_secondary(&mut self) {
+        if self.pdf[1..].iter().all(|&pdf| pdf == 0.0) {
+            return;
+        }
+        for pdf in &mut self.pdf[1..] {
+            *pdf = 0.0;
+        }
+        self.pdf[0] /= N_SPECTRUM_SAMPLES as f64;
+    }
+}
+
+// Spectral quantity evaluated at a set of sampled wavelengths.
+#[derive(Clone, Debug)]
+pub(crate) struct SampledSpectrum([f64; N_SPECTRUM_SAMPLES]);
+
+impl SampledSpectrum {
+    pub(crate) fn splat(value: f64) -> Self {
+        SampledSpectrum([value; N_SPECTRUM_SAMPLES])
+    }
+
+    pub(crate) fn from_fn(f: impl FnMut(usize) -> f64) -> Self {
+        SampledSpectrum(std::array::from_fn(f))
+    }
+
+    // Smooth reflectance spectrum whose colour under the reference white matches `rgb`.
+    pub(crate) fn from_albedo(rgb: &Color, lambda: &SampledWavelengths) -> Self {
+        let coefficients = rgb_basis().coefficients(rgb);
+        Self::from_fn(|i| {
+            let b = basis(lambda.get(i));
+            (coefficients[0] * b[0] + coefficients[1] * b[1] + coefficients[2] * b[2]).max(0.0)
+        })
+    }
+
+    // Emission spectrum whose colour matches `rgb`, built on top of the reference white.
+    pub(crate) fn from_illuminant(rgb: &Color, lambda: &SampledWavelengths) -> Self {
+        let albedo = Self::from_albedo(rgb, lambda);
+        Self::from_fn(|i| albedo.0[i] * reference_white(lambda.get(i)))
+    }
+
+    pub(crate) fn map(&self, f: impl Fn(f64) -> f64) -> Self {
+        Self::from_fn(|i| f(self.0[i]))
+    }
+
+    // Monte Carlo estimate of the colour of this spectrum, in white balanced linear sRGB.
+    pub(crate) fn to_rgb(&self, lambda: &SampledWavelengths) -> Color {
+        let mut xyz = [0.0; 3];
+        for i in 0..N_SPECTRUM_SAMPLES {
+            if lambda.pdf[i] == 0.0 {
+                continue;
+            }
+            let cmf = cie_xyz(lambda.get(i));
+            for c in 0..3 {
+                xyz[c] += cmf[c] * self.0[i] / lambda.pdf[i];
+            }
+        }
+
+        let scale = rgb_basis().xyz_normalization / N_SPECTRUM_SAMPLES as f64;
+        rgb_basis().xyz_to_rgb(&xyz.map(|c| c * scale))
+    }
+}
+
+impl std::ops::Add<SampledSpectrum> for SampledSpectrum {
+    type Output = SampledSpectrum;
+
+    fn add(self, rhs: SampledSpectrum) -> Self::Output {
+        SampledSpectrum::from_fn(|i| self.0[i] + rhs.0[i])
+    }
+}
+
+impl std::ops::Mul<SampledSpectrum> for SampledSpectrum {
+    type Output = SampledSpectrum;
+
+    fn mul(self, rhs: SampledSpectrum) -> Self::Output {
+        SampledSpectrum::from_fn(|i| self.0[i] * rhs.0[i])
+    }
+}
+
+impl std::ops::Mul<SampledSpectrum> for f64 {
+    type Output = SampledSpectrum;
+
+    fn mul(self, rhs: SampledSpectrum) -> Self::Output {
+        rhs.map(|v| self * v)
+    }
+}
+
+// Continuous spectral distribution over wavelengths in nanometres.
+#[derive(Clone, Debug)]
+pub(crate) enum Spectrum {
+    // Planck's law at the given temperature in kelvin, normalized to a peak of 1.
+    Blackbody(f64),
+    // Linear interpolation between (wavelength, value) pairs sorted by wavelength, clamped to
+    // the end values outside the tabulated range.
+    PiecewiseLinear(Vec<(f64, f64)>),
+}
+
+impl Spectrum {
+    pub(crate) fn eval(&self, lambda: f64) -> f64 {
+        match self {
+            Spectrum::Blackbody(temperature) => {
+                let lambda_max = 2.8977721e-3 / temperature * 1e9;
+                planck(lambda, *temperature) / planck(lambda_max, *temperature)
+            }
+            Spectrum::PiecewiseLinear(samples) => {
+                let Some(first) = samples.first() else {
+                    return 0.0;
+                };
+                if lambda <= first.0 {
+                    return first.1;
+                }
+                for pair in samples.windows(2) {
+                    let ((l0, v0), (l1, v1)) = (pair[0], pair[1]);
+                    if lambda <= l1 {
+                        let t = (lambda - l0) / (l1 - l0);
+                        return (1.0 - t) * v0 + t * v1;
+                    }
+                }
+                samples[samples.len() - 1].1
+            }
+        }
+    }
+
+    pub(crate) fn sample(&self, lambda: &SampledWavelengths) -> SampledSpectrum {
+        SampledSpectrum::from_fn(|i| self.eval(lambda.get(i)))
+    }
+
+    // Luminance relative to the reference white.
+    pub(crate) fn luminance(&self) -> f64 {
+        integrate(|l| cie_xyz(l)[1] * self.eval(l)) * rgb_basis().xyz_normalization
+    }
+
+    // Colour of the spectrum in white balanced linear sRGB, integrated numerically.
+    pub(crate) fn to_rgb(&self) -> Color {
+        let xyz = [0, 1, 2]
+            .map(|c| integrate(|l| cie_xyz(l)[c] * self.eval(l)) * rgb_basis().xyz_normalization);
+        rgb_basis().xyz_to_rgb(&xyz)
+    }
+}
+
+// White balanced linear sRGB of a CIE XYZ colour, keeping Y as the luminance.
+pub(crate) fn xyz_to_rgb(xyz: &[f64; 3]) -> Color {
+    rgb_basis().xyz_to_rgb(xyz)
+}
+
+// Spectral radiance of a blackbody, with the wavelength in nanometres.
+fn planck(lambda: f64, temperature: f64) -> f64 {
+    const C: f64 = 299792458.0;
+    const H: f64 = 6.62606957e-34;
+    const KB: f64 = 1.3806488e-23;
+
+    let l = lambda * 1e-9;
+    (2.0 * H * C * C) / (l.powi(5) * ((H * C / (l * KB * temperature)).exp() - 1.0))
+}
+
+// Reference white used to uplift emitters; a 6504K blackbody stands in for D65.
+fn reference_white(lambda: f64) -> f64 {
+    Spectrum::Blackbody(6504.0).eval(lambda)
+}
+
+// Riemann sum over the visible range at 1nm spacing.
+fn integrate(f: impl Fn(f64) -> f64) -> f64 {
+    let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+    (0..=steps).map(|i| f(LAMBDA_MIN + i as f64)).sum()
+}
+
+// CIE 1931 colour matching functions using the multi-lobe Gaussian fit of Wyman, Sloan and
+// Shirley (2013).
+fn cie_xyz(lambda: f64) -> [f64; 3] {
+    let g = |mu: f64, sigma1: f64, sigma2: f64| {
+        let t = (lambda - mu) / if lambda < mu { sigma1 } else { sigma2 };
+        (-0.5 * t * t).exp()
+    };
+
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
+
+// Smooth blue, green and red basis spectra that sum to one at every wavelength, so a white
+// albedo uplifts to a perfectly flat reflectance.
+fn basis(lambda: f64) -> [f64; 3] {
+    let sigmoid = |x: f64| 1.0 / (1.0 + (-x).exp());
+    let blue_green = sigmoid((lambda - 490.0) / 12.0);
+    let green_red = sigmoid((lambda - 600.0) / 12.0);
+    [green_red, blue_green - green_red, 1.0 - blue_green]
+}
+
+type Matrix3 = [[f64; 3]; 3];
+
+fn mul(m: &Matrix3, v: &[f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|r| m[r][0] * v[0] + m[r][1] * v[1] + m[r][2] * v[2])
+}
+
+fn invert(m: &Matrix3) -> Matrix3 {
+    let cofactor = |r: usize, c: usize| {
+        let (r0, r1) = ((r + 1) % 3, (r + 2) % 3);
+        let (c0, c1) = ((c + 1) % 3, (c + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let det = (0..3).map(|c| m[0][c] * cofactor(0, c)).sum::<f64>();
+
+    let mut inv = [[0.0; 3]; 3];
+    for (r, row) in inv.iter_mut().enumerate() {
+        for (c, value) in row.iter_mut().enumerate() {
+            *value = cofactor(c, r) / det;
+        }
+    }
+    inv
+}
+
+// Precomputed tables tying the spectral basis to the RGB pipeline.
+struct RgbBasis {
+    // Scale making the reference white have unit luminance.
+    xyz_normalization: f64,
+    // Per-channel scale that maps the reference white to (1, 1, 1).
+    white_balance: [f64; 3],
+    // Maps an RGB triple to basis coefficients that reproduce it under the reference white.
+    to_coefficients: Matrix3,
+}
+
+const XYZ_TO_SRGB: Matrix3 = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.9692660, 1.8760108, 0.0415560],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+
+fn rgb_basis() -> &'static RgbBasis {
+    static BASIS: OnceLock<RgbBasis> = OnceLock::new();
+    BASIS.get_or_init(RgbBasis::compute)
+}
+
+impl RgbBasis {
+    fn compute() -> Self {
+        let xyz_of =
+            |f: &dyn Fn(f64) -> f64| [0, 1, 2].map(|c| integrate(|l| cie_xyz(l)[c] * f(l)));
+
+        let white = xyz_of(&reference_white);
+        let xyz_normalization = 1.0 / white[1];
+        let white_rgb = mul(&XYZ_TO_SRGB, &white.map(|c| c * xyz_normalization));
+        let white_balance = white_rgb.map(|c| 1.0 / c);
+
+        // Column j holds the white balanced colour of basis spectrum j under the reference white.
+        let columns = [0, 1, 2].map(|j| {
+            let xyz = xyz_of(&|l| basis(l)[j] * reference_white(l));
+            let rgb = mul(&XYZ_TO_SRGB, &xyz.map(|c| c * xyz_normalization));
+            [0, 1, 2].map(|i| rgb[i] * white_balance[i])
+        });
+        let basis_to_rgb = [0, 1, 2].map(|i| columns.map(|column| column[i]));
+
+        RgbBasis {
+            xyz_normalization,
+            white_balance,
+            to_coefficients: invert(&basis_to_rgb),
+        }
+    }
+
+    fn coefficients(&self, rgb: &Color) -> [f64; 3] {
+        mul(&self.to_coefficients, &[rgb.r(), rgb.g(), rgb.b()])
+    }
+
+    fn xyz_to_rgb(&self, xyz: &[f64; 3]) -> Color {
+        let rgb = mul(&XYZ_TO_SRGB, xyz);
+        Color::new(
+            rgb[0] * self.white_balance[0],
+            rgb[1] * self.white_balance[1],
+            rgb[2] * self.white_balance[2],
+        )
+    }
+}
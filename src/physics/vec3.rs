@@ -1,10 +1,11 @@
 use std::{
+    f64::consts::PI,
     fmt::Display,
     ops::{Mul, MulAssign},
     slice::SliceIndex,
 };
 
-use crate::utils::{random_f64_in_interval, random_vec3_in_interval};
+use crate::utils::{random_f64, random_f64_in_interval, random_vec3_in_interval};
 
 #[derive(Clone, Debug)]
 pub(crate) struct Vec3 {
@@ -232,7 +233,19 @@ impl Vec3 {
         Self::new_random_in_unit_sphere().unit()
     }
 
-    #[allow(dead_code)]
+    // Random direction about +z, distributed proportionally to the cosine of its angle to it.
+    pub(crate) fn new_random_cosine_direction() -> Self {
+        let r1 = random_f64();
+        let r2 = random_f64();
+
+        let phi = 2.0 * PI * r1;
+        let x = phi.cos() * r2.sqrt();
+        let y = phi.sin() * r2.sqrt();
+        let z = (1.0 - r2).sqrt();
+
+        Vec3::new(x, y, z)
+    }
+
     pub(crate) fn new_random_in_unit_disk() -> Self {
         loop {
             let p = Vec3::new(
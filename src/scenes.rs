@@ -0,0 +1,2214 @@
+use std::{
+    io::{Error, ErrorKind},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    animation::{Interpolation, Track},
+    camera::{Aperture, Camera, LensSystem, PhotographicSettings, StereoLayout},
+    image::Image,
+    lights::{
+        Background, DirectionalLight, EnvironmentMap, IesProfile, Light, PhysicalSky, PointLight,
+        SpotLight,
+    },
+    loaders::{load_gltf, load_obj, Names},
+    physics::{
+        AlphaMasked, BumpMapped, Color, ComplexIor, Dielectric, DiffuseLight, Dispersion,
+        GgxConductor, GgxDielectric, HenyeyGreenstein, Isotropic, LambertianMaterial, Material,
+        Metal, Onb, ParameterError, Point3, Principled, Vec3,
+    },
+    shapes::{
+        Aabb, BvhNode, ConstantMedium, HeterogeneousMedium, Hittable, HittableList, Sphere,
+        Transform, Transformed, Triangle, Vertex, VoxelGrid,
+    },
+    textures::{solid, ImageTexture, SolidColor},
+    utils::{random_color, random_color_in_interval, random_f64, random_f64_in_interval},
+};
+
+pub(crate) struct Scene {
+    pub(crate) world: HittableList,
+    pub(crate) camera: Camera,
+    pub(crate) background: Background,
+    pub(crate) lights: Vec<Arc<dyn Light>>,
+    // Names of materials and lights from a model file, which `set` accepts besides numbers.
+    pub(crate) names: Names,
+}
+
+pub(crate) const SCENE_NAMES: &[&str] = &[
+    "random_spheres",
+    "clouds",
+    "glass",
+    "spectral",
+    "microfacet",
+    "principled",
+    "bumps",
+    "cutout",
+    "environment",
+    "daylight",
+    "lights",
+    "ies",
+    "many_lights",
+    "isometric",
+    "panorama",
+    "stereo",
+    "lens",
+    "bokeh",
+    "motion_blur",
+    "animation",
+    "exposure",
+];
+
+// Values of a scene that can be overridden with `Scene::set`. Anything else is fixed by the
+// scene's code. `<n>` is the number of a light, or of a material in the order of the material ID
+// pass, counting from 1, or else the name a model file gives it.
+pub(crate) const SCENE_KEYS: &[&str] = &[
+    "camera.look_from",
+    "camera.look_at",
+    "camera.vup",
+    "camera.vfov",
+    "camera.defocus_angle",
+    "camera.shutter",
+    "camera.samples_per_pixel",
+    "camera.image_width",
+    "camera.image_height",
+    "background",
+    "light.<n>.intensity",
+    "light.<n>.color",
+    "material.<n>.<parameter>",
+];
+
+impl Scene {
+    // Overrides the value named `key`, one of `SCENE_KEYS`, with `value`: three numbers separated
+    // by commas for points, directions and colours, a number of degrees for angles, the opening
+    // and closing times separated by a comma for the shutter, a whole number for counts and
+    // sizes, and `gradient` or a colour for the background. A single number also sets a colour
+    // to a grey. Changing the width keeps the aspect ratio, changing the height keeps the width.
+    // A part of the view that the scene animates can't be set, as its track would override it.
+    pub(crate) fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        let invalid = |expected: &str| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("--set {}: expected {}, got {}", key, expected, value),
+            )
+        };
+        let numbers = || -> Option<Vec<f64>> {
+            value
+                .split(',')
+                .map(|c| c.trim().parse().ok().filter(|v: &f64| v.is_finite()))
+                .collect()
+        };
+
+        if key == "background" {
+            self.background = match (value, numbers().as_deref()) {
+                ("gradient", _) => Background::Gradient,
+                (_, Some(&[r, g, b])) if r >= 0.0 && g >= 0.0 && b >= 0.0 => {
+                    Background::Uniform(Color::new(r, g, b))
+                }
+                (_, Some(&[v])) if v >= 0.0 => Background::Uniform(Color::new(v, v, v)),
+                _ => return Err(invalid("gradient or <r>,<g>,<b> of at least 0")),
+            };
+            return Ok(());
+        }
+        if let Some(rest) = key.strip_prefix("light.") {
+            let Some((id, parameter)) = rest.rsplit_once('.') else {
+                return Err(unknown_key(key));
+            };
+            let emission: Box<dyn Fn(&Color) -> Color> = match (parameter, numbers().as_deref()) {
+                // The luminance, keeping the colour.
+                ("intensity", Some(&[v])) if v >= 0.0 => Box::new(move |c: &Color| {
+                    if c.luminance() > 0.0 {
+                        (v / c.luminance()) * c.clone()
+                    } else {
+                        Color::new(v, v, v)
+                    }
+                }),
+                ("intensity", _) => return Err(invalid("a number of at least 0")),
+                // The colour, keeping the luminance.
+                ("color", Some(&[r, g, b])) if r >= 0.0 && g >= 0.0 && b >= 0.0 => {
+                    let color = Color::new(r, g, b);
+                    if color.luminance() <= 0.0 {
+                        return Err(invalid("a colour brighter than black"));
+                    }
+                    Box::new(move |c: &Color| (c.luminance() / color.luminance()) * color.clone())
+                }
+                ("color", _) => return Err(invalid("<r>,<g>,<b> of at least 0")),
+                _ => return Err(unknown_key(key)),
+            };
+            for i in self.light_indices(key, id)? {
+                self.lights[i] = self.lights[i].with_emission(&emission).ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("--set {}: light {} has no emission to set", key, i + 1),
+                    )
+                })?;
+            }
+            return Ok(());
+        }
+        if let Some(rest) = key.strip_prefix("material.") {
+            let Some((id, parameter)) = rest.rsplit_once('.') else {
+                return Err(unknown_key(key));
+            };
+            let Some(value) = numbers() else {
+                return Err(invalid("a number or <r>,<g>,<b>"));
+            };
+            for material in self.materials_by_id(key, id)? {
+                let replacement =
+                    material
+                        .with_parameter(parameter, &value)
+                        .map_err(|e| match e {
+                            ParameterError::Unknown(names) if names.is_empty() => Error::new(
+                                ErrorKind::InvalidInput,
+                                format!("--set {}: {} has no parameters", key, material.name()),
+                            ),
+                            ParameterError::Unknown(names) => Error::new(
+                                ErrorKind::InvalidInput,
+                                format!(
+                                    "--set {}: {} has no parameter {}, expected one of {}",
+                                    key,
+                                    material.name(),
+                                    parameter,
+                                    names.join(", ")
+                                ),
+                            ),
+                            ParameterError::Invalid(expected) => invalid(&expected),
+                        })?;
+                if let Some(world) = self.world.with_replaced_material(&material, &replacement) {
+                    self.world = world;
+                }
+                for (_, named) in &mut self.names.materials {
+                    if Arc::ptr_eq(named, &material) {
+                        *named = replacement.clone();
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        if let Some(part) = key.strip_prefix("camera.") {
+            if self.camera.animated_view().contains(&part) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "--set {}: the scene animates {}, which would override it",
+                        key, part
+                    ),
+                ));
+            }
+        }
+        let vector = || match numbers().as_deref() {
+            Some(&[x, y, z]) => Ok(Vec3::new(x, y, z)),
+            _ => Err(invalid("<x>,<y>,<z>")),
+        };
+        let degrees = |max: f64| match numbers().as_deref() {
+            Some(&[angle]) if (0.0..max).contains(&angle) => Ok(angle),
+            _ => Err(invalid(&format!("degrees from 0 to {}", max))),
+        };
+        let count = || match value.parse::<usize>() {
+            Ok(count) if count > 0 => Ok(count),
+            _ => Err(invalid("a whole number of at least 1")),
+        };
+
+        let camera = self.camera.clone();
+        let (width, _) = camera.image_size();
+        self.camera = match key {
+            "camera.look_from" => camera.with_view(Some(vector()?), None, None, None),
+            "camera.look_at" => camera.with_view(None, Some(vector()?), None, None),
+            "camera.vup" => camera.with_view(None, None, Some(vector()?), None),
+            "camera.vfov" => camera.with_view(None, None, None, Some(degrees(180.0)?)),
+            "camera.defocus_angle" => Ok(camera.with_defocus(degrees(180.0)?)),
+            "camera.shutter" => match numbers().as_deref() {
+                Some(&[open, close]) if open <= close => Ok(camera.with_shutter(open, close)),
+                _ => Err(invalid("<open>,<close>")),
+            },
+            "camera.samples_per_pixel" => Ok(camera.with_samples_per_pixel(count()?)),
+            "camera.image_width" => camera.with_resolution(count()?, None),
+            "camera.image_height" => camera.with_resolution(width, Some(count()?)),
+            _ => return Err(unknown_key(key)),
+        }?;
+        Ok(())
+    }
+
+    // Indices into `lights` of the light numbered or named `id`.
+    fn light_indices(&self, key: &str, id: &str) -> Result<Vec<usize>, Error> {
+        let indices = match id.parse::<usize>() {
+            Ok(n) if (1..=self.lights.len()).contains(&n) => vec![n - 1],
+            Ok(_) => Vec::new(),
+            Err(_) => self
+                .names
+                .lights
+                .iter()
+                .filter(|(name, _)| name == id)
+                .map(|(_, i)| *i)
+                .collect(),
+        };
+        if indices.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "--set {}: no light {}, the scene has {} numbered from 1",
+                    key,
+                    id,
+                    self.lights.len()
+                ),
+            ));
+        }
+        Ok(indices)
+    }
+
+    // The materials in the world numbered or named `id`.
+    fn materials_by_id(&self, key: &str, id: &str) -> Result<Vec<Arc<dyn Material>>, Error> {
+        let mut materials: Vec<Arc<dyn Material>> = Vec::new();
+        self.world.materials(&mut |material| {
+            if !materials.iter().any(|m| Arc::ptr_eq(m, material)) {
+                materials.push(material.clone());
+            }
+        });
+
+        let count = materials.len();
+        let named = match id.parse::<usize>() {
+            Ok(n) if (1..=count).contains(&n) => vec![materials.swap_remove(n - 1)],
+            Ok(_) => Vec::new(),
+            Err(_) => materials
+                .into_iter()
+                .filter(|m| {
+                    self.names
+                        .materials
+                        .iter()
+                        .any(|(name, named)| name == id && Arc::ptr_eq(named, m))
+                })
+                .collect(),
+        };
+        if named.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "--set {}: no material {} on the scene's shapes, which have {} numbered \
+                     from 1",
+                    key, id, count
+                ),
+            ));
+        }
+        Ok(named)
+    }
+}
+
+fn unknown_key(key: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "--set: unknown key {}, expected one of {}",
+            key,
+            SCENE_KEYS.join(", ")
+        ),
+    )
+}
+
+pub(crate) fn by_name(name: &str) -> Option<Scene> {
+    match name {
+        "random_spheres" => Some(random_spheres()),
+        "clouds" => Some(clouds()),
+        "glass" => Some(glass()),
+        "spectral" => Some(spectral()),
+        "microfacet" => Some(microfacet()),
+        "principled" => Some(principled()),
+        "bumps" => Some(bumps()),
+        "cutout" => Some(cutout()),
+        "environment" => Some(environment()),
+        "daylight" => Some(daylight()),
+        "lights" => Some(lights()),
+        "ies" => Some(ies()),
+        "many_lights" => Some(many_lights()),
+        "isometric" => Some(isometric()),
+        "panorama" => Some(panorama()),
+        "stereo" => Some(stereo()),
+        "lens" => Some(lens()),
+        "bokeh" => Some(bokeh()),
+        "motion_blur" => Some(motion_blur()),
+        "animation" => Some(animation()),
+        "exposure" => Some(exposure()),
+        _ => None,
+    }
+}
+
+fn generate_material() -> Arc<dyn Material> {
+    let choose_mat = random_f64();
+    if choose_mat < 0.8 {
+        // diffuse
+        let albedo = random_color() * random_color();
+        Arc::new(LambertianMaterial::new(albedo))
+    } else if choose_mat < 0.95 {
+        //metal
+        let albedo = random_color_in_interval(0.5, 1.0);
+        let fuzz = random_f64_in_interval(0.0, 0.5);
+        Arc::new(Metal::new(albedo, fuzz))
+    } else {
+        //glass
+        Arc::new(Dielectric::new(1.5))
+    }
+}
+
+fn random_spheres() -> Scene {
+    let mut world = HittableList::default();
+
+    let material_ground = Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    )));
+
+    for a in (-11..11).map(|a| a as f64) {
+        for b in (-11..11).map(|b| b as f64) {
+            let center = Point3::new(a + 0.9 * random_f64(), 0.2, b + 0.9 * random_f64());
+            if (&center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let material = generate_material();
+                world.add(Arc::new(Sphere::new(center, 0.2, material)));
+            }
+        }
+    }
+
+    let mut world = {
+        let mut bvh = HittableList::default();
+        bvh.add(Arc::new(BvhNode::new(world)));
+        bvh
+    };
+
+    let material1 = Arc::new(Dielectric::new(1.5));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        material1,
+    )));
+
+    let material2 = Arc::new(LambertianMaterial::new(Color::new(0.4, 0.2, 0.1)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        material2,
+    )));
+
+    let material3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        material3,
+    )));
+
+    const SAMPLES_PER_PIXEL: usize = 500;
+    const VFOV: f64 = 20.0;
+    const ASPECT_RATIO: f64 = 16.0 / 9.0;
+    const IMAGE_WIDTH: usize = 1200;
+
+    let look_from = Point3::new(13.0, 2.0, 3.0);
+    let look_at = Point3::new(0.0, 0.0, 0.0);
+    let view_up = Vec3::new(0.0, 1.0, 0.0);
+
+    let camera = Camera::new(
+        SAMPLES_PER_PIXEL,
+        VFOV,
+        ASPECT_RATIO,
+        IMAGE_WIDTH,
+        look_from,
+        look_at,
+        view_up,
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// Cheap lattice value noise in [0, 1], good enough to break up the silhouette of a cloud.
+fn value_noise(x: f64, y: f64, z: f64) -> f64 {
+    fn hash(x: i64, y: i64, z: i64) -> f64 {
+        let mut h =
+            (x.wrapping_mul(73856093) ^ y.wrapping_mul(19349663) ^ z.wrapping_mul(83492791)) as u64;
+        h ^= h >> 13;
+        h = h.wrapping_mul(0x5bd1e995);
+        h ^= h >> 15;
+        (h & 0xffff) as f64 / 65535.0
+    }
+
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+    let (fx, fy, fz) = (smooth(x - x0), smooth(y - y0), smooth(z - z0));
+    let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+
+    let lerp = |a: f64, b: f64, t: f64| (1.0 - t) * a + t * b;
+    let corner = |dx: i64, dy: i64, dz: i64| hash(x0 + dx, y0 + dy, z0 + dz);
+
+    lerp(
+        lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), fx),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), fx),
+            fy,
+        ),
+        lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), fx),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), fx),
+            fy,
+        ),
+        fz,
+    )
+}
+
+// Procedural cumulus-like density: a few overlapping blobs eroded by fractal noise.
+fn cloud_grid(resolution: usize) -> VoxelGrid {
+    let blobs = [
+        (Vec3::new(0.5, 0.4, 0.5), 0.30),
+        (Vec3::new(0.3, 0.35, 0.45), 0.22),
+        (Vec3::new(0.7, 0.35, 0.55), 0.20),
+        (Vec3::new(0.5, 0.6, 0.5), 0.20),
+    ];
+
+    let n = resolution as f64;
+    let mut data = Vec::with_capacity(resolution * resolution * resolution);
+    for z in 0..resolution {
+        for y in 0..resolution {
+            for x in 0..resolution {
+                let p = Vec3::new(
+                    (x as f64 + 0.5) / n,
+                    (y as f64 + 0.5) / n,
+                    (z as f64 + 0.5) / n,
+                );
+
+                let shape = blobs
+                    .iter()
+                    .map(|(c, r)| 1.0 - (&p - c).length() / r)
+                    .fold(0.0, f64::max);
+
+                let noise = (0..4).fold(0.0, |acc, octave| {
+                    let f = 4.0 * (1 << octave) as f64;
+                    acc + value_noise(p.x() * f, p.y() * f, p.z() * f) / (1 << octave) as f64
+                }) / 1.875;
+
+                data.push((shape - 0.35 * noise).max(0.0));
+            }
+        }
+    }
+
+    VoxelGrid::new(resolution, resolution, resolution, data).unwrap()
+}
+
+fn clouds() -> Scene {
+    let mut world = HittableList::default();
+
+    let material_ground = Arc::new(LambertianMaterial::new(Color::new(0.4, 0.5, 0.3)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    )));
+
+    let cloud_phase = Arc::new(HenyeyGreenstein::new(Color::new(0.95, 0.95, 0.95), 0.6));
+    world.add(Arc::new(HeterogeneousMedium::new(
+        Aabb::new(&Point3::new(-3.0, 0.0, -3.0), &Point3::new(3.0, 6.0, 3.0)),
+        cloud_grid(64),
+        40.0,
+        cloud_phase,
+    )));
+
+    let fog_boundary = Arc::new(Sphere::new(
+        Point3::new(4.5, 1.0, 2.0),
+        1.0,
+        Arc::new(Dielectric::new(1.5)),
+    ));
+    world.add(Arc::new(ConstantMedium::new(
+        fog_boundary,
+        1.5,
+        Arc::new(Isotropic::new(Color::new(0.8, 0.3, 0.2))),
+    )));
+
+    let camera = Camera::new(
+        200,
+        30.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 3.0, 18.0),
+        Point3::new(0.0, 2.5, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+fn glass() -> Scene {
+    let mut world = HittableList::default();
+
+    let material_ground = Arc::new(LambertianMaterial::new(Color::new(0.8, 0.8, 0.8)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    )));
+
+    // Bottle green glass absorbing mostly red and blue.
+    let green_glass = Dielectric::new(1.5).with_absorption(Color::new(1.2, 0.15, 0.9));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-3.3, 1.0, 0.0),
+        1.0,
+        Arc::new(green_glass),
+    )));
+
+    let tinted_crown = Dielectric::new(1.5)
+        .with_dispersion(Dispersion::bk7())
+        .with_absorption(Color::new(0.6, 0.3, 0.05));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-1.1, 1.0, 0.0),
+        1.0,
+        Arc::new(tinted_crown),
+    )));
+
+    let flint = Dielectric::new(1.7).with_dispersion(Dispersion::Cauchy {
+        a: 1.728,
+        b: 0.01342,
+    });
+    world.add(Arc::new(Sphere::new(
+        Point3::new(1.1, 1.0, 0.0),
+        1.0,
+        Arc::new(flint),
+    )));
+
+    let diamond = Dielectric::new(2.42).with_dispersion(Dispersion::diamond());
+    world.add(Arc::new(Sphere::new(
+        Point3::new(3.3, 1.0, 0.0),
+        1.0,
+        Arc::new(diamond),
+    )));
+
+    let dense_flint = Dielectric::new(1.78).with_dispersion(Dispersion::dense_flint());
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 0.5, 2.0),
+        0.5,
+        Arc::new(dense_flint),
+    )));
+
+    let camera = Camera::new(
+        200,
+        30.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 3.0, 12.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// Measured metals, dispersive glass and water under a warm blackbody lamp. Best viewed with
+// spectral rendering enabled.
+fn spectral() -> Scene {
+    let mut world = HittableList::default();
+
+    let material_ground = Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    )));
+
+    let metals = [
+        ComplexIor::gold(),
+        ComplexIor::copper(),
+        ComplexIor::silver(),
+        ComplexIor::aluminium(),
+    ];
+    for (i, ior) in metals.into_iter().enumerate() {
+        world.add(Arc::new(Sphere::new(
+            Point3::new(-3.3 + 2.2 * i as f64, 1.0, 0.0),
+            1.0,
+            Arc::new(Metal::measured(ior, 0.05)),
+        )));
+    }
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-1.0, 0.6, 2.5),
+        0.6,
+        Arc::new(Dielectric::new(1.33).with_dispersion(Dispersion::water())),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(1.0, 0.6, 2.5),
+        0.6,
+        Arc::new(Dielectric::new(2.42).with_dispersion(Dispersion::diamond())),
+    )));
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-4.0, 6.0, 5.0),
+        1.5,
+        Arc::new(DiffuseLight::blackbody(2700.0, 8.0)),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(5.0, 4.0, 4.0),
+        0.5,
+        Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 6.0))),
+    )));
+
+    let camera = Camera::new(
+        200,
+        30.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 3.0, 13.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// Rows of GGX conductors and rough glass with increasing roughness, plus anisotropic metal.
+fn microfacet() -> Scene {
+    let mut world = HittableList::default();
+
+    let material_ground = Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    )));
+
+    for i in 0..5 {
+        let roughness = 0.05 + 0.2 * i as f64;
+        let x = -4.0 + 2.0 * i as f64;
+        world.add(Arc::new(Sphere::new(
+            Point3::new(x, 0.8, -1.2),
+            0.8,
+            Arc::new(GgxConductor::new(ComplexIor::gold(), roughness)),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(x, 0.8, 1.2),
+            0.8,
+            Arc::new(GgxDielectric::new(1.5, roughness)),
+        )));
+    }
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-2.0, 0.6, 3.5),
+        0.6,
+        Arc::new(GgxConductor::anisotropic(ComplexIor::copper(), 0.1, 0.5)),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 0.6, 3.5),
+        0.6,
+        Arc::new(GgxConductor::new(ComplexIor::aluminium(), 0.3)),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(2.0, 0.6, 3.5),
+        0.6,
+        Arc::new(GgxDielectric::anisotropic(1.5, 0.05, 0.4)),
+    )));
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 8.0, 4.0),
+        2.0,
+        Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+    )));
+
+    let camera = Camera::new(
+        200,
+        30.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 5.0, 14.0),
+        Point3::new(0.0, 0.5, 0.5),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// Principled spheres sweeping the main parameters: roughness and metallic on the back rows,
+// then sheen, clearcoat, transmission, anisotropy and emission up front.
+fn principled() -> Scene {
+    let mut world = HittableList::default();
+
+    let material_ground = Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    )));
+
+    let red = Arc::new(SolidColor::new(Color::new(0.8, 0.1, 0.1)));
+    let gold = Arc::new(SolidColor::new(Color::new(1.0, 0.78, 0.34)));
+    for i in 0..5 {
+        let t = i as f64 / 4.0;
+        let x = -4.0 + 2.0 * i as f64;
+        world.add(Arc::new(Sphere::new(
+            Point3::new(x, 0.8, -2.4),
+            0.8,
+            Arc::new(Principled::new(red.clone()).with_roughness(solid(t))),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(x, 0.8, 0.0),
+            0.8,
+            Arc::new(
+                Principled::new(gold.clone())
+                    .with_metallic(solid(t))
+                    .with_roughness(solid(0.3)),
+            ),
+        )));
+    }
+
+    let blue = Arc::new(SolidColor::new(Color::new(0.1, 0.2, 0.6)));
+    let front: [Principled; 5] = [
+        Principled::new(blue.clone())
+            .with_roughness(solid(1.0))
+            .with_sheen(solid(1.0))
+            .with_sheen_tint(solid(0.0)),
+        Principled::new(blue.clone())
+            .with_roughness(solid(0.6))
+            .with_clearcoat(solid(1.0))
+            .with_clearcoat_gloss(solid(0.9)),
+        Principled::new(Arc::new(SolidColor::new(Color::new(0.9, 1.0, 0.95))))
+            .with_roughness(solid(0.1))
+            .with_transmission(solid(1.0))
+            .with_ior(solid(1.45)),
+        Principled::new(gold.clone())
+            .with_metallic(solid(1.0))
+            .with_roughness(solid(0.4))
+            .with_anisotropic(solid(0.8)),
+        Principled::new(red.clone())
+            .with_specular(solid(1.0))
+            .with_specular_tint(solid(1.0))
+            .with_emission(Arc::new(SolidColor::new(Color::new(2.0, 0.6, 0.2)))),
+    ];
+    for (i, material) in front.into_iter().enumerate() {
+        world.add(Arc::new(Sphere::new(
+            Point3::new(-4.0 + 2.0 * i as f64, 0.7, 2.4),
+            0.7,
+            Arc::new(material),
+        )));
+    }
+
+    let camera = Camera::new(
+        200,
+        30.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 6.0, 15.0),
+        Point3::new(0.0, 0.5, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// Height map over the sphere's (u, v) parameterization, sampling noise on the sphere itself so
+// that it wraps around without a seam.
+fn sphere_height_map(width: usize, height: usize, frequency: f64) -> Image {
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let phi = 2.0 * std::f64::consts::PI * (x as f64 + 0.5) / width as f64;
+            let theta = std::f64::consts::PI * (1.0 - (y as f64 + 0.5) / height as f64);
+            let p = frequency
+                * Vec3::new(
+                    -phi.cos() * theta.sin(),
+                    -theta.cos(),
+                    phi.sin() * theta.sin(),
+                );
+            let h = value_noise(p.x(), p.y(), p.z());
+            pixels.push(Color::new(h, h, h));
+        }
+    }
+    Image::new(width, height, pixels)
+}
+
+// Tangent-space normal map of rounded square tiles separated by grooves.
+fn tile_normal_map(size: usize) -> Image {
+    let tile_height = |x: f64, y: f64| {
+        let edge = |t: f64| (t - t.floor() - 0.5).abs();
+        let d = 0.5 - edge(x).max(edge(y));
+        (d / 0.06).min(1.0)
+    };
+
+    let step = 1.0 / size as f64;
+    let mut pixels = Vec::with_capacity(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            let (u, v) = ((x as f64 + 0.5) * step, 1.0 - (y as f64 + 0.5) * step);
+            let dhdu = (tile_height(u + step, v) - tile_height(u - step, v)) / (2.0 * step);
+            let dhdv = (tile_height(u, v + step) - tile_height(u, v - step)) / (2.0 * step);
+            let n = Vec3::new(-0.02 * dhdu, -0.02 * dhdv, 1.0).unit();
+            pixels.push(Color::new(
+                0.5 * n.x() + 0.5,
+                0.5 * n.y() + 0.5,
+                0.5 * n.z() + 0.5,
+            ));
+        }
+    }
+    Image::new(size, size, pixels)
+}
+
+// Normal-mapped tiled floor with height-mapped spheres: a rough dielectric and hammered metal.
+fn bumps() -> Scene {
+    let mut world = HittableList::default();
+
+    let tiles: Arc<dyn Material> = Arc::new(BumpMapped::normal_map(
+        Arc::new(
+            Principled::new(Arc::new(SolidColor::new(Color::new(0.6, 0.55, 0.5))))
+                .with_roughness(solid(0.35)),
+        ),
+        Arc::new(ImageTexture::new(tile_normal_map(256))),
+        1.0,
+    ));
+    let corner = |x: f64, z: f64| {
+        let mut vertex = Vertex::new(Point3::new(x, 0.0, z));
+        vertex.uv = Some((x / 2.0, -z / 2.0));
+        vertex
+    };
+    world.add(Arc::new(Triangle::new(
+        [corner(-10.0, 10.0), corner(10.0, 10.0), corner(10.0, -10.0)],
+        tiles.clone(),
+    )));
+    world.add(Arc::new(Triangle::new(
+        [
+            corner(-10.0, 10.0),
+            corner(10.0, -10.0),
+            corner(-10.0, -10.0),
+        ],
+        tiles,
+    )));
+
+    let bumpy = Arc::new(ImageTexture::new(sphere_height_map(512, 256, 12.0)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-1.3, 1.0, 0.0),
+        1.0,
+        Arc::new(BumpMapped::height_map(
+            Arc::new(
+                Principled::new(Arc::new(SolidColor::new(Color::new(0.8, 0.3, 0.1))))
+                    .with_roughness(solid(0.3)),
+            ),
+            bumpy.clone(),
+            0.02,
+        )),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(1.3, 1.0, 0.0),
+        1.0,
+        Arc::new(BumpMapped::height_map(
+            Arc::new(Metal::new(Color::new(0.8, 0.8, 0.85), 0.0)),
+            bumpy,
+            0.01,
+        )),
+    )));
+
+    let camera = Camera::new(
+        200,
+        30.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 3.0, 8.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// Adds the parallelogram q, q + u, q + u + v, q + v as two triangles, with (u, v) texture
+// coordinates running over it once.
+fn add_quad(world: &mut HittableList, q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) {
+    let corner = |s: f64, t: f64| {
+        let mut vertex = Vertex::new(&q + &(s * &u + t * &v));
+        vertex.uv = Some((s, t));
+        vertex
+    };
+    world.add(Arc::new(Triangle::new(
+        [corner(0.0, 0.0), corner(1.0, 0.0), corner(1.0, 1.0)],
+        material.clone(),
+    )));
+    world.add(Arc::new(Triangle::new(
+        [corner(0.0, 0.0), corner(1.0, 1.0), corner(0.0, 1.0)],
+        material,
+    )));
+}
+
+// Grayscale mask image computed from a function of (u, v).
+fn mask_image(size: usize, mask: impl Fn(f64, f64) -> f64) -> Image {
+    let mut pixels = Vec::with_capacity(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            let (u, v) = (
+                (x as f64 + 0.5) / size as f64,
+                1.0 - (y as f64 + 0.5) / size as f64,
+            );
+            let a = mask(u, v);
+            pixels.push(Color::new(a, a, a));
+        }
+    }
+    Image::new(size, size, pixels)
+}
+
+// Cutout transparency: a lattice fence, a leaf-like card with soft edges, a sphere with holes
+// and a half-opaque sphere, all casting soft shadows shaped by their masks.
+fn cutout() -> Scene {
+    let mut world = HittableList::default();
+
+    let material_ground = Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    )));
+
+    let lattice = mask_image(256, |u, v| {
+        let bar = |t: f64| ((t * 8.0).fract() - 0.5).abs() > 0.35;
+        if bar(u + v) || bar(u - v + 1.0) {
+            1.0
+        } else {
+            0.0
+        }
+    });
+    add_quad(
+        &mut world,
+        Point3::new(-4.0, 0.0, -1.5),
+        Vec3::new(8.0, 0.0, 0.0),
+        Vec3::new(0.0, 2.5, 0.0),
+        Arc::new(
+            AlphaMasked::new(
+                Arc::new(LambertianMaterial::new(Color::new(0.45, 0.3, 0.15))),
+                Arc::new(ImageTexture::new(lattice)),
+            )
+            .with_cutoff(0.5),
+        ),
+    );
+
+    // Leaf silhouette whose opacity falls off smoothly towards its rim.
+    let leaf = mask_image(256, |u, v| {
+        let (x, y) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+        let width = 0.6 * (1.0 - y * y);
+        let d = if width > 0.0 { (x / width).abs() } else { 2.0 };
+        (4.0 * (1.0 - d)).clamp(0.0, 1.0)
+    });
+    add_quad(
+        &mut world,
+        Point3::new(-3.5, 0.2, 1.0),
+        Vec3::new(1.5, 0.0, -0.5),
+        Vec3::new(0.3, 2.2, 0.2),
+        Arc::new(AlphaMasked::new(
+            Arc::new(LambertianMaterial::new(Color::new(0.2, 0.5, 0.1))),
+            Arc::new(ImageTexture::new(leaf)),
+        )),
+    );
+
+    let holes = mask_image(256, |u, v| {
+        let (x, y) = ((u * 12.0).fract() - 0.5, (v * 6.0).fract() - 0.5);
+        if x * x + y * y < 0.1 {
+            0.0
+        } else {
+            1.0
+        }
+    });
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 1.0),
+        1.0,
+        Arc::new(
+            AlphaMasked::new(
+                Arc::new(Metal::new(Color::new(0.8, 0.6, 0.3), 0.1)),
+                Arc::new(ImageTexture::new(holes)),
+            )
+            .with_cutoff(0.5),
+        ),
+    )));
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(2.5, 1.0, 1.0),
+        1.0,
+        Arc::new(AlphaMasked::new(
+            Arc::new(LambertianMaterial::new(Color::new(0.1, 0.2, 0.7))),
+            solid(0.5),
+        )),
+    )));
+
+    let camera = Camera::new(
+        200,
+        35.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 3.0, 11.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// Equirectangular HDR sky: a blue gradient over a dim ground with a small, very bright sun at
+// the given elevation in degrees, a stand-in for a sun-lit photographed HDRI.
+fn sun_sky_map(width: usize, height: usize, sun_elevation: f64) -> Image {
+    let elevation = sun_elevation.to_radians();
+    let sun = Vec3::new(0.0, elevation.sin(), -elevation.cos());
+    let sun_cos_radius = 1.5f64.to_radians().cos();
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let phi = 2.0 * std::f64::consts::PI * ((x as f64 + 0.5) / width as f64 - 0.5);
+            let theta = std::f64::consts::PI * (y as f64 + 0.5) / height as f64;
+            let d = Vec3::new(
+                theta.sin() * phi.sin(),
+                theta.cos(),
+                -theta.sin() * phi.cos(),
+            );
+
+            let color = if d.dot(&sun) > sun_cos_radius {
+                Color::new(800.0, 760.0, 680.0)
+            } else if d.y() >= 0.0 {
+                let a = d.y().powf(0.5);
+                (1.0 - a) * Color::new(1.2, 1.2, 1.25) + a * Color::new(0.25, 0.45, 0.9)
+            } else {
+                Color::new(0.2, 0.18, 0.15)
+            };
+            pixels.push(color);
+        }
+    }
+    Image::new(width, height, pixels)
+}
+
+// Diffuse, metallic and glass spheres lit only by an HDR environment with a sun, which next
+// event estimation finds through the map's luminance distribution.
+fn environment() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.7, 0.3, 0.2))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.05)),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Dielectric::new(1.5)),
+    )));
+
+    let camera = Camera::new(
+        100,
+        30.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 2.5, 10.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    let background = Background::Map(
+        EnvironmentMap::new(sun_sky_map(1024, 512, 35.0))
+            .with_rotation(120.0)
+            .with_intensity(0.5),
+    );
+
+    Scene {
+        world,
+        camera,
+        background,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// Adds the axis-aligned box spanning `min` to `max` as six quads.
+fn add_box(world: &mut HittableList, min: Point3, max: Point3, material: Arc<dyn Material>) {
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    let faces = [
+        (
+            Point3::new(min.x(), min.y(), max.z()),
+            dx.clone(),
+            dy.clone(),
+        ),
+        (Point3::new(max.x(), min.y(), max.z()), -&dz, dy.clone()),
+        (Point3::new(max.x(), min.y(), min.z()), -&dx, dy.clone()),
+        (
+            Point3::new(min.x(), min.y(), min.z()),
+            dz.clone(),
+            dy.clone(),
+        ),
+        (Point3::new(min.x(), max.y(), max.z()), dx.clone(), -&dz),
+        (Point3::new(min.x(), min.y(), min.z()), dx, dz),
+    ];
+    for (q, u, v) in faces {
+        add_quad(world, q, u, v, material.clone());
+    }
+}
+
+// Late afternoon sun over a few white blocks, with the sun placed from a location and time.
+fn daylight() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.35, 0.35, 0.33))),
+    )));
+
+    let plaster: Arc<dyn Material> = Arc::new(LambertianMaterial::new(Color::new(0.8, 0.8, 0.78)));
+    add_box(
+        &mut world,
+        Point3::new(-3.0, 0.0, -2.0),
+        Point3::new(-1.0, 3.0, 0.0),
+        plaster.clone(),
+    );
+    add_box(
+        &mut world,
+        Point3::new(-0.5, 0.0, -3.0),
+        Point3::new(2.5, 1.5, -1.0),
+        plaster.clone(),
+    );
+    add_box(
+        &mut world,
+        Point3::new(1.0, 0.0, 0.5),
+        Point3::new(1.6, 2.0, 1.1),
+        plaster,
+    );
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-0.2, 0.6, 1.2),
+        0.6,
+        Arc::new(Metal::new(Color::new(0.9, 0.9, 0.9), 0.02)),
+    )));
+
+    let camera = Camera::new(
+        100,
+        40.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(1.5, 2.0, 8.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    // Munich, 21 June at 17:30 summer time.
+    let background = Background::Sky(Box::new(
+        PhysicalSky::at_location(48.14, 11.58, 2.0, 172, 17.5, 3.0)
+            .with_ground_albedo(Color::new(0.2, 0.2, 0.2)),
+    ));
+
+    Scene {
+        world,
+        camera,
+        background,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+fn lights() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    let wall: Arc<dyn Material> = Arc::new(LambertianMaterial::new(Color::new(0.7, 0.7, 0.7)));
+    add_box(
+        &mut world,
+        Point3::new(-6.0, 0.0, -3.5),
+        Point3::new(6.0, 4.0, -3.0),
+        wall,
+    );
+
+    let white = Arc::new(SolidColor::new(Color::new(0.8, 0.8, 0.8)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-2.5, 1.0, 0.0),
+        1.0,
+        Arc::new(Principled::new(white.clone()).with_roughness(solid(0.7))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, -0.5),
+        1.0,
+        Arc::new(
+            Principled::new(Arc::new(SolidColor::new(Color::new(0.95, 0.64, 0.54))))
+                .with_metallic(solid(1.0))
+                .with_roughness(solid(0.5)),
+        ),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(2.5, 1.0, 0.0),
+        1.0,
+        Arc::new(
+            Principled::new(white)
+                .with_roughness(solid(0.8))
+                .with_sheen(solid(1.0)),
+        ),
+    )));
+
+    let camera = Camera::new(
+        100,
+        40.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 3.0, 10.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    // Dim night sky, so the scene is lit almost entirely by the lights.
+    let background = Background::Map(EnvironmentMap::new(Image::new(
+        1,
+        1,
+        vec![Color::new(0.01, 0.012, 0.02)],
+    )));
+
+    let lights: Vec<Arc<dyn Light>> = vec![
+        // A warm 100 W bulb to the left.
+        Arc::new(PointLight::new(
+            Point3::new(-3.5, 3.0, 2.0),
+            Color::new(1.0, 0.8, 0.6),
+            100.0,
+        )),
+        // A cool 25 W spotlight aimed down at the metal sphere.
+        Arc::new(SpotLight::new(
+            Point3::new(1.0, 6.0, 2.0),
+            Vec3::new(-1.0, -5.0, -2.5),
+            Color::new(0.7, 0.85, 1.0),
+            25.0,
+            10.0,
+            25.0,
+        )),
+        // Faint moonlight from behind the camera.
+        Arc::new(DirectionalLight::new(
+            Vec3::new(-0.3, -1.0, -0.6),
+            Color::new(0.75, 0.8, 1.0),
+            0.05,
+        )),
+    ];
+
+    Scene {
+        world,
+        camera,
+        background,
+        lights,
+        names: Names::default(),
+    }
+}
+
+// Downlight with a bright ring around 25° off the nadir, like a recessed wall washer.
+const DOWNLIGHT_IES: &str = "IESNA:LM-63-2002
+[TEST] synthetic
+[LUMINAIRE] recessed downlight
+TILT=NONE
+1 -1 1.0 19 1 1 2 0.0 0.0 0.0
+1.0 1.0 20
+0 5 10 15 20 25 30 35 40 45 50 55 60 65 70 75 80 85 90
+0
+374 529 853 1342 1818 2002 1753 1216 674 309
+125 51 22 10 4 1 0 0 0
+";
+
+fn ies() -> Scene {
+    let mut world = HittableList::default();
+
+    let plaster: Arc<dyn Material> = Arc::new(LambertianMaterial::new(Color::new(0.75, 0.73, 0.7)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        plaster.clone(),
+    )));
+    add_box(
+        &mut world,
+        Point3::new(-8.0, 0.0, -3.5),
+        Point3::new(8.0, 5.0, -3.0),
+        plaster,
+    );
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 0.7, -1.5),
+        0.7,
+        Arc::new(Principled::new(Arc::new(SolidColor::new(Color::new(
+            0.8, 0.2, 0.1,
+        ))))),
+    )));
+
+    let camera = Camera::new(
+        100,
+        45.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 2.0, 7.0),
+        Point3::new(0.0, 2.0, -3.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    let background = Background::Map(EnvironmentMap::new(Image::new(
+        1,
+        1,
+        vec![Color::new(0.005, 0.005, 0.008)],
+    )));
+
+    // Three fixtures close to the wall, each with two of the profiled lamps, throwing the
+    // profile's scallops onto it. The right one is tilted towards the wall.
+    let profile = Arc::new(IesProfile::parse(DOWNLIGHT_IES, "downlight").unwrap());
+    let down = Vec3::new(0.0, -1.0, 0.0);
+    let lights: Vec<Arc<dyn Light>> = [
+        (-4.0, down.clone()),
+        (0.0, down.clone()),
+        (4.0, Vec3::new(0.0, -1.0, -0.6)),
+    ]
+    .into_iter()
+    .map(|(x, nadir)| {
+        let light: Arc<dyn Light> = Arc::new(
+            PointLight::with_intensity(
+                Point3::new(x, 4.5, -2.5),
+                (2.0 * profile.peak_intensity()) * Color::new(1.0, 0.85, 0.7),
+            )
+            .with_profile(profile.clone(), Onb::new(&nadir)),
+        );
+        light
+    })
+    .collect();
+
+    Scene {
+        world,
+        camera,
+        background,
+        lights,
+        names: Names::default(),
+    }
+}
+
+// A night street of small glowing spheres and a strip of emitting triangles, lit only by them.
+fn many_lights() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    let mut objects: Vec<Arc<dyn Hittable>> = Vec::new();
+    for a in -15..15 {
+        for b in -15..15 {
+            let center = Point3::new(
+                a as f64 + 0.8 * random_f64(),
+                0.1,
+                b as f64 + 0.8 * random_f64(),
+            );
+            objects.push(Arc::new(Sphere::new(
+                center,
+                0.05,
+                Arc::new(DiffuseLight::new(40.0 * random_color_in_interval(0.2, 1.0))),
+            )));
+        }
+    }
+
+    // A long ribbon overhead, split into small emitting triangles.
+    let ribbon: Arc<dyn Material> = Arc::new(DiffuseLight::new(Color::new(3.0, 1.5, 0.6)));
+    for i in 0..200 {
+        let x0 = -10.0 + 0.1 * i as f64;
+        let x1 = x0 + 0.1;
+        let y = |x: f64| 3.0 + 0.5 * (x * 0.7).sin();
+        let corners = [
+            Point3::new(x0, y(x0), -4.0),
+            Point3::new(x1, y(x1), -4.0),
+            Point3::new(x1, y(x1), -3.8),
+            Point3::new(x0, y(x0), -3.8),
+        ];
+        for [a, b, c] in [[0, 1, 2], [0, 2, 3]] {
+            objects.push(Arc::new(Triangle::new(
+                [
+                    Vertex::new(corners[a].clone()),
+                    Vertex::new(corners[b].clone()),
+                    Vertex::new(corners[c].clone()),
+                ],
+                ribbon.clone(),
+            )));
+        }
+    }
+
+    for (center, material) in [
+        (
+            Point3::new(-2.0, 1.0, 0.0),
+            Arc::new(LambertianMaterial::new(Color::new(0.7, 0.7, 0.7))) as Arc<dyn Material>,
+        ),
+        (
+            Point3::new(0.5, 1.0, -1.0),
+            Arc::new(
+                Principled::new(Arc::new(SolidColor::new(Color::new(0.9, 0.9, 0.9))))
+                    .with_metallic(solid(1.0))
+                    .with_roughness(solid(0.4)),
+            ),
+        ),
+        (
+            Point3::new(3.0, 1.0, 0.5),
+            Arc::new(LambertianMaterial::new(Color::new(0.2, 0.4, 0.7))),
+        ),
+    ] {
+        objects.push(Arc::new(Sphere::new(center, 1.0, material)));
+    }
+    world.add(Arc::new(BvhNode::from_objects(objects)));
+
+    let camera = Camera::new(
+        100,
+        40.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 4.0, 12.0),
+        Point3::new(0.0, 0.5, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    let background = Background::Map(EnvironmentMap::new(Image::new(
+        1,
+        1,
+        vec![Color::new(0.002, 0.002, 0.004)],
+    )));
+
+    Scene {
+        world,
+        camera,
+        background,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// Stacked blocks seen from the isometric angle, where all three axes are equally foreshortened.
+fn isometric() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.8, 0.8, 0.8))),
+    )));
+
+    let blocks = [
+        (
+            Point3::new(-2.0, 0.0, -2.0),
+            Point3::new(0.0, 2.0, 0.0),
+            Color::new(0.8, 0.3, 0.2),
+        ),
+        (
+            Point3::new(0.0, 0.0, -2.0),
+            Point3::new(2.0, 1.0, 0.0),
+            Color::new(0.9, 0.7, 0.2),
+        ),
+        (
+            Point3::new(-2.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 2.0),
+            Color::new(0.2, 0.5, 0.8),
+        ),
+        (
+            Point3::new(-2.0, 2.0, -2.0),
+            Point3::new(-1.0, 3.0, -1.0),
+            Color::new(0.3, 0.7, 0.3),
+        ),
+    ];
+    for (min, max, color) in blocks {
+        add_box(
+            &mut world,
+            min,
+            max,
+            Arc::new(LambertianMaterial::new(color)),
+        );
+    }
+    world.add(Arc::new(Sphere::new(
+        Point3::new(1.0, 1.5, -1.0),
+        0.5,
+        Arc::new(
+            Principled::new(Arc::new(SolidColor::new(Color::new(0.9, 0.9, 0.9))))
+                .with_metallic(solid(1.0))
+                .with_roughness(solid(0.2)),
+        ),
+    )));
+
+    let camera = Camera::new(
+        100,
+        20.0,
+        1.0,
+        600,
+        Point3::new(10.0, 10.0, 10.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    )
+    .with_orthographic(7.0);
+
+    let sun = Arc::new(DirectionalLight::new(
+        Vec3::new(-0.5, -1.0, -0.3),
+        Color::new(1.0, 0.95, 0.9),
+        1.0,
+    ));
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: vec![sun],
+        names: Names::default(),
+    }
+}
+
+// A ring of pillars and spheres around the camera under an afternoon sky, rendered as a full
+// spherical panorama.
+fn panorama() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.4, 0.4, 0.38))),
+    )));
+
+    let stone: Arc<dyn Material> = Arc::new(LambertianMaterial::new(Color::new(0.75, 0.72, 0.65)));
+    for k in 0..12 {
+        let angle = 2.0 * std::f64::consts::PI * k as f64 / 12.0;
+        let (x, z) = (6.0 * angle.sin(), -6.0 * angle.cos());
+        if k % 2 == 0 {
+            add_box(
+                &mut world,
+                Point3::new(x - 0.4, 0.0, z - 0.4),
+                Point3::new(x + 0.4, 4.0, z + 0.4),
+                stone.clone(),
+            );
+        } else {
+            let color = Color::new(
+                0.5 + 0.4 * angle.cos(),
+                0.5 + 0.4 * (angle + 2.0).cos(),
+                0.5 + 0.4 * (angle + 4.0).cos(),
+            );
+            world.add(Arc::new(Sphere::new(
+                Point3::new(x, 1.0, z),
+                1.0,
+                Arc::new(
+                    Principled::new(Arc::new(SolidColor::new(color))).with_roughness(solid(0.3)),
+                ),
+            )));
+        }
+    }
+
+    let camera = Camera::new(
+        100,
+        90.0,
+        2.0,
+        1000,
+        Point3::new(0.0, 1.7, 0.0),
+        Point3::new(0.0, 1.7, -1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    )
+    .with_equirectangular();
+
+    let background = Background::Sky(Box::new(PhysicalSky::new(Vec3::new(0.6, 0.5, -0.6), 3.0)));
+
+    Scene {
+        world,
+        camera,
+        background,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// A row of spheres running from in front of the screen to well behind it, converging on the
+// middle one, as a red-cyan anaglyph.
+fn stereo() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    for k in 0..7 {
+        let t = k as f64 / 6.0;
+        let color = Color::new(0.8 - 0.6 * t, 0.3 + 0.4 * t, 0.2 + 0.6 * t);
+        world.add(Arc::new(Sphere::new(
+            Point3::new(-3.0 + 6.0 * t, 0.6, 4.0 - 12.0 * t),
+            0.6,
+            Arc::new(Principled::new(Arc::new(SolidColor::new(color))).with_roughness(solid(0.4))),
+        )));
+    }
+
+    let camera = Camera::new(
+        100,
+        40.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 2.0, 12.0),
+        Point3::new(0.0, 0.6, -2.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    )
+    .with_stereo(0.5, None, StereoLayout::Anaglyph);
+
+    let sun = Arc::new(DirectionalLight::new(
+        Vec3::new(-0.4, -1.0, -0.5),
+        Color::new(1.0, 0.95, 0.9),
+        2.0,
+    ));
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: vec![sun],
+        names: Names::default(),
+    }
+}
+
+// Three spheres in front of a field of small lamps, shot through a double-Gauss 50mm lens wide
+// open and focused on the middle sphere, so the lamps behind turn into discs of bokeh and the
+// image darkens towards the corners. Units are meters.
+fn lens() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.3, 0.3, 0.32))),
+    )));
+
+    for (x, z, color) in [
+        (-0.22, 0.3, Color::new(0.8, 0.3, 0.2)),
+        (0.0, 0.0, Color::new(0.9, 0.8, 0.3)),
+        (0.25, -0.35, Color::new(0.2, 0.5, 0.8)),
+    ] {
+        world.add(Arc::new(Sphere::new(
+            Point3::new(x, 0.12, z),
+            0.12,
+            Arc::new(Principled::new(Arc::new(SolidColor::new(color))).with_roughness(solid(0.3))),
+        )));
+    }
+
+    for k in 0..40 {
+        let x = random_f64_in_interval(-2.5, 2.5);
+        let z = random_f64_in_interval(-10.0, -5.0);
+        let y = random_f64_in_interval(0.3, 1.5);
+        world.add(Arc::new(Sphere::new(
+            Point3::new(x, y, z),
+            0.03,
+            Arc::new(DiffuseLight::new(if k % 3 == 0 {
+                Color::new(60.0, 30.0, 10.0)
+            } else {
+                Color::new(60.0, 50.0, 35.0)
+            })),
+        )));
+    }
+
+    let camera = Camera::new(
+        100,
+        40.0,
+        3.0 / 2.0,
+        600,
+        Point3::new(0.0, 0.3, 1.5),
+        Point3::new(0.0, 0.12, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    )
+    .with_lens(&LensSystem::by_name("dgauss50").unwrap(), 36.0)
+    .unwrap();
+
+    let background = Background::Map(EnvironmentMap::new(Image::new(
+        1,
+        1,
+        vec![Color::new(0.02, 0.02, 0.04)],
+    )));
+    let moon = Arc::new(DirectionalLight::new(
+        Vec3::new(0.3, -1.0, -0.6),
+        Color::new(0.8, 0.85, 1.0),
+        1.5,
+    ));
+
+    Scene {
+        world,
+        camera,
+        background,
+        lights: vec![moon],
+        names: Names::default(),
+    }
+}
+
+// A sphere in focus before a wall of lamps far behind it, seen through a six-bladed aperture
+// whose highlights are clipped into cat's eyes towards the edges of the frame.
+fn bokeh() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.3, 0.3, 0.32))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(
+            Principled::new(Arc::new(SolidColor::new(Color::new(0.8, 0.5, 0.2))))
+                .with_roughness(solid(0.35)),
+        ),
+    )));
+
+    let warm = Arc::new(DiffuseLight::new(Color::new(80.0, 56.0, 28.0)));
+    let cool = Arc::new(DiffuseLight::new(Color::new(32.0, 48.0, 80.0)));
+    for i in 0..13 {
+        for j in 0..7 {
+            let material: Arc<dyn Material> = if (i + j) % 3 == 0 {
+                cool.clone()
+            } else {
+                warm.clone()
+            };
+            world.add(Arc::new(Sphere::new(
+                Point3::new(-24.0 + 4.0 * i as f64, 1.0 + 3.0 * j as f64, -40.0),
+                0.15,
+                material,
+            )));
+        }
+    }
+
+    let camera = Camera::new(
+        100,
+        30.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 1.5, 8.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    )
+    .with_defocus(3.0)
+    .with_aperture(Aperture::polygon(6, 90.0))
+    .with_cats_eye(0.5);
+
+    let background = Background::Map(EnvironmentMap::new(Image::new(
+        1,
+        1,
+        vec![Color::new(0.02, 0.02, 0.03)],
+    )));
+    let moon = Arc::new(DirectionalLight::new(
+        Vec3::new(0.4, -1.0, -0.5),
+        Color::new(0.8, 0.85, 1.0),
+        1.0,
+    ));
+
+    Scene {
+        world,
+        camera,
+        background,
+        lights: vec![moon],
+        names: Names::default(),
+    }
+}
+
+// Spheres bouncing across the floor, a cube sliding past and another spinning and growing,
+// all moving while the shutter is open.
+fn motion_blur() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    for k in 0..5 {
+        let x = -4.0 + 2.0 * k as f64;
+        let color = Color::new(0.2 + 0.15 * k as f64, 0.4, 0.8 - 0.15 * k as f64);
+        world.add(Arc::new(Sphere::moving(
+            Point3::new(x, 0.5, 2.0),
+            Point3::new(x, 0.5 + 0.3 * (k + 1) as f64, 2.0),
+            0.5,
+            Arc::new(LambertianMaterial::new(color)),
+        )));
+    }
+
+    let cube = |color: Color| {
+        let mut cube = HittableList::default();
+        add_box(
+            &mut cube,
+            Point3::new(-0.75, -0.75, -0.75),
+            Point3::new(0.75, 0.75, 0.75),
+            Arc::new(LambertianMaterial::new(color)),
+        );
+        Arc::new(BvhNode::new(cube))
+    };
+    world.add(Arc::new(Transformed::linear(
+        cube(Color::new(0.8, 0.3, 0.2)),
+        Transform::new(Vec3::new(-3.5, 0.75, -1.5)),
+        Transform::new(Vec3::new(-1.5, 0.75, -1.5)),
+    )));
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    // A still cube for comparison.
+    world.add(Arc::new(Transformed::new(
+        cube(Color::new(0.3, 0.7, 0.4)),
+        Transform::new(Vec3::new(0.0, 0.75, -3.0)).with_rotation(&up, 45.0),
+    )));
+    world.add(Arc::new(Transformed::animated(
+        cube(Color::new(0.9, 0.8, 0.3)),
+        Track::new(
+            0.0,
+            Transform::new(Vec3::new(2.5, 0.75, -1.5)),
+            Interpolation::Linear,
+        )
+        .key(
+            0.5,
+            Transform::new(Vec3::new(2.5, 1.0, -1.5))
+                .with_rotation(&up, 30.0)
+                .with_scale(1.3),
+            Interpolation::Linear,
+        )
+        .key(
+            1.0,
+            Transform::new(Vec3::new(2.5, 0.75, -1.5)).with_rotation(&up, 60.0),
+            Interpolation::Linear,
+        ),
+    )));
+
+    let camera = Camera::new(
+        100,
+        30.0,
+        16.0 / 9.0,
+        800,
+        Point3::new(0.0, 3.0, 14.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    )
+    .with_shutter(0.0, 1.0);
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// Four seconds of a cube turning on a pedestal while a ball loops around it, seen from a camera
+// that swoops in and zooms. Meant for `--frames=0..95`.
+fn animation() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+    add_box(
+        &mut world,
+        Point3::new(-1.0, 0.0, -1.0),
+        Point3::new(1.0, 0.5, 1.0),
+        Arc::new(LambertianMaterial::new(Color::new(0.8, 0.8, 0.8))),
+    );
+
+    // A full turn at a steady rate, keyed every quarter turn as each key slerps the short way.
+    let mut cube = HittableList::default();
+    add_box(
+        &mut cube,
+        Point3::new(-0.6, -0.6, -0.6),
+        Point3::new(0.6, 0.6, 0.6),
+        Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.1)),
+    );
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let turntable = (1..=4).fold(
+        Track::new(
+            0.0,
+            Transform::new(Vec3::new(0.0, 1.1, 0.0)),
+            Interpolation::Linear,
+        ),
+        |track, quarter| {
+            track.key(
+                quarter as f64,
+                Transform::new(Vec3::new(0.0, 1.1, 0.0)).with_rotation(&up, 90.0 * quarter as f64),
+                Interpolation::Linear,
+            )
+        },
+    );
+    world.add(Arc::new(Transformed::animated(
+        Arc::new(BvhNode::new(cube)),
+        turntable,
+    )));
+
+    // A smooth loop through a few points around the pedestal.
+    let ball = Arc::new(Sphere::new(
+        Point3::default(),
+        0.35,
+        Arc::new(LambertianMaterial::new(Color::new(0.2, 0.4, 0.9))),
+    ));
+    let path = [
+        Vec3::new(2.5, 0.35, 0.0),
+        Vec3::new(0.0, 1.5, 2.5),
+        Vec3::new(-2.5, 0.35, 0.0),
+        Vec3::new(0.0, 2.5, -2.5),
+        Vec3::new(2.5, 0.35, 0.0),
+    ];
+    let track = path.iter().enumerate().skip(1).fold(
+        Track::new(0.0, Transform::new(path[0].clone()), Interpolation::Bezier),
+        |track, (i, p)| track.key(i as f64, Transform::new(p.clone()), Interpolation::Bezier),
+    );
+    world.add(Arc::new(Transformed::animated(ball, track)));
+
+    let look_from = Track::new(0.0, Point3::new(-10.0, 6.0, 14.0), Interpolation::Bezier)
+        .key(2.0, Point3::new(4.0, 3.0, 10.0), Interpolation::Bezier)
+        .key(4.0, Point3::new(9.0, 2.0, 4.0), Interpolation::Bezier);
+    let vfov = Track::new(0.0, 35.0, Interpolation::Bezier).key(4.0, 25.0, Interpolation::Bezier);
+    let camera = Camera::new(
+        50,
+        35.0,
+        16.0 / 9.0,
+        400,
+        Point3::new(-10.0, 6.0, 14.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    )
+    .with_animated_look_from(look_from)
+    .with_animated_look_at(
+        Track::new(0.0, Point3::new(0.0, 0.5, 0.0), Interpolation::Linear).key(
+            4.0,
+            Point3::new(0.0, 1.1, 0.0),
+            Interpolation::Linear,
+        ),
+    )
+    .with_animated_vfov(vfov);
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// A row of balls half a meter across in midday sun, shot with an 85 mm lens wide open at f/2.8,
+// 1/2000 s and ISO 100 on full frame, focused on the third ball.
+fn exposure() -> Scene {
+    let mut world = HittableList::default();
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(LambertianMaterial::new(Color::new(0.3, 0.3, 0.28))),
+    )));
+    for k in 0..7 {
+        let color = Color::new(0.8 - 0.1 * k as f64, 0.2 + 0.1 * k as f64, 0.3);
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.6 * k as f64 - 1.8, 0.25, -1.5 * k as f64),
+            0.25,
+            Arc::new(LambertianMaterial::new(color)),
+        )));
+    }
+
+    let camera = Camera::new(
+        100,
+        20.0,
+        3.0 / 2.0,
+        600,
+        Point3::new(-3.5, 1.2, 6.0),
+        Point3::new(-0.6, 0.25, -3.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    )
+    .with_settings(&PhotographicSettings {
+        sensor_width: 36.0,
+        focal_length: 85.0,
+        f_number: 2.8,
+        shutter_time: 1.0 / 2000.0,
+        iso: 100.0,
+    });
+
+    // Munich, 21 June at 13:30 summer time.
+    let background = Background::Sky(Box::new(
+        PhysicalSky::at_location(48.14, 11.58, 2.0, 172, 13.5, 3.0)
+            .with_ground_albedo(Color::new(0.2, 0.2, 0.2)),
+    ));
+
+    Scene {
+        world,
+        camera,
+        background,
+        lights: Vec::new(),
+        names: Names::default(),
+    }
+}
+
+// Loads an OBJ or glTF model and frames it from the front, resting on a large ground sphere. A
+// glTF file's own camera is used instead when it has one, animated along with the model.
+pub(crate) fn from_model(path: &str) -> Result<Scene, Error> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let model = match extension.as_deref() {
+        Some("obj") => load_obj(path)?,
+        Some("gltf") | Some("glb") => load_gltf(path)?,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{}: unsupported model format", path),
+            ))
+        }
+    };
+    if model.triangles.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{}: model has no triangles", path),
+        ));
+    }
+
+    let mut scene = framed(Arc::new(BvhNode::new(model.triangles)), model.lights);
+    scene.names = model.names;
+    if let Some(view) = model.camera {
+        let mut camera = Camera::new(
+            100,
+            view.vfov,
+            view.aspect_ratio.unwrap_or(16.0 / 9.0),
+            800,
+            view.look_from,
+            view.look_at,
+            view.vup,
+        );
+        if let Some(view_width) = view.view_width {
+            camera = camera.with_orthographic(view_width);
+        }
+        if let Some((look_from, look_at)) = view.animation {
+            camera = camera
+                .with_animated_look_from(look_from)
+                .with_animated_look_at(look_at);
+        }
+        scene.camera = camera;
+    }
+    Ok(scene)
+}
+
+// Scene around a voxel grid of densities loaded from a Mitsuba `.vol` file, or from a headerless
+// `.raw` file of f32 values with the given resolution. The densities are scaled by
+// `density_scale`. A raw grid fills a box as long as its longest side.
+pub(crate) fn from_volume(
+    path: &str,
+    resolution: Option<[usize; 3]>,
+    density_scale: f64,
+) -> Result<Scene, Error> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let (grid, bounds) = match (extension.as_deref(), resolution) {
+        (Some("vol"), _) => VoxelGrid::load_vol(path)?,
+        (Some("raw"), Some([nx, ny, nz])) => {
+            let longest = nx.max(ny).max(nz) as f64;
+            let bounds = Aabb::new(
+                &Point3::new(0.0, 0.0, 0.0),
+                &Point3::new(
+                    nx as f64 / longest,
+                    ny as f64 / longest,
+                    nz as f64 / longest,
+                ),
+            );
+            (VoxelGrid::load_raw(path, nx, ny, nz)?, bounds)
+        }
+        (Some("raw"), None) => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{}: a raw grid needs its resolution", path),
+            ))
+        }
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{}: unsupported volume format", path),
+            ))
+        }
+    };
+
+    let phase_function = Arc::new(HenyeyGreenstein::new(Color::new(0.95, 0.95, 0.95), 0.6));
+    let medium = HeterogeneousMedium::new(bounds, grid, density_scale, phase_function);
+    Ok(framed(Arc::new(medium), Vec::new()))
+}
+
+// Scene showing `object` standing on a ground, seen from the front and a little above by a
+// camera framing its bounding box.
+fn framed(object: Arc<dyn Hittable>, lights: Vec<Arc<dyn Light>>) -> Scene {
+    let bbox = object.bounding_box();
+    let (min, max) = (bbox.min(), bbox.max());
+    let center = 0.5 * (&min + &max);
+    let radius = 0.5 * (&max - &min).length();
+
+    let mut world = HittableList::default();
+    world.add(object);
+    let ground_radius = 1000.0 * radius;
+    world.add(Arc::new(Sphere::new(
+        Point3::new(center.x(), min.y() - ground_radius, center.z()),
+        ground_radius,
+        Arc::new(LambertianMaterial::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    const VFOV: f64 = 30.0;
+    let distance = 1.1 * radius / (0.5 * VFOV).to_radians().sin();
+    let look_from = &center + distance * Vec3::new(0.0, 0.3, 1.0).unit();
+
+    let camera = Camera::new(
+        100,
+        VFOV,
+        16.0 / 9.0,
+        800,
+        look_from,
+        center,
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+
+    Scene {
+        world,
+        camera,
+        background: Background::Gradient,
+        lights,
+        names: Names::default(),
+    }
+}
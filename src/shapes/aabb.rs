@@ -0,0 +1,106 @@
+use crate::{
+    physics::{Point3, Ray},
+    utils::Interval,
+};
+
+// Axis-aligned bounding box.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Aabb {
+    x: Interval,
+    y: Interval,
+    z: Interval,
+}
+
+impl Aabb {
+    // Treat the two points a and b as extrema for the bounding box, so we don't require a
+    // particular minimum/maximum coordinate order.
+    pub(crate) fn new(a: &Point3, b: &Point3) -> Self {
+        Aabb {
+            x: Interval::new(a.x().min(b.x()), a.x().max(b.x())),
+            y: Interval::new(a.y().min(b.y()), a.y().max(b.y())),
+            z: Interval::new(a.z().min(b.z()), a.z().max(b.z())),
+        }
+    }
+
+    pub(crate) fn surrounding(box0: &Aabb, box1: &Aabb) -> Self {
+        Aabb {
+            x: Interval::enclosing(&box0.x, &box1.x),
+            y: Interval::enclosing(&box0.y, &box1.y),
+            z: Interval::enclosing(&box0.z, &box1.z),
+        }
+    }
+
+    // Return an AABB that has no side narrower than some delta, padding if necessary. Flat
+    // primitives such as axis-aligned triangles would otherwise produce degenerate slabs.
+    pub(crate) fn pad(&self) -> Self {
+        let delta = 0.0001;
+        let pad = |i: Interval| {
+            if i.size() >= delta {
+                i
+            } else {
+                i.expand(delta)
+            }
+        };
+        Aabb {
+            x: pad(self.x),
+            y: pad(self.y),
+            z: pad(self.z),
+        }
+    }
+
+    pub(crate) fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() {
+                0
+            } else {
+                2
+            }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub(crate) fn axis(&self, n: usize) -> Interval {
+        match n {
+            1 => self.y,
+            2 => self.z,
+            _ => self.x,
+        }
+    }
+
+    pub(crate) fn min(&self) -> Point3 {
+        Point3::new(self.x.min, self.y.min, self.z.min)
+    }
+
+    pub(crate) fn max(&self) -> Point3 {
+        Point3::new(self.x.max, self.y.max, self.z.max)
+    }
+
+    // Returns the parametric interval over which the ray is inside the box, if any.
+    pub(crate) fn hit(&self, r: &Ray, ray_t: Interval) -> Option<Interval> {
+        let origin = r.origin();
+        let direction = r.direction();
+
+        let mut t = ray_t;
+        for axis in 0..3 {
+            let ax = self.axis(axis);
+            let inv_d = 1.0 / direction[axis];
+
+            let mut t0 = (ax.min - origin[axis]) * inv_d;
+            let mut t1 = (ax.max - origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t.min = t.min.max(t0);
+            t.max = t.max.min(t1);
+            if t.max <= t.min {
+                return None;
+            }
+        }
+
+        Some(t)
+    }
+}
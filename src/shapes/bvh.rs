@@ -0,0 +1,127 @@
+use std::{cmp::Ordering, sync::Arc};
+
+use crate::{
+    lights::Light,
+    physics::{Material, Ray},
+    shapes::{
+        hittable::{HitRecord, Hittable},
+        Aabb, HittableList,
+    },
+    utils::Interval,
+};
+
+// Bounding volume hierarchy node, built by recursively splitting the objects at the median of
+// the longest axis of their combined bounding box.
+pub(crate) struct BvhNode {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    pub(crate) fn new(list: HittableList) -> Self {
+        Self::from_objects(list.into_objects())
+    }
+
+    pub(crate) fn from_objects(mut objects: Vec<Arc<dyn Hittable>>) -> Self {
+        let bbox = objects.iter().fold(Aabb::default(), |bbox, object| {
+            Aabb::surrounding(&bbox, &object.bounding_box())
+        });
+        let axis = bbox.longest_axis();
+
+        let (left, right): (Arc<dyn Hittable>, Arc<dyn Hittable>) = match objects.len() {
+            0 => panic!("cannot build a BVH over no objects"),
+            1 => (objects[0].clone(), objects[0].clone()),
+            2 => (objects[0].clone(), objects[1].clone()),
+            _ => {
+                objects.sort_by(|a, b| Self::box_compare(a, b, axis));
+                let rest = objects.split_off(objects.len() / 2);
+                (
+                    Arc::new(Self::from_objects(objects)),
+                    Arc::new(Self::from_objects(rest)),
+                )
+            }
+        };
+
+        BvhNode { left, right, bbox }
+    }
+
+    fn box_compare(a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>, axis: usize) -> Ordering {
+        let a_min = a.bounding_box().axis(axis).min;
+        let b_min = b.bounding_box().axis(axis).min;
+        a_min.total_cmp(&b_min)
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.bbox.hit(r, ray_t)?;
+
+        let hit_left = self.left.hit(r, ray_t);
+        let max = hit_left.as_ref().map_or(ray_t.max, |rec| rec.t);
+        let hit_right = self.right.hit(r, Interval::new(ray_t.min, max));
+
+        hit_right.or(hit_left)
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        if self.bbox.hit(r, ray_t).is_none() {
+            return 1.0;
+        }
+
+        let left = self.left.transmittance(r, ray_t);
+        if left <= 0.0 || Arc::ptr_eq(&self.left, &self.right) {
+            return left;
+        }
+        left * self.right.transmittance(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Light>> {
+        let mut lights = self.left.lights();
+        // A node over a single object holds it on both sides.
+        if !Arc::ptr_eq(&self.left, &self.right) {
+            lights.extend(self.right.lights());
+        }
+        lights
+    }
+
+    fn primitives(&self) -> usize {
+        if Arc::ptr_eq(&self.left, &self.right) {
+            self.left.primitives()
+        } else {
+            self.left.primitives() + self.right.primitives()
+        }
+    }
+
+    fn materials(&self, visit: &mut dyn FnMut(&Arc<dyn Material>)) {
+        self.left.materials(visit);
+        if !Arc::ptr_eq(&self.left, &self.right) {
+            self.right.materials(visit);
+        }
+    }
+
+    fn replace_material(
+        &self,
+        old: &Arc<dyn Material>,
+        new: &Arc<dyn Material>,
+    ) -> Option<Arc<dyn Hittable>> {
+        let left = self.left.replace_material(old, new);
+        let right = if Arc::ptr_eq(&self.left, &self.right) {
+            left.clone()
+        } else {
+            self.right.replace_material(old, new)
+        };
+        if left.is_none() && right.is_none() {
+            return None;
+        }
+        Some(Arc::new(BvhNode {
+            left: left.unwrap_or_else(|| self.left.clone()),
+            right: right.unwrap_or_else(|| self.right.clone()),
+            bbox: self.bbox.clone(),
+        }))
+    }
+}
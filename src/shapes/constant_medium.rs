@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use crate::{
+    physics::{Material, Ray, Vec3},
+    shapes::{
+        hittable::{HitRecord, Hittable},
+        Aabb,
+    },
+    utils::{random_f64, Interval},
+};
+
+// Participating medium of uniform density filling the inside of a closed boundary.
+pub(crate) struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub(crate) fn new(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> Self {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let rec1 = self
+            .boundary
+            .hit(r, Interval::new(f64::NEG_INFINITY, f64::INFINITY))?;
+        let rec2 = self
+            .boundary
+            .hit(r, Interval::new(rec1.t + 0.0001, f64::INFINITY))?;
+
+        let t_min = rec1.t.max(ray_t.min).max(0.0);
+        let t_max = rec2.t.min(ray_t.max);
+        if t_min >= t_max {
+            return None;
+        }
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t_max - t_min) * ray_length;
+        let hit_distance = self.neg_inv_density * random_f64().ln();
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(self.phase_function.clone());
+        rec.t = t_min + hit_distance / ray_length;
+        rec.p = r.at(rec.t);
+
+        // Arbitrary, a medium has no surface to face.
+        rec.normal = Vec3::new(1.0, 0.0, 0.0);
+        rec.front_face = true;
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn materials(&self, visit: &mut dyn FnMut(&Arc<dyn Material>)) {
+        visit(&self.phase_function);
+    }
+
+    fn replace_material(
+        &self,
+        old: &Arc<dyn Material>,
+        new: &Arc<dyn Material>,
+    ) -> Option<Arc<dyn Hittable>> {
+        Arc::ptr_eq(&self.phase_function, old).then(|| {
+            Arc::new(ConstantMedium {
+                boundary: self.boundary.clone(),
+                neg_inv_density: self.neg_inv_density,
+                phase_function: new.clone(),
+            }) as _
+        })
+    }
+}
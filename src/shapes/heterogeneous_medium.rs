@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use crate::{
+    physics::{Material, Point3, Ray, Vec3},
+    shapes::{
+        hittable::{HitRecord, Hittable},
+        Aabb, VoxelGrid,
+    },
+    utils::{random_f64, Interval},
+};
+
+// Participating medium whose density is given by a voxel grid stretched over a world-space
+// bounding box. Free-flight distances are sampled with delta tracking against the grid's
+// maximum density, which keeps the estimator unbiased regardless of how the density varies.
+pub(crate) struct HeterogeneousMedium {
+    bounds: Aabb,
+    grid: VoxelGrid,
+    density_scale: f64,
+    max_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl HeterogeneousMedium {
+    pub(crate) fn new(
+        bounds: Aabb,
+        grid: VoxelGrid,
+        density_scale: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> Self {
+        let max_density = grid.max_value() * density_scale;
+        HeterogeneousMedium {
+            bounds,
+            grid,
+            density_scale,
+            max_density,
+            phase_function,
+        }
+    }
+
+    fn density(&self, p: &Point3) -> f64 {
+        let min = self.bounds.min();
+        let extent = self.bounds.max() - &min;
+        let offset = p - &min;
+        let local = Vec3::new(
+            offset.x() / extent.x(),
+            offset.y() / extent.y(),
+            offset.z() / extent.z(),
+        );
+        self.grid.lookup(&local) * self.density_scale
+    }
+
+    // Parametric distance to the next tentative collision against the majorant density.
+    fn free_flight(&self, ray_length: f64) -> f64 {
+        -(1.0 - random_f64()).ln() / (self.max_density * ray_length)
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let t_range = self.bounds.hit(r, ray_t)?;
+        if self.max_density <= 0.0 {
+            return None;
+        }
+
+        let ray_length = r.direction().length();
+        let mut t = t_range.min;
+        loop {
+            t += self.free_flight(ray_length);
+            if t >= t_range.max {
+                return None;
+            }
+
+            let p = r.at(t);
+            if random_f64() * self.max_density < self.density(&p) {
+                let mut rec = HitRecord::new(self.phase_function.clone());
+                rec.t = t;
+                rec.p = p;
+
+                // Arbitrary, a medium has no surface to face.
+                rec.normal = Vec3::new(1.0, 0.0, 0.0);
+                rec.front_face = true;
+
+                return Some(rec);
+            }
+        }
+    }
+
+    // Estimates the fraction of light that passes through the medium along the ray segment
+    // using ratio tracking, so that shadow rays are weighted by it instead of being blocked at a
+    // sampled collision.
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        let Some(t_range) = self.bounds.hit(r, ray_t) else {
+            return 1.0;
+        };
+        if self.max_density <= 0.0 {
+            return 1.0;
+        }
+
+        let ray_length = r.direction().length();
+        let mut transmittance = 1.0;
+        let mut t = t_range.min;
+        loop {
+            t += self.free_flight(ray_length);
+            if t >= t_range.max {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.density(&r.at(t)) / self.max_density;
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds.clone()
+    }
+
+    fn materials(&self, visit: &mut dyn FnMut(&Arc<dyn Material>)) {
+        visit(&self.phase_function);
+    }
+
+    fn replace_material(
+        &self,
+        old: &Arc<dyn Material>,
+        new: &Arc<dyn Material>,
+    ) -> Option<Arc<dyn Hittable>> {
+        Arc::ptr_eq(&self.phase_function, old).then(|| {
+            Arc::new(HeterogeneousMedium {
+                bounds: self.bounds.clone(),
+                grid: self.grid.clone(),
+                density_scale: self.density_scale,
+                max_density: self.max_density,
+                phase_function: new.clone(),
+            }) as _
+        })
+    }
+}
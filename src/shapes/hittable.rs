@@ -1,26 +1,41 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
-    physics::{Material, Point3, Ray, Vec3},
-    utils::Interval,
+    lights::Light,
+    physics::{Material, Onb, Point3, Ray, Vec3},
+    shapes::Aabb,
+    utils::{hash_f64, Interval},
 };
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct HitRecord {
     pub(crate) p: Point3,
     pub(crate) normal: Vec3,
-    pub(crate) material: Rc<dyn Material>,
+    // Partial derivatives of the surface position with respect to u and v, spanning the tangent
+    // plane. Left at zero by hittables without a surface parameterization.
+    pub(crate) dpdu: Vec3,
+    pub(crate) dpdv: Vec3,
+    pub(crate) material: Arc<dyn Material>,
+    // The light this surface belongs to when it emits and takes part in light sampling.
+    pub(crate) light: Option<Arc<dyn Light>>,
     pub(crate) t: f64,
+    pub(crate) u: f64,
+    pub(crate) v: f64,
     pub(crate) front_face: bool,
 }
 
 impl HitRecord {
-    pub(crate) fn new(material: Rc<dyn Material>) -> Self {
+    pub(crate) fn new(material: Arc<dyn Material>) -> Self {
         HitRecord {
             p: Default::default(),
             normal: Default::default(),
+            dpdu: Default::default(),
+            dpdv: Default::default(),
             material,
+            light: None,
             t: Default::default(),
+            u: Default::default(),
+            v: Default::default(),
             front_face: Default::default(),
         }
     }
@@ -33,8 +48,67 @@ impl HitRecord {
             -outward_normal
         };
     }
+
+    // Whether the intersection survives the material's opacity. Partially opaque surfaces are
+    // kept stochastically, hashing the ray and hit point so that testing the same primitive twice,
+    // as a BVH leaf may do, always gives the same answer. Every ray goes through here, shadow rays
+    // included, so cutouts also shape shadows.
+    pub(crate) fn is_opaque(&self, r: &Ray) -> bool {
+        let opacity = self.material.opacity(self);
+        if opacity >= 1.0 {
+            return true;
+        }
+        if opacity <= 0.0 {
+            return false;
+        }
+
+        let (o, d) = (r.origin(), r.direction());
+        hash_f64(&[o.x(), o.y(), o.z(), d.x(), d.y(), d.z(), self.t]) < opacity
+    }
+
+    // Shading frame around the normal with its first axis following dpdu, so that anisotropic
+    // materials and normal maps are oriented consistently with the texture coordinates.
+    pub(crate) fn shading_frame(&self) -> Onb {
+        Onb::from_tangent(&self.normal, &self.dpdu)
+    }
 }
 
-pub(crate) trait Hittable {
+pub(crate) trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord>;
+
+    // Fraction of the light that gets through along the ray segment, for shadow rays. Anything
+    // hit blocks it all, media may let part of it through.
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        if self.hit(r, ray_t).is_some() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb;
+
+    // Emitting shapes within, to be sampled as lights.
+    fn lights(&self) -> Vec<Arc<dyn Light>> {
+        Vec::new()
+    }
+
+    // Number of shapes within, counting each instance of a shared one.
+    fn primitives(&self) -> usize {
+        1
+    }
+
+    // Calls `visit` with the material of every shape within, in a fixed order. A material shared
+    // by several shapes is visited once for each of them.
+    fn materials(&self, _visit: &mut dyn FnMut(&Arc<dyn Material>)) {}
+
+    // Copy in which every shape with the material `old` has `new` instead, sharing whatever
+    // doesn't change. None when no shape within has `old`.
+    fn replace_material(
+        &self,
+        _old: &Arc<dyn Material>,
+        _new: &Arc<dyn Material>,
+    ) -> Option<Arc<dyn Hittable>> {
+        None
+    }
 }
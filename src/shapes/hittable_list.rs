@@ -1,34 +1,120 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
-    physics::Ray,
-    shapes::hittable::{HitRecord, Hittable},
+    lights::Light,
+    physics::{Material, Ray},
+    shapes::{
+        hittable::{HitRecord, Hittable},
+        Aabb,
+    },
     utils::Interval,
 };
 
 #[derive(Default)]
 pub(crate) struct HittableList {
-    objects: Vec<Rc<dyn Hittable>>,
+    objects: Vec<Arc<dyn Hittable>>,
+    bbox: Aabb,
 }
 
 impl HittableList {
-    pub(crate) fn add(&mut self, object: Rc<dyn Hittable>) {
+    pub(crate) fn add(&mut self, object: Arc<dyn Hittable>) {
+        self.bbox = Aabb::surrounding(&self.bbox, &object.bounding_box());
         self.objects.push(object);
     }
-}
 
-impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    pub(crate) fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub(crate) fn into_objects(self) -> Vec<Arc<dyn Hittable>> {
+        self.objects
+    }
+
+    // `Hittable::replace_material` for a list that is to stay one, keeping its objects in order.
+    pub(crate) fn with_replaced_material(
+        &self,
+        old: &Arc<dyn Material>,
+        new: &Arc<dyn Material>,
+    ) -> Option<HittableList> {
+        let replaced: Vec<Option<Arc<dyn Hittable>>> = self
+            .objects
+            .iter()
+            .map(|obj| obj.replace_material(old, new))
+            .collect();
+        if replaced.iter().all(Option::is_none) {
+            return None;
+        }
+        let objects = replaced
+            .into_iter()
+            .zip(&self.objects)
+            .map(|(replaced, obj)| replaced.unwrap_or_else(|| obj.clone()))
+            .collect();
+        Some(HittableList {
+            objects,
+            bbox: self.bbox.clone(),
+        })
+    }
+
+    // The closest hit along with the index of the object it is on.
+    pub(crate) fn hit_object(&self, r: &Ray, ray_t: Interval) -> Option<(usize, HitRecord)> {
         let mut record = None;
         let mut closest_so_far = ray_t.max;
 
-        for obj in &self.objects {
+        for (i, obj) in self.objects.iter().enumerate() {
             if let Some(rec) = obj.hit(r, Interval::new(ray_t.min, closest_so_far)) {
                 closest_so_far = rec.t;
-                record = Some(rec)
+                record = Some((i, rec))
             }
         }
 
         record
     }
 }
+
+impl Hittable for HittableList {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.hit_object(r, ray_t).map(|(_, rec)| rec)
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        let mut transmittance = 1.0;
+        for obj in &self.objects {
+            transmittance *= obj.transmittance(r, ray_t);
+            if transmittance <= 0.0 {
+                break;
+            }
+        }
+        transmittance
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Light>> {
+        self.objects.iter().flat_map(|obj| obj.lights()).collect()
+    }
+
+    fn primitives(&self) -> usize {
+        self.objects.iter().map(|obj| obj.primitives()).sum()
+    }
+
+    fn materials(&self, visit: &mut dyn FnMut(&Arc<dyn Material>)) {
+        for obj in &self.objects {
+            obj.materials(visit);
+        }
+    }
+
+    fn replace_material(
+        &self,
+        old: &Arc<dyn Material>,
+        new: &Arc<dyn Material>,
+    ) -> Option<Arc<dyn Hittable>> {
+        self.with_replaced_material(old, new)
+            .map(|list| Arc::new(list) as _)
+    }
+}
@@ -1,7 +1,21 @@
+mod aabb;
+mod bvh;
+mod constant_medium;
+mod heterogeneous_medium;
 mod hittable;
 mod hittable_list;
 mod sphere;
+mod transform;
+mod triangle;
+mod voxel_grid;
 
+pub(crate) use aabb::Aabb;
+pub(crate) use bvh::BvhNode;
+pub(crate) use constant_medium::ConstantMedium;
+pub(crate) use heterogeneous_medium::HeterogeneousMedium;
 pub(crate) use hittable::{HitRecord, Hittable};
 pub(crate) use hittable_list::HittableList;
 pub(crate) use sphere::Sphere;
+pub(crate) use transform::{Transform, Transformed};
+pub(crate) use triangle::{Triangle, Vertex};
+pub(crate) use voxel_grid::VoxelGrid;
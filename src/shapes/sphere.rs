@@ -1,30 +1,128 @@
-use std::rc::Rc;
+use std::{f64::consts::PI, sync::Arc};
 
 use crate::{
-    physics::{Material, Point3, Ray},
-    shapes::hittable::{HitRecord, Hittable},
-    utils::Interval,
+    lights::{Light, LightBounds, LightSample},
+    physics::{Material, Onb, Point3, Ray, Vec3},
+    shapes::{
+        hittable::{HitRecord, Hittable},
+        Aabb,
+    },
+    utils::{random_f64, Interval},
 };
 
+#[derive(Debug)]
 pub(crate) struct Sphere {
     center: Point3,
+    // How far the center moves between times zero and one, zero for a sphere at rest.
+    motion: Vec3,
     radius: f64,
-    material: Rc<dyn Material>,
+    material: Arc<dyn Material>,
+    // An emitting sphere registers a copy of itself as a light, which its hits report.
+    light: Option<Arc<dyn Light>>,
 }
 
 impl Sphere {
-    pub(crate) fn new(center: Point3, radius: f64, material: Rc<dyn Material>) -> Self {
-        Sphere {
+    pub(crate) fn new(center: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        let mut sphere = Sphere {
             center,
+            motion: Vec3::default(),
             radius,
             material,
+            light: None,
+        };
+        if sphere.power() > 0.0 {
+            sphere.light = Some(Arc::new(Sphere {
+                center: sphere.center.clone(),
+                motion: Vec3::default(),
+                radius: sphere.radius,
+                material: sphere.material.clone(),
+                light: None,
+            }));
+        }
+        sphere
+    }
+
+    // Sphere moving in a straight line from `center0` at time zero to `center1` at time one,
+    // and resting there before and after. A moving sphere is never sampled as a light, as it
+    // is somewhere else at every instant; rays that hit it still find its emission.
+    pub(crate) fn moving(
+        center0: Point3,
+        center1: Point3,
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Sphere {
+            motion: &center1 - &center0,
+            center: center0,
+            radius,
+            material,
+            light: None,
+        }
+    }
+
+    fn center(&self, time: f64) -> Point3 {
+        &self.center + time.clamp(0.0, 1.0) * &self.motion
+    }
+
+    // Luminance of the emitted flux, judging the emission by its value at the top of the sphere.
+    fn power(&self) -> f64 {
+        if self.radius <= 0.0 {
+            return 0.0;
         }
+        let r = Ray::new(
+            &(&self.center + Vec3::new(0.0, 2.0 * self.radius, 0.0)),
+            &Vec3::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        let radiance = self
+            .hit(&r, Interval::new(0.0, f64::INFINITY))
+            .map_or(0.0, |rec| rec.material.emitted(&rec).luminance());
+        4.0 * PI * PI * self.radius * self.radius * radiance
+    }
+
+    // One minus the cosine of the half angle of the cone the sphere fills as seen from `p`, or
+    // None from inside. Computed from the sine so that it stays accurate for distant spheres.
+    fn cone_extent(&self, p: &Point3) -> Option<f64> {
+        let sin2_theta_max = self.radius * self.radius / (&self.center - p).length_squared();
+        (sin2_theta_max < 1.0).then(|| sin2_theta_max / (1.0 + (1.0 - sin2_theta_max).sqrt()))
+    }
+
+    // p: a given point on the sphere of radius one, centered at the origin.
+    // u: returned value [0,1] of angle around the Y axis from X=-1.
+    // v: returned value [0,1] of angle from Y=-1 to Y=+1.
+    fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    // Derivatives of the position with respect to the (u, v) mapping of get_sphere_uv, at the
+    // point with unit outward normal n. dpdv vanishes at the poles.
+    fn tangents(&self, n: &Vec3) -> (Vec3, Vec3) {
+        let dpdu = (2.0 * PI * self.radius) * Vec3::new(n.z(), 0.0, -n.x());
+
+        let sin_theta = (n.x() * n.x() + n.z() * n.z()).sqrt();
+        let dpdv = if sin_theta > 0.0 {
+            let cos_theta = -n.y();
+            (PI * self.radius)
+                * Vec3::new(
+                    n.x() * cos_theta / sin_theta,
+                    sin_theta,
+                    n.z() * cos_theta / sin_theta,
+                )
+        } else {
+            Vec3::default()
+        };
+
+        (dpdu, dpdv)
     }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let oc = r.origin() - &self.center;
+        let center = self.center(r.time());
+        let oc = r.origin() - &center;
         let dir = r.direction();
 
         let a = dir.length_squared();
@@ -38,21 +136,110 @@ impl Hittable for Sphere {
 
         let sqrtd = discriminant.sqrt();
 
-        let mut root = (-half_b - sqrtd) / a;
-        if !ray_t.surrounds(root) {
-            root = (-half_b + sqrtd) / a;
+        // Try the far root as well when the near one is out of range or cut away by the
+        // material's opacity.
+        for root in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
             if !ray_t.surrounds(root) {
-                return None;
+                continue;
+            }
+
+            let mut rec = HitRecord::new(self.material.clone());
+            rec.light = self.light.clone();
+
+            rec.t = root;
+            rec.p = r.at(rec.t);
+
+            let outward_normal = (&rec.p - &center) / self.radius;
+            rec.set_face_normal(r, &outward_normal);
+            (rec.u, rec.v) = Self::get_sphere_uv(&outward_normal);
+            (rec.dpdu, rec.dpdv) = self.tangents(&outward_normal);
+
+            if rec.is_opaque(r) {
+                return Some(rec);
             }
         }
 
-        let mut rec = HitRecord::new(self.material.clone());
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        let end = &self.center + &self.motion;
+        Aabb::surrounding(
+            &Aabb::new(&(&self.center - &rvec), &(&self.center + &rvec)),
+            &Aabb::new(&(&end - &rvec), &(&end + &rvec)),
+        )
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Light>> {
+        self.light.iter().cloned().collect()
+    }
+
+    fn materials(&self, visit: &mut dyn FnMut(&Arc<dyn Material>)) {
+        visit(&self.material);
+    }
+
+    fn replace_material(
+        &self,
+        old: &Arc<dyn Material>,
+        new: &Arc<dyn Material>,
+    ) -> Option<Arc<dyn Hittable>> {
+        if !Arc::ptr_eq(&self.material, old) {
+            return None;
+        }
+        let sphere = if self.motion.length_squared() == 0.0 {
+            Sphere::new(self.center.clone(), self.radius, new.clone())
+        } else {
+            Sphere::moving(
+                self.center.clone(),
+                &self.center + &self.motion,
+                self.radius,
+                new.clone(),
+            )
+        };
+        Some(Arc::new(sphere))
+    }
+}
+
+// Emitting spheres are sampled uniformly over the cone of directions they fill, or over all
+// directions from inside.
+impl Light for Sphere {
+    fn sample(&self, p: &Point3) -> Option<LightSample> {
+        let wi = match self.cone_extent(p) {
+            Some(extent) => {
+                let cos_theta = 1.0 - random_f64() * extent;
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let phi = 2.0 * PI * random_f64();
+                Onb::new(&(&self.center - p)).local(
+                    sin_theta * phi.cos(),
+                    sin_theta * phi.sin(),
+                    cos_theta,
+                )
+            }
+            None => Vec3::new_random_unit(),
+        };
+
+        let rec = self.hit(&Ray::new(p, &wi, 0.0), Interval::new(0.0, f64::INFINITY))?;
+        Some(LightSample {
+            wi,
+            radiance: rec.material.emitted(&rec),
+            pdf: self.pdf(p, &rec),
+            distance: rec.t,
+            surface: Some(rec),
+        })
+    }
 
-        rec.t = root;
-        rec.p = r.at(rec.t);
+    fn pdf(&self, p: &Point3, _hit: &HitRecord) -> f64 {
+        match self.cone_extent(p) {
+            Some(extent) => 1.0 / (2.0 * PI * extent),
+            None => 1.0 / (4.0 * PI),
+        }
+    }
 
-        let outward_normal = (&rec.p - &self.center) / self.radius;
-        rec.set_face_normal(r, &outward_normal);
-        Some(rec)
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::omnidirectional(
+            self.bounding_box(),
+            self.power(),
+        ))
     }
 }
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use crate::{
+    animation::{Animatable, Interpolation, Track},
+    physics::{Material, Point3, Quaternion, Ray, Vec3},
+    shapes::{
+        hittable::{HitRecord, Hittable},
+        Aabb,
+    },
+    utils::Interval,
+};
+
+// Steps per keyframe interval at which the motion is bounded.
+const BOUND_STEPS: usize = 16;
+
+// Placement of an object: a uniform scale, then a rotation about the origin, then a translation.
+#[derive(Clone, Debug)]
+pub(crate) struct Transform {
+    translation: Vec3,
+    rotation: Quaternion,
+    scale: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: Vec3::default(),
+            rotation: Quaternion::default(),
+            scale: 1.0,
+        }
+    }
+}
+
+impl Transform {
+    pub(crate) fn new(translation: Vec3) -> Self {
+        Transform {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    // Adds a rotation by `degrees` about `axis`, applied before the translation.
+    pub(crate) fn with_rotation(mut self, axis: &Vec3, degrees: f64) -> Self {
+        self.rotation = self
+            .rotation
+            .then(&Quaternion::from_axis_angle(axis, degrees));
+        self
+    }
+
+    // Adds a rotation given as a quaternion, applied before the translation.
+    pub(crate) fn with_quaternion(mut self, rotation: &Quaternion) -> Self {
+        self.rotation = self.rotation.then(rotation);
+        self
+    }
+
+    pub(crate) fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub(crate) fn rotation(&self) -> Quaternion {
+        self.rotation
+    }
+
+    pub(crate) fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    fn point(&self, p: &Point3) -> Point3 {
+        &self.translation + self.scale * self.rotation.rotate(p)
+    }
+
+    fn vector(&self, v: &Vec3) -> Vec3 {
+        self.scale * self.rotation.rotate(v)
+    }
+
+    pub(crate) fn inverse_point(&self, p: &Point3) -> Point3 {
+        self.inverse_vector(&(p - &self.translation))
+    }
+
+    pub(crate) fn inverse_vector(&self, v: &Vec3) -> Vec3 {
+        self.rotation.inverse().rotate(v) / self.scale
+    }
+}
+
+// Blends the translation and scale linearly and the rotation along the shortest arc.
+impl Animatable for Transform {
+    fn lerp(&self, other: &Transform, t: f64) -> Transform {
+        Transform {
+            translation: self.translation.lerp(&other.translation, t),
+            rotation: self.rotation.lerp(&other.rotation, t),
+            scale: self.scale.lerp(&other.scale, t),
+        }
+    }
+}
+
+// An object placed by a transform that may change over time, following a track of keyframes.
+// Emitting objects inside aren't sampled as lights, as their copies only know where they are
+// in the object's own space; rays that hit them still find their emission.
+pub(crate) struct Transformed {
+    object: Arc<dyn Hittable>,
+    track: Track<Transform>,
+    bbox: Aabb,
+}
+
+impl Transformed {
+    pub(crate) fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        Self::animated(object, Track::new(0.0, transform, Interpolation::Linear))
+    }
+
+    // Moves the object from `from` at time zero to `to` at time one.
+    pub(crate) fn linear(object: Arc<dyn Hittable>, from: Transform, to: Transform) -> Self {
+        Self::animated(
+            object,
+            Track::new(0.0, from, Interpolation::Linear).key(1.0, to, Interpolation::Linear),
+        )
+    }
+
+    pub(crate) fn animated(object: Arc<dyn Hittable>, track: Track<Transform>) -> Self {
+        // Bound the object at steps through each interval, and pad for how far a rotation or a
+        // curved path can carry it off the chord between two steps.
+        let object_bbox = object.bounding_box();
+        let (min, max) = (object_bbox.min(), object_bbox.max());
+        let corners: Vec<Point3> = (0..8)
+            .map(|i| {
+                Point3::new(
+                    if i & 1 == 0 { min.x() } else { max.x() },
+                    if i & 2 == 0 { min.y() } else { max.y() },
+                    if i & 4 == 0 { min.z() } else { max.z() },
+                )
+            })
+            .collect();
+        let radius = corners.iter().map(|c| c.length()).fold(0.0, f64::max);
+        let bound = |transform: &Transform| {
+            corners.iter().fold(Aabb::default(), |bbox, c| {
+                let p = transform.point(c);
+                Aabb::surrounding(&bbox, &Aabb::new(&p, &p))
+            })
+        };
+
+        let times: Vec<f64> = track.times().collect();
+        let mut bbox = bound(&track.at(times[0]));
+        let mut pad: f64 = 0.0;
+        for pair in times.windows(2) {
+            let step = (pair[1] - pair[0]) / BOUND_STEPS as f64;
+            for i in 0..BOUND_STEPS {
+                let t = pair[0] + i as f64 * step;
+                let (a, mid, b) = (track.at(t), track.at(t + 0.5 * step), track.at(t + step));
+                bbox = Aabb::surrounding(&bbox, &bound(&b));
+                let angle = a.rotation.angle_to(&b.rotation);
+                let scale = a.scale.max(b.scale);
+                let bend = (&mid.translation - 0.5 * (&a.translation + &b.translation)).length();
+                pad = pad.max(scale * radius * (1.0 - (0.5 * angle).cos()) + 2.0 * bend);
+            }
+        }
+        let bbox = Aabb::new(
+            &(bbox.min() - Vec3::new(pad, pad, pad)),
+            &(bbox.max() + Vec3::new(pad, pad, pad)),
+        );
+
+        Transformed {
+            object,
+            track,
+            bbox,
+        }
+    }
+
+    // The ray in the object's space. Scaling the direction along with the origin keeps the ray
+    // parameter the same in both spaces.
+    fn local_ray(transform: &Transform, r: &Ray) -> Ray {
+        Ray::new(
+            &transform.inverse_point(&r.origin()),
+            &transform.inverse_vector(&r.direction()),
+            r.time(),
+        )
+    }
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let transform = self.track.at(r.time());
+        let mut rec = self.object.hit(&Self::local_ray(&transform, r), ray_t)?;
+
+        rec.p = transform.point(&rec.p);
+        rec.normal = transform.rotation.rotate(&rec.normal);
+        rec.dpdu = transform.vector(&rec.dpdu);
+        rec.dpdv = transform.vector(&rec.dpdv);
+        rec.light = None;
+        Some(rec)
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        let transform = self.track.at(r.time());
+        self.object
+            .transmittance(&Self::local_ray(&transform, r), ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+
+    fn primitives(&self) -> usize {
+        self.object.primitives()
+    }
+
+    fn materials(&self, visit: &mut dyn FnMut(&Arc<dyn Material>)) {
+        self.object.materials(visit);
+    }
+
+    fn replace_material(
+        &self,
+        old: &Arc<dyn Material>,
+        new: &Arc<dyn Material>,
+    ) -> Option<Arc<dyn Hittable>> {
+        let object = self.object.replace_material(old, new)?;
+        Some(Arc::new(Transformed {
+            object,
+            track: self.track.clone(),
+            bbox: self.bbox.clone(),
+        }))
+    }
+}
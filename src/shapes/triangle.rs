@@ -0,0 +1,262 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    lights::{Light, LightBounds, LightSample},
+    physics::{Material, Point3, Ray, Vec3},
+    shapes::{
+        hittable::{HitRecord, Hittable},
+        Aabb,
+    },
+    utils::{random_f64, Interval},
+};
+
+// Per-vertex attributes of a triangle.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Vertex {
+    pub(crate) p: Point3,
+    pub(crate) normal: Option<Vec3>,
+    pub(crate) uv: Option<(f64, f64)>,
+}
+
+impl Vertex {
+    pub(crate) fn new(p: Point3) -> Self {
+        Vertex {
+            p,
+            normal: None,
+            uv: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Triangle {
+    vertices: [Vertex; 3],
+    material: Arc<dyn Material>,
+    bbox: Aabb,
+    // An emitting triangle registers a copy of itself as a light, which its hits report.
+    light: Option<Arc<dyn Light>>,
+}
+
+impl Triangle {
+    pub(crate) fn new(vertices: [Vertex; 3], material: Arc<dyn Material>) -> Self {
+        let bbox = Aabb::surrounding(
+            &Aabb::new(&vertices[0].p, &vertices[1].p),
+            &Aabb::new(&vertices[0].p, &vertices[2].p),
+        )
+        .pad();
+
+        let mut triangle = Triangle {
+            vertices,
+            material,
+            bbox,
+            light: None,
+        };
+        if triangle.power() > 0.0 {
+            triangle.light = Some(Arc::new(Triangle {
+                vertices: triangle.vertices.clone(),
+                material: triangle.material.clone(),
+                bbox: triangle.bbox.clone(),
+                light: None,
+            }));
+        }
+        triangle
+    }
+
+    fn edges(&self) -> (Vec3, Vec3) {
+        let [v0, v1, v2] = &self.vertices;
+        (&v1.p - &v0.p, &v2.p - &v0.p)
+    }
+
+    fn area(&self) -> f64 {
+        let (edge1, edge2) = self.edges();
+        0.5 * edge1.cross(&edge2).length()
+    }
+
+    fn geometric_normal(&self) -> Vec3 {
+        let (edge1, edge2) = self.edges();
+        edge1.cross(&edge2).unit()
+    }
+
+    // Luminance of the emitted flux, judging the emission of each side by its value at the
+    // centroid.
+    fn power(&self) -> f64 {
+        if self.area() <= 0.0 {
+            return 0.0;
+        }
+        let [v0, v1, v2] = &self.vertices;
+        let centroid = (&v0.p + &v1.p + &v2.p) / 3.0;
+        let n = self.geometric_normal();
+        let radiance: f64 = [n.clone(), -n]
+            .iter()
+            .filter_map(|n| {
+                let r = Ray::new(&(&centroid + n), &-n, 0.0);
+                self.hit(&r, Interval::new(0.0, f64::INFINITY))
+            })
+            .map(|rec| rec.material.emitted(&rec).luminance())
+            .sum();
+        PI * self.area() * radiance
+    }
+
+    // Solves for dpdu and dpdv from the position and texture coordinate differences along two
+    // edges. Returns None when the texture coordinates are degenerate.
+    fn tangents(vertices: &[Vertex; 3], uv: [(f64, f64); 3]) -> Option<(Vec3, Vec3)> {
+        let (du02, dv02) = (uv[0].0 - uv[2].0, uv[0].1 - uv[2].1);
+        let (du12, dv12) = (uv[1].0 - uv[2].0, uv[1].1 - uv[2].1);
+        let dp02 = &vertices[0].p - &vertices[2].p;
+        let dp12 = &vertices[1].p - &vertices[2].p;
+
+        let det = du02 * dv12 - dv02 * du12;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let dpdu = inv_det * (dv12 * &dp02 - dv02 * &dp12);
+        let dpdv = inv_det * (du02 * &dp12 - du12 * &dp02);
+        Some((dpdu, dpdv))
+    }
+}
+
+impl Hittable for Triangle {
+    // Möller-Trumbore intersection.
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let [v0, v1, v2] = &self.vertices;
+        let edge1 = &v1.p - &v0.p;
+        let edge2 = &v2.p - &v0.p;
+
+        let dir = r.direction();
+        let pvec = dir.cross(&edge2);
+        let det = edge1.dot(&pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = r.origin() - &v0.p;
+        let b1 = tvec.dot(&pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+
+        let qvec = tvec.cross(&edge1);
+        let b2 = dir.dot(&qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(&qvec) * inv_det;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let b0 = 1.0 - b1 - b2;
+        let mut rec = HitRecord::new(self.material.clone());
+        rec.light = self.light.clone();
+        rec.t = t;
+        rec.p = r.at(t);
+
+        let geometric_normal = edge1.cross(&edge2).unit();
+        rec.set_face_normal(r, &geometric_normal);
+
+        if let (Some(n0), Some(n1), Some(n2)) = (&v0.normal, &v1.normal, &v2.normal) {
+            let shading_normal = (b0 * n0 + b1 * n1 + b2 * n2).unit();
+            rec.normal = if rec.front_face {
+                shading_normal
+            } else {
+                -shading_normal
+            };
+        }
+
+        // Without texture coordinates the barycentrics serve as (u, v), whose derivatives are
+        // the edges themselves.
+        (rec.u, rec.v, rec.dpdu, rec.dpdv) = match (v0.uv, v1.uv, v2.uv) {
+            (Some(uv0), Some(uv1), Some(uv2)) => {
+                let (dpdu, dpdv) = Self::tangents(&self.vertices, [uv0, uv1, uv2])
+                    .unwrap_or((edge1.clone(), edge2.clone()));
+                (
+                    b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+                    b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+                    dpdu,
+                    dpdv,
+                )
+            }
+            _ => (b1, b2, edge1, edge2),
+        };
+
+        rec.is_opaque(r).then_some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Light>> {
+        self.light.iter().cloned().collect()
+    }
+
+    fn materials(&self, visit: &mut dyn FnMut(&Arc<dyn Material>)) {
+        visit(&self.material);
+    }
+
+    fn replace_material(
+        &self,
+        old: &Arc<dyn Material>,
+        new: &Arc<dyn Material>,
+    ) -> Option<Arc<dyn Hittable>> {
+        Arc::ptr_eq(&self.material, old)
+            .then(|| Arc::new(Triangle::new(self.vertices.clone(), new.clone())) as _)
+    }
+}
+
+// Emitting triangles are sampled uniformly by area.
+impl Light for Triangle {
+    fn sample(&self, p: &Point3) -> Option<LightSample> {
+        let [v0, v1, v2] = &self.vertices;
+        let su0 = random_f64().sqrt();
+        let b0 = 1.0 - su0;
+        let b1 = random_f64() * su0;
+        let q = b0 * &v0.p + b1 * &v1.p + (1.0 - b0 - b1) * &v2.p;
+
+        let to_light = q - p;
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let rec = self.hit(
+            &Ray::new(p, &to_light, 0.0),
+            Interval::new(0.0, f64::INFINITY),
+        )?;
+        let pdf = self.pdf(p, &rec);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            wi: to_light / distance,
+            radiance: rec.material.emitted(&rec),
+            pdf,
+            distance,
+            surface: Some(rec),
+        })
+    }
+
+    fn pdf(&self, p: &Point3, hit: &HitRecord) -> f64 {
+        let to_light = &hit.p - p;
+        let distance_squared = to_light.length_squared();
+        let cos_theta = self.geometric_normal().dot(&to_light.unit()).abs();
+        if cos_theta <= 0.0 || distance_squared <= 0.0 {
+            return 0.0;
+        }
+        distance_squared / (cos_theta * self.area())
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::new(
+            self.bbox.clone(),
+            self.power(),
+            self.geometric_normal(),
+            1.0,
+            0.0,
+            true,
+        ))
+    }
+}
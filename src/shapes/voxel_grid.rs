@@ -0,0 +1,303 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+};
+
+use crate::{
+    physics::{Point3, Vec3},
+    shapes::Aabb,
+};
+
+// Dense grid of scalar densities. Voxels are stored with x varying fastest, then y, then z,
+// and are treated as cell-centred samples when interpolating.
+#[derive(Clone)]
+pub(crate) struct VoxelGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    data: Vec<f64>,
+}
+
+impl VoxelGrid {
+    pub(crate) fn new(nx: usize, ny: usize, nz: usize, data: Vec<f64>) -> Result<Self, Error> {
+        let count = nx.checked_mul(ny).and_then(|n| n.checked_mul(nz));
+        if nx == 0 || ny == 0 || nz == 0 || count != Some(data.len()) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "voxel grid {}x{}x{} does not match {} values",
+                    nx,
+                    ny,
+                    nz,
+                    data.len()
+                ),
+            ));
+        }
+        // Ratio tracking bounds the medium by the largest density, which a NaN or a negative
+        // value would break.
+        if let Some(i) = data.iter().position(|d| !d.is_finite() || *d < 0.0) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("voxel {} has density {}", i, data[i]),
+            ));
+        }
+
+        Ok(VoxelGrid { nx, ny, nz, data })
+    }
+
+    // Loads a headerless file of little-endian f32 densities with the given resolution.
+    pub(crate) fn load_raw(path: &str, nx: usize, ny: usize, nz: usize) -> Result<Self, Error> {
+        let bytes = fs::read(path)?;
+        let data = bytes
+            .chunks_exact(4)
+            .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f64)
+            .collect();
+        Self::new(nx, ny, nz, data)
+    }
+
+    // Loads a dense grid in the Mitsuba `.vol` format. Only float32 and uint8 encodings are
+    // supported; for multi-channel grids the first channel is used as density. The bounding box
+    // stored in the file is returned alongside the grid.
+    pub(crate) fn load_vol(path: &str) -> Result<(Self, Aabb), Error> {
+        let bytes = fs::read(path)?;
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, msg));
+
+        if bytes.len() < 48 || &bytes[0..3] != b"VOL" || bytes[3] != 3 {
+            return Err(invalid("not a version 3 VOL file"));
+        }
+
+        let read_i32 = |offset: usize| {
+            i32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        let read_f32 = |offset: usize| {
+            f32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ]) as f64
+        };
+
+        let encoding = read_i32(4);
+        let dims = [read_i32(8), read_i32(12), read_i32(16)];
+        let channels = read_i32(20);
+        if dims.iter().any(|&d| d <= 0) || channels <= 0 {
+            return Err(invalid("bad grid resolution"));
+        }
+        let (nx, ny, nz) = (dims[0] as usize, dims[1] as usize, dims[2] as usize);
+        let channels = channels as usize;
+
+        let bounds = Aabb::new(
+            &Point3::new(read_f32(24), read_f32(28), read_f32(32)),
+            &Point3::new(read_f32(36), read_f32(40), read_f32(44)),
+        );
+
+        let payload = &bytes[48..];
+        // Sizes from the header can be large enough to overflow.
+        let count = nx
+            .checked_mul(ny)
+            .and_then(|n| n.checked_mul(nz))
+            .ok_or_else(|| invalid("grid too large"))?;
+        let values = count
+            .checked_mul(channels)
+            .ok_or_else(|| invalid("grid too large"))?;
+        let data = match encoding {
+            1 => {
+                if payload.len() / 4 < values {
+                    return Err(invalid("truncated voxel data"));
+                }
+                (0..count)
+                    .map(|i| {
+                        let o = i * channels * 4;
+                        f32::from_le_bytes([
+                            payload[o],
+                            payload[o + 1],
+                            payload[o + 2],
+                            payload[o + 3],
+                        ]) as f64
+                    })
+                    .collect()
+            }
+            3 => {
+                if payload.len() < values {
+                    return Err(invalid("truncated voxel data"));
+                }
+                (0..count)
+                    .map(|i| payload[i * channels] as f64 / 255.0)
+                    .collect()
+            }
+            _ => return Err(invalid("unsupported encoding")),
+        };
+
+        Ok((Self::new(nx, ny, nz, data)?, bounds))
+    }
+
+    pub(crate) fn max_value(&self) -> f64 {
+        self.data.iter().cloned().fold(0.0, f64::max)
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.data[(z * self.ny + y) * self.nx + x]
+    }
+
+    // Trilinearly interpolated density at a point given in the grid's [0,1]^3 local space.
+    pub(crate) fn lookup(&self, local: &Vec3) -> f64 {
+        let dims = [self.nx, self.ny, self.nz];
+
+        let mut i0 = [0; 3];
+        let mut i1 = [0; 3];
+        let mut f = [0.0; 3];
+        for axis in 0..3 {
+            let g = (local[axis] * dims[axis] as f64 - 0.5).clamp(0.0, (dims[axis] - 1) as f64);
+            i0[axis] = g.floor() as usize;
+            i1[axis] = (i0[axis] + 1).min(dims[axis] - 1);
+            f[axis] = g - i0[axis] as f64;
+        }
+
+        let lerp = |a: f64, b: f64, t: f64| (1.0 - t) * a + t * b;
+
+        let c00 = lerp(
+            self.voxel(i0[0], i0[1], i0[2]),
+            self.voxel(i1[0], i0[1], i0[2]),
+            f[0],
+        );
+        let c10 = lerp(
+            self.voxel(i0[0], i1[1], i0[2]),
+            self.voxel(i1[0], i1[1], i0[2]),
+            f[0],
+        );
+        let c01 = lerp(
+            self.voxel(i0[0], i0[1], i1[2]),
+            self.voxel(i1[0], i0[1], i1[2]),
+            f[0],
+        );
+        let c11 = lerp(
+            self.voxel(i0[0], i1[1], i1[2]),
+            self.voxel(i1[0], i1[1], i1[2]),
+            f[0],
+        );
+
+        lerp(lerp(c00, c10, f[1]), lerp(c01, c11, f[1]), f[2])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A `.vol` file with the given encoding, resolution and channel count, bounded by
+    // (0, 0, 0)-(1, 2, 3), followed by `payload`.
+    fn vol(encoding: i32, dims: [i32; 3], channels: i32, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = b"VOL\x03".to_vec();
+        for value in [encoding, dims[0], dims[1], dims[2], channels] {
+            bytes.extend(value.to_le_bytes());
+        }
+        for value in [0.0f32, 0.0, 0.0, 1.0, 2.0, 3.0] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(payload);
+        bytes
+    }
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    // Loads `bytes` from a file named after the test, so that tests running at once don't
+    // share one.
+    fn load(name: &str, bytes: &[u8]) -> Result<(VoxelGrid, Aabb), Error> {
+        let path = std::env::temp_dir().join(format!(
+            "ray_tracer_vol_{}_{}.vol",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, bytes).unwrap();
+        let grid = VoxelGrid::load_vol(&path.to_string_lossy());
+        fs::remove_file(&path).unwrap();
+        grid
+    }
+
+    #[test]
+    fn loads_float_densities() {
+        let (grid, bounds) = load("float", &vol(1, [2, 1, 1], 1, &floats(&[0.5, 2.0]))).unwrap();
+        assert_eq!((grid.nx, grid.ny, grid.nz), (2, 1, 1));
+        assert_eq!(grid.data, [0.5, 2.0]);
+        assert_eq!(grid.max_value(), 2.0);
+        assert_eq!(bounds.max().z(), 3.0);
+    }
+
+    #[test]
+    fn takes_the_first_channel_as_density() {
+        let payload = floats(&[0.5, 9.0, 9.0, 1.5, 9.0, 9.0]);
+        let (grid, _) = load("channels", &vol(1, [1, 2, 1], 3, &payload)).unwrap();
+        assert_eq!(grid.data, [0.5, 1.5]);
+    }
+
+    #[test]
+    fn scales_byte_densities() {
+        let (grid, _) = load("bytes", &vol(3, [1, 1, 2], 2, &[255, 7, 51, 7])).unwrap();
+        assert_eq!(grid.data, [1.0, 0.2]);
+    }
+
+    #[test]
+    fn interpolates_between_voxel_centres() {
+        let grid = VoxelGrid::new(2, 1, 1, vec![1.0, 3.0]).unwrap();
+        assert_eq!(grid.lookup(&Vec3::new(0.25, 0.5, 0.5)), 1.0);
+        assert_eq!(grid.lookup(&Vec3::new(0.5, 0.5, 0.5)), 2.0);
+        // Densities hold past the outermost centres.
+        assert_eq!(grid.lookup(&Vec3::new(1.0, 0.0, 1.0)), 3.0);
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        let mut version_2 = vol(1, [1, 1, 1], 1, &floats(&[1.0]));
+        version_2[3] = 2;
+        for (i, (bytes, message)) in [
+            (b"VOL\x03".to_vec(), "not a version 3 VOL file"),
+            (b"XYZ\x03".repeat(13), "not a version 3 VOL file"),
+            (version_2, "not a version 3 VOL file"),
+            (vol(1, [1, 0, 1], 1, &[]), "bad grid resolution"),
+            (vol(1, [1, 1, 1], -1, &[]), "bad grid resolution"),
+            (vol(1, [i32::MAX; 3], 1, &[]), "grid too large"),
+            (
+                vol(1, [1 << 20, 1 << 20, 1 << 20], 1 << 30, &[]),
+                "grid too large",
+            ),
+            (
+                vol(1, [2, 1, 1], 1, &floats(&[1.0])),
+                "truncated voxel data",
+            ),
+            (vol(3, [2, 1, 1], 2, &[1, 2, 3]), "truncated voxel data"),
+            (vol(2, [1, 1, 1], 1, &[0, 0]), "unsupported encoding"),
+            (
+                vol(1, [2, 1, 1], 1, &floats(&[1.0, -1.0])),
+                "voxel 1 has density -1",
+            ),
+            (
+                vol(1, [1, 1, 1], 1, &floats(&[f32::NAN])),
+                "voxel 0 has density NaN",
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let e = load(&format!("broken_{}", i), &bytes).err().unwrap();
+            assert!(
+                e.to_string().contains(message),
+                "expected {}, got {}",
+                message,
+                e
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_a_missing_file() {
+        assert!(VoxelGrid::load_vol("/nonexistent/cloud.vol").is_err());
+    }
+}
@@ -0,0 +1,42 @@
+use crate::{
+    image::Image,
+    physics::{Color, Point3},
+    textures::Texture,
+};
+
+// Image mapped over the (u, v) square with repeat wrapping and bilinear filtering. v runs from
+// the bottom of the image to its top.
+#[derive(Debug)]
+pub(crate) struct ImageTexture {
+    image: Image,
+}
+
+impl ImageTexture {
+    pub(crate) fn new(image: Image) -> Self {
+        ImageTexture { image }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        let (width, height) = (self.image.width(), self.image.height());
+        if width == 0 || height == 0 {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        let x = (u - u.floor()) * width as f64 - 0.5;
+        let y = (1.0 - (v - v.floor())) * height as f64 - 0.5;
+
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let wrap = |i: f64, n: usize| i.rem_euclid(n as f64) as usize;
+        let (x0, x1) = (wrap(x0, width), wrap(x0 + 1.0, width));
+        let (y0, y1) = (wrap(y0, height), wrap(y0 + 1.0, height));
+
+        (1.0 - fy)
+            * ((1.0 - fx) * self.image.pixel(x0, y0).clone()
+                + fx * self.image.pixel(x1, y0).clone())
+            + fy * ((1.0 - fx) * self.image.pixel(x0, y1).clone()
+                + fx * self.image.pixel(x1, y1).clone())
+    }
+}
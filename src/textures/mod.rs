@@ -0,0 +1,5 @@
+mod image_texture;
+mod texture;
+
+pub(crate) use image_texture::ImageTexture;
+pub(crate) use texture::{solid, ChannelTexture, ScaledTexture, SolidColor, Texture};
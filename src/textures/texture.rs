@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use crate::physics::{Color, Point3};
+
+pub(crate) trait Texture: std::fmt::Debug + Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+#[derive(Debug)]
+pub(crate) struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub(crate) fn new(albedo: Color) -> Self {
+        SolidColor { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.albedo.clone()
+    }
+}
+
+// Constant texture for scalar parameters.
+pub(crate) fn solid(value: f64) -> Arc<dyn Texture> {
+    Arc::new(SolidColor::new(Color::new(value, value, value)))
+}
+
+// Multiplies a texture by a constant factor, as glTF and MTL do with their texture maps.
+#[derive(Debug)]
+pub(crate) struct ScaledTexture {
+    texture: Arc<dyn Texture>,
+    factor: Color,
+}
+
+impl ScaledTexture {
+    pub(crate) fn new(texture: Arc<dyn Texture>, factor: Color) -> Self {
+        ScaledTexture { texture, factor }
+    }
+}
+
+impl Texture for ScaledTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.texture.value(u, v, p) * self.factor.clone()
+    }
+}
+
+// Broadcasts a single channel of a texture, for maps that pack several parameters together.
+#[derive(Debug)]
+pub(crate) struct ChannelTexture {
+    texture: Arc<dyn Texture>,
+    channel: usize,
+}
+
+impl ChannelTexture {
+    pub(crate) fn new(texture: Arc<dyn Texture>, channel: usize) -> Self {
+        ChannelTexture { texture, channel }
+    }
+}
+
+impl Texture for ChannelTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let c = self.texture.value(u, v, p);
+        let value = match self.channel {
+            0 => c.r(),
+            1 => c.g(),
+            _ => c.b(),
+        };
+        Color::new(value, value, value)
+    }
+}
@@ -1,6 +1,6 @@
-use std::f64::{consts::PI, INFINITY, NEG_INFINITY};
+use std::{cell::RefCell, f64::consts::PI};
 
-use rand::{random, thread_rng, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::physics::{Color, Vec3};
 
@@ -8,7 +8,7 @@ pub(crate) fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub(crate) struct Interval {
     pub(crate) min: f64,
     pub(crate) max: f64,
@@ -17,8 +17,8 @@ pub(crate) struct Interval {
 impl Default for Interval {
     fn default() -> Self {
         Self {
-            min: INFINITY,
-            max: NEG_INFINITY,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
         }
     }
 }
@@ -28,6 +28,20 @@ impl Interval {
         Interval { min, max }
     }
 
+    // Creates the interval tightly enclosing the two input intervals.
+    pub(crate) fn enclosing(a: &Interval, b: &Interval) -> Self {
+        Interval::new(a.min.min(b.min), a.max.max(b.max))
+    }
+
+    pub(crate) fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    pub(crate) fn expand(&self, delta: f64) -> Self {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+
     pub(crate) fn surrounds(&self, x: f64) -> bool {
         self.min < x && x < self.max
     }
@@ -43,12 +57,38 @@ impl Interval {
     }
 }
 
+// Deterministic value in [0, 1) derived from the bits of the inputs. Used where the same random
+// decision has to come out the same every time it is made for a given ray.
+pub(crate) fn hash_f64(values: &[f64]) -> f64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for value in values {
+        h ^= value.to_bits();
+        h = h.wrapping_mul(0x100000001b3);
+        h ^= h >> 29;
+    }
+    h = (h ^ (h >> 32)).wrapping_mul(0xd6e8feb86659fd93);
+    h ^= h >> 32;
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+thread_local! {
+    // Each thread draws its own random numbers, seeded from the operating system until it is
+    // given a seed.
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+// Restarts the calling thread's random numbers from `seed`, so that what it draws next comes out
+// the same every run.
+pub(crate) fn seed_random(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
 pub(crate) fn random_f64() -> f64 {
-    random()
+    RNG.with(|rng| rng.borrow_mut().gen())
 }
 
 pub(crate) fn random_f64_in_interval(min: f64, max: f64) -> f64 {
-    thread_rng().gen_range(min..max)
+    RNG.with(|rng| rng.borrow_mut().gen_range(min..max))
 }
 
 fn random_vec3() -> Vec3 {